@@ -0,0 +1,275 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::materials::BlendMode;
+use crate::settings::StarfieldSettings;
+use crate::speed::{FAST_FORWARD, SLOW_MOTION, SPEED_LIMITS, SPEED_STEP};
+use crate::theme::Theme;
+
+pub struct ActionsPlugin;
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Action>().add_system(apply_actions);
+    }
+}
+
+/// Everything the user can ask the starfield to do, whether through a key, a gamepad or the command palette.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    ToggleBlendMode,
+    ToggleLightTheme,
+    CycleRetroTheme,
+    /// Moves on to the next theme, through the built in presets and then any custom ones.
+    CycleTheme,
+    CycleMotionMode,
+    ToggleCrt,
+    TogglePixelArt,
+    ExportWallpaper,
+    Screenshot,
+    PrintShareCode,
+    Undo,
+    Redo,
+    ToggleThemeEditor,
+    ToggleSettingsPanel,
+    ToggleStarEditor,
+    ExportStarsJson,
+    ExportStarsCsv,
+    ToggleMerging,
+    ToggleTrails,
+    TogglePause,
+    /// Held down to speed the field up, tapped twice for a boost. Flies forward while spectating.
+    Warp,
+    ToggleSpectator,
+    ReturnToDefaultView,
+    FlyBack,
+    FlyLeft,
+    FlyRight,
+    ToggleFlightRecording,
+    HyperspaceJump,
+    ToggleWarpDrive,
+    ToggleTunnel,
+    ToggleConstellations,
+    ToggleDodgeGame,
+    ToggleSteering,
+    ToggleConverge,
+    RandomizeEverything,
+    TogglePhotoMode,
+    SpeedUp,
+    SlowDown,
+    /// Drops to slow motion, or back to normal speed if already there.
+    SlowMotion,
+    /// Jumps to fast forward, or back to normal speed if already there.
+    FastForward,
+    ToggleParallax,
+    ToggleCameraShake,
+    ToggleBpmSync,
+    TapTempo,
+    ToggleDepthHeatmap,
+    SaveScene,
+    ResetBindings,
+}
+
+impl Action {
+    pub const ALL: [Action; 47] = [
+        Action::ToggleBlendMode,
+        Action::ToggleLightTheme,
+        Action::CycleRetroTheme,
+        Action::CycleTheme,
+        Action::CycleMotionMode,
+        Action::ToggleCrt,
+        Action::TogglePixelArt,
+        Action::ExportWallpaper,
+        Action::Screenshot,
+        Action::PrintShareCode,
+        Action::Undo,
+        Action::Redo,
+        Action::ToggleThemeEditor,
+        Action::ToggleSettingsPanel,
+        Action::ToggleStarEditor,
+        Action::ExportStarsJson,
+        Action::ExportStarsCsv,
+        Action::ToggleMerging,
+        Action::ToggleTrails,
+        Action::TogglePause,
+        Action::Warp,
+        Action::ToggleSpectator,
+        Action::ReturnToDefaultView,
+        Action::FlyBack,
+        Action::FlyLeft,
+        Action::FlyRight,
+        Action::ToggleFlightRecording,
+        Action::HyperspaceJump,
+        Action::ToggleWarpDrive,
+        Action::ToggleTunnel,
+        Action::ToggleConstellations,
+        Action::ToggleDodgeGame,
+        Action::ToggleSteering,
+        Action::ToggleConverge,
+        Action::RandomizeEverything,
+        Action::TogglePhotoMode,
+        Action::SpeedUp,
+        Action::SlowDown,
+        Action::SlowMotion,
+        Action::FastForward,
+        Action::ToggleParallax,
+        Action::ToggleCameraShake,
+        Action::ToggleBpmSync,
+        Action::TapTempo,
+        Action::ToggleDepthHeatmap,
+        Action::SaveScene,
+        Action::ResetBindings,
+    ];
+
+    /// What the action is called in the UI.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::ToggleBlendMode => "Toggle additive blending",
+            Action::ToggleLightTheme => "Toggle light theme",
+            Action::CycleRetroTheme => "Cycle retro phosphor themes",
+            Action::CycleTheme => "Cycle themes",
+            Action::CycleMotionMode => "Cycle motion modes",
+            Action::ToggleCrt => "Toggle CRT effect",
+            Action::TogglePixelArt => "Toggle pixel art mode",
+            Action::ExportWallpaper => "Export wallpaper",
+            Action::Screenshot => "Take a screenshot",
+            Action::PrintShareCode => "Print share code",
+            Action::Undo => "Undo settings change",
+            Action::Redo => "Redo settings change",
+            Action::ToggleThemeEditor => "Toggle theme editor",
+            Action::ToggleSettingsPanel => "Toggle settings panel",
+            Action::ToggleStarEditor => "Toggle star placing mode",
+            Action::ExportStarsJson => "Export stars as JSON",
+            Action::ExportStarsCsv => "Export stars as CSV",
+            Action::ToggleMerging => "Toggle star merging",
+            Action::ToggleTrails => "Toggle warp trails",
+            Action::TogglePause => "Pause or resume",
+            Action::Warp => "Warp",
+            Action::ToggleSpectator => "Toggle spectator camera",
+            Action::ReturnToDefaultView => "Return to the default view",
+            Action::FlyBack => "Fly back",
+            Action::FlyLeft => "Fly left",
+            Action::FlyRight => "Fly right",
+            Action::ToggleFlightRecording => "Start or stop recording a flight path",
+            Action::HyperspaceJump => "Jump to hyperspace",
+            Action::ToggleWarpDrive => "Engage or disengage the warp drive",
+            Action::ToggleTunnel => "Toggle wormhole tunnel",
+            Action::ToggleConstellations => "Toggle constellations",
+            Action::ToggleDodgeGame => "Toggle dodge game",
+            Action::ToggleSteering => "Toggle steering",
+            Action::ToggleConverge => "Converge stars on the middle",
+            Action::RandomizeEverything => "Randomize everything",
+            Action::TogglePhotoMode => "Toggle photo mode",
+            Action::SpeedUp => "Speed up",
+            Action::SlowDown => "Slow down",
+            Action::SlowMotion => "Toggle slow motion",
+            Action::FastForward => "Toggle fast forward",
+            Action::ToggleParallax => "Toggle mouse parallax",
+            Action::ToggleCameraShake => "Toggle camera shake",
+            Action::ToggleBpmSync => "Toggle BPM sync",
+            Action::TapTempo => "Tap tempo",
+            Action::ToggleDepthHeatmap => "Toggle depth heatmap",
+            Action::SaveScene => "Save field as a scene",
+            Action::ResetBindings => "Reset key and button bindings to defaults",
+        }
+    }
+}
+
+/// Handles the actions that just flip a setting or swap the theme.
+/// Anything more involved is handled by the module it belongs to.
+fn apply_actions(
+    mut actions: EventReader<Action>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+) {
+    for action in actions.iter() {
+        match action {
+            Action::ToggleBlendMode => {
+                settings.blend_mode = match settings.blend_mode {
+                    BlendMode::Alpha => BlendMode::Additive,
+                    BlendMode::Additive => BlendMode::Alpha,
+                };
+            }
+            Action::ToggleLightTheme => {
+                *theme = if *theme == Theme::LIGHT {
+                    Theme::CLASSIC
+                } else {
+                    Theme::LIGHT
+                };
+            }
+            Action::CycleRetroTheme => {
+                *theme = if *theme == Theme::PHOSPHOR_GREEN {
+                    Theme::PHOSPHOR_AMBER
+                } else if *theme == Theme::PHOSPHOR_AMBER {
+                    Theme::CLASSIC
+                } else {
+                    Theme::PHOSPHOR_GREEN
+                };
+            }
+            Action::CycleMotionMode => settings.motion = settings.motion.next(),
+            Action::ToggleCrt => settings.crt.enabled = !settings.crt.enabled,
+            Action::TogglePixelArt => settings.pixel_art.enabled = !settings.pixel_art.enabled,
+            Action::ToggleMerging => settings.merge.enabled = !settings.merge.enabled,
+            Action::ToggleParallax => settings.parallax.enabled = !settings.parallax.enabled,
+            Action::ToggleCameraShake => settings.shake.enabled = !settings.shake.enabled,
+            Action::ToggleBpmSync => settings.bpm.enabled = !settings.bpm.enabled,
+            Action::ToggleTunnel => settings.tunnel.enabled = !settings.tunnel.enabled,
+            Action::ToggleConstellations => {
+                settings.constellations.enabled = !settings.constellations.enabled;
+            }
+            Action::SpeedUp | Action::SlowDown => {
+                let step = if *action == Action::SpeedUp {
+                    SPEED_STEP
+                } else {
+                    1.0 / SPEED_STEP
+                };
+                let (min, max) = SPEED_LIMITS;
+                settings.speed.multiplier = (settings.speed.multiplier * step).clamp(min, max);
+            }
+            Action::SlowMotion | Action::FastForward => {
+                let preset = if *action == Action::SlowMotion {
+                    SLOW_MOTION
+                } else {
+                    FAST_FORWARD
+                };
+                settings.speed.multiplier = if settings.speed.multiplier == preset {
+                    1.0
+                } else {
+                    preset
+                };
+            }
+            Action::CycleTheme
+            | Action::ExportWallpaper
+            | Action::Screenshot
+            | Action::PrintShareCode
+            | Action::Undo
+            | Action::Redo
+            | Action::ToggleThemeEditor
+            | Action::ToggleSettingsPanel
+            | Action::ToggleStarEditor
+            | Action::ToggleTrails
+            | Action::ExportStarsJson
+            | Action::ExportStarsCsv
+            | Action::TogglePause
+            | Action::Warp
+            | Action::ToggleSpectator
+            | Action::ReturnToDefaultView
+            | Action::FlyBack
+            | Action::FlyLeft
+            | Action::FlyRight
+            | Action::ToggleFlightRecording
+            | Action::HyperspaceJump
+            | Action::ToggleWarpDrive
+            | Action::ToggleDodgeGame
+            | Action::ToggleSteering
+            | Action::ToggleConverge
+            | Action::RandomizeEverything
+            | Action::TogglePhotoMode
+            | Action::TapTempo
+            | Action::ToggleDepthHeatmap
+            | Action::SaveScene
+            | Action::ResetBindings => {}
+        }
+    }
+}
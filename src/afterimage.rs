@@ -0,0 +1,153 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::theme::Theme;
+use crate::Star;
+
+/// Number of afterimages each star leaves behind.
+/// These are recycled, so this is also the most that can be visible for a star at once.
+const AFTERIMAGE_COUNT: usize = 4;
+
+/// Opacity of a freshly dropped afterimage.
+const AFTERIMAGE_STRENGTH: f32 = 0.6;
+
+pub struct AfterimagePlugin;
+impl Plugin for AfterimagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PreUpdate, clear_afterimages)
+            .add_system(add_afterimages)
+            .add_system(drop_afterimages)
+            .add_system(fade_afterimages);
+    }
+}
+
+/// A fading copy of a star left at one of its previous positions, like phosphor decaying on an old screen.
+#[derive(Component)]
+struct Afterimage {
+    owner: Entity,
+    age: f32,
+    /// The owning star's spot in the tint range, so the afterimage fades from the same color.
+    tint: f32,
+}
+
+/// Keeps track of a star's afterimages and which one gets moved next.
+#[derive(Component)]
+struct AfterimageEmitter {
+    afterimages: [Entity; AFTERIMAGE_COUNT],
+    next: usize,
+    timer: f32,
+}
+
+/// Removes every afterimage when the theme changes, as well as any whose star no longer exists.
+fn clear_afterimages(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    emitters: Query<Entity, With<AfterimageEmitter>>,
+    afterimages: Query<(Entity, &Afterimage)>,
+) {
+    for (entity, afterimage) in afterimages.iter() {
+        if theme.is_changed() || emitters.get(afterimage.owner).is_err() {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    if theme.is_changed() {
+        for star in emitters.iter() {
+            commands.entity(star).remove::<AfterimageEmitter>();
+        }
+    }
+}
+
+/// Spawns the afterimages for every star that doesn't have them yet.
+#[allow(clippy::type_complexity)]
+fn add_afterimages(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    query: Query<(Entity, &Star, &Mesh2dHandle), Without<AfterimageEmitter>>,
+) {
+    if theme.afterimage_decay <= 0.0 {
+        return;
+    }
+
+    for (entity, star, mesh) in query.iter() {
+        // They start out fully decayed, so they stay invisible until they're dropped behind the star.
+        let afterimages = [(); AFTERIMAGE_COUNT].map(|_| {
+            commands
+                .spawn_bundle(MaterialMesh2dBundle::<StarMaterial> {
+                    mesh: mesh.clone(),
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(Afterimage {
+                    owner: entity,
+                    age: theme.afterimage_decay,
+                    tint: star.tint,
+                })
+                .id()
+        });
+
+        commands.entity(entity).insert(AfterimageEmitter {
+            afterimages,
+            next: 0,
+            timer: 0.0,
+        });
+    }
+}
+
+/// Moves the oldest afterimage of each star to where the star is now, spaced evenly over the decay time.
+fn drop_afterimages(
+    time: Res<Time>,
+    theme: Res<Theme>,
+    mut stars: Query<(&mut AfterimageEmitter, &Transform), With<Star>>,
+    mut afterimages: Query<(&mut Afterimage, &mut Transform), Without<Star>>,
+) {
+    let interval = theme.afterimage_decay / AFTERIMAGE_COUNT as f32;
+
+    for (mut emitter, star_transform) in stars.iter_mut() {
+        emitter.timer += time.delta_seconds();
+        if emitter.timer < interval {
+            continue;
+        }
+
+        emitter.timer = 0.0;
+        let entity = emitter.afterimages[emitter.next];
+        emitter.next = (emitter.next + 1) % AFTERIMAGE_COUNT;
+
+        if let Ok((mut afterimage, mut transform)) = afterimages.get_mut(entity) {
+            afterimage.age = 0.0;
+
+            // Just behind the star, so it never covers it up.
+            transform.translation = star_transform.translation - Vec3::Z * 0.01;
+        }
+    }
+}
+
+/// Ages afterimages and fades them out.
+fn fade_afterimages(
+    time: Res<Time>,
+    theme: Res<Theme>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    mut query: Query<(&mut Afterimage, &mut Handle<StarMaterial>, &mut Visibility)>,
+) {
+    for (mut afterimage, mut material, mut visibility) in query.iter_mut() {
+        afterimage.age += time.delta_seconds();
+
+        let remaining = 1.0 - afterimage.age / theme.afterimage_decay;
+        visibility.is_visible = remaining > 0.0;
+        if !visibility.is_visible {
+            continue;
+        }
+
+        // The pool quantizes alpha as well, so the fade happens in a few steps sharing a few materials.
+        let alpha = remaining * AFTERIMAGE_STRENGTH;
+        let color = theme.star_color(afterimage.tint);
+        let faded = pool.get(*color.clone().set_a(alpha), &mut materials);
+        if *material != faded {
+            *material = faded;
+        }
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Every so often a tumbling asteroid flies past, to break up the uniformity of the star stream.
+//!
+//! Asteroids move like stars, flying out from the middle of the field, and use a star material so parallax,
+//! the spectator and the tunnel all treat them the same. Each one gets its own lumpy outline.
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::depth::depth_to_z;
+use crate::dodge::Dodge;
+use crate::materials::{BlendMode, StarMaterial};
+use crate::settings::StarfieldSettings;
+use crate::sim::{Field, StarfieldRng};
+use crate::speed::SimulationSpeed;
+use crate::theme::Theme;
+use crate::PHYSICS_STEP;
+
+/// How many points go around an asteroid's outline.
+const OUTLINE_POINTS: usize = 9;
+
+/// How far in from its full radius each point of the outline can be pulled, as a fraction of the radius.
+const LUMPINESS: f32 = 0.35;
+
+/// Radius of an asteroid at the nearest depth, in pixels, before it grows on its way out.
+const SIZE_RANGE: std::ops::RangeInclusive<f32> = 6.0..=18.0;
+
+/// Fastest an asteroid spins, in radians per second.
+const MAX_SPIN: f32 = 2.0;
+
+/// How far from the background towards the star color asteroids are drawn, so they read as dark rock.
+const ROCK_BRIGHTNESS: f32 = 0.3;
+
+/// Longest an asteroid takes to reach the camera at normal speed, in seconds.
+/// Ones heading straight at the camera barely move across the screen, so they close in over time instead.
+const APPROACH_TIME: f32 = 8.0;
+
+pub struct AsteroidPlugin;
+impl Plugin for AsteroidPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AsteroidSettings>()
+            .add_event::<AsteroidArrived>()
+            .add_startup_system(create_asteroid_material)
+            .add_system(spawn_asteroids)
+            .add_system(apply_asteroid_theme)
+            .add_system_to_stage(CoreStage::PostUpdate, move_asteroids);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AsteroidSettings {
+    pub enabled: bool,
+    /// On average how many asteroids fly past a minute at normal speed.
+    pub density: f32,
+}
+
+impl Default for AsteroidSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            density: 6.0,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Asteroid {
+    base_speed: f32,
+    /// Spin in radians per second, either way round.
+    spin: f32,
+    /// Radius at the nearest depth, already scaled down for how deep this one is.
+    size: f32,
+    /// Seconds since it was spawned, at normal speed.
+    age: f32,
+}
+
+/// Sent when an asteroid reaches the camera, just before it's cleared away.
+pub struct AsteroidArrived {
+    /// Where it was on screen, relative to the vanishing point.
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+/// Every asteroid shares one material, so the theme only has to be applied once.
+struct AsteroidMaterial(Handle<StarMaterial>);
+
+fn rock_color(theme: &Theme) -> Color {
+    let background = Vec4::from(theme.clear_color.as_rgba_f32());
+    let star = Vec4::from(theme.star_color(0.5).as_rgba_f32());
+    let [r, g, b, _] = background.lerp(star, ROCK_BRIGHTNESS).to_array();
+    Color::rgb(r, g, b)
+}
+
+fn create_asteroid_material(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+) {
+    // Rock isn't see-through, whatever blend mode the stars use.
+    let material = StarMaterial::new(rock_color(&theme), BlendMode::Alpha);
+    commands.insert_resource(AsteroidMaterial(materials.add(material)));
+}
+
+fn apply_asteroid_theme(
+    theme: Res<Theme>,
+    material: Res<AsteroidMaterial>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    if let Some(material) = materials.get_mut(&material.0) {
+        material.color = rock_color(&theme);
+    }
+}
+
+/// A unit sized lumpy polygon, fanned out from its middle.
+fn asteroid_mesh(rng: &mut impl Rng) -> Mesh {
+    let mut positions = vec![[0.0, 0.0, 0.0]];
+    for point in 0..OUTLINE_POINTS {
+        let angle = point as f32 / OUTLINE_POINTS as f32 * std::f32::consts::TAU;
+        let radius = 1.0 - rng.gen_range(0.0..=LUMPINESS);
+        positions.push([angle.cos() * radius, angle.sin() * radius, 0.0]);
+    }
+
+    let uvs: Vec<[f32; 2]> = positions
+        .iter()
+        .map(|[x, y, _]| [x * 0.5 + 0.5, 0.5 - y * 0.5])
+        .collect();
+    let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+
+    let outline = OUTLINE_POINTS as u32;
+    let indices = (0..outline)
+        .flat_map(|point| [0, point + 1, (point + 1) % outline + 1])
+        .collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Sends out new asteroids at random, more often the faster the field is going.
+#[allow(clippy::too_many_arguments)]
+fn spawn_asteroids(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    speed: Res<SimulationSpeed>,
+    dodge: Option<Res<Dodge>>,
+    material: Res<AsteroidMaterial>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    field: Res<Field>,
+    mut rng: ResMut<StarfieldRng>,
+) {
+    // The dodge game brings its own asteroids, even with them turned off for the screensaver.
+    // Runs without the dodge game, like benchmarks, don't have one at all.
+    let density = if dodge.map_or(false, |dodge| dodge.active()) {
+        settings.dodge.asteroid_density
+    } else if settings.asteroids.enabled {
+        settings.asteroids.density
+    } else {
+        0.0
+    };
+    if density <= 0.0 {
+        return;
+    }
+
+    let chance = density / 60.0 * time.delta_seconds() * speed.current;
+    let rng = &mut rng.effects;
+    if rng.gen_range(0.0..1.0) >= chance {
+        return;
+    }
+
+    let depth = field.random_depth(rng);
+
+    // Far asteroids are smaller, the same as they'd look through the parallax and spectator views.
+    let size = rng.gen_range(SIZE_RANGE) * (1.0 - depth * 0.6);
+    let position = field.respawn_position(rng).extend(depth_to_z(depth));
+    let rotation = Quat::from_rotation_z(rng.gen_range(0.0..std::f32::consts::TAU));
+
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(asteroid_mesh(rng)).into(),
+            material: material.0.clone(),
+            transform: Transform::from_translation(position)
+                .with_rotation(rotation)
+                .with_scale(Vec3::ZERO),
+            ..default()
+        })
+        .insert(Asteroid {
+            base_speed: field.random_base_speed(rng),
+            spin: rng.gen_range(-MAX_SPIN..=MAX_SPIN),
+            size,
+            age: 0.0,
+        });
+}
+
+/// Flies asteroids outwards like stars, growing as they come closer, and clears them away once they've passed.
+fn move_asteroids(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut arrived: EventWriter<AsteroidArrived>,
+    field: Res<Field>,
+    mut query: Query<(Entity, &mut Asteroid, &mut Transform, &Mesh2dHandle)>,
+) {
+    let delta = time.delta_seconds() * speed.current * speed.pulse * speed.audio;
+
+    for (entity, mut asteroid, mut transform, mesh) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        asteroid.age += delta;
+
+        // Whichever says it's closer, how far it's flown out or how long it's been coming.
+        let closeness = field
+            .extent_progress(position)
+            .max(asteroid.age / APPROACH_TIME);
+        let radius = asteroid.size * (0.5 + closeness);
+
+        if closeness >= 1.0 {
+            arrived.send(AsteroidArrived { position, radius });
+            meshes.remove(&mesh.0);
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let velocity = field.velocity(position, asteroid.base_speed, PHYSICS_STEP as f32);
+        transform.translation += (velocity * delta).extend(0.0);
+        transform.rotate_z(asteroid.spin * delta);
+        transform.scale = Vec3::new(radius, radius, 1.0);
+    }
+}
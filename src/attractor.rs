@@ -0,0 +1,202 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Now and then something massive, like a black hole, drifts through the field and bends the paths of passing stars.
+//!
+//! Attractors pull on every star, so stars curve towards them and whip around the far side. Anything that
+//! falls inside one is swallowed and recycled like a star that left the field. All that's drawn is a dark
+//! core inside a glowing accretion disc.
+
+use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::materials::{BlendMode, MaterialPool, StarMaterial};
+use crate::settings::StarfieldSettings;
+use crate::sim::{Field, StarfieldRng};
+use crate::speed::SimulationSpeed;
+use crate::star_mesh::StarMesh;
+
+/// Radius of an attractor's dark core, in pixels.
+const RADIUS_RANGE: std::ops::RangeInclusive<f32> = 12.0..=30.0;
+
+/// How much mass each square pixel of core is worth.
+const DENSITY: f32 = 3000.0;
+
+/// Keeps the pull from growing without limit right next to the core, in pixels.
+/// Without it stars grazing the core would be flung off at absurd speeds.
+const SOFTENING: f32 = 40.0;
+
+/// Most attractors in the field at once.
+const MAX_ATTRACTORS: usize = 2;
+
+/// How far past the field's edge attractors come in from and leave by, as a fraction of its extent.
+const EDGE_MARGIN: f32 = 1.3;
+
+/// The accretion disc's rings from the outside in, as their size relative to the core and their opacity.
+const GLOW_RINGS: [(f32, f32); 3] = [(3.5, 0.08), (2.4, 0.16), (1.5, 0.3)];
+
+/// Behind even the farthest stars, but in front of the nebula.
+const ATTRACTOR_Z: f32 = 0.5;
+
+pub struct AttractorPlugin;
+impl Plugin for AttractorPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AttractorSettings>()
+            .add_startup_system(create_core_material)
+            .add_system(spawn_attractors)
+            .add_system(drift_attractors);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttractorSettings {
+    pub enabled: bool,
+    /// On average how many attractors drift in a minute at normal speed.
+    pub frequency: f32,
+    /// How hard attractors pull, 1.0 being normal.
+    pub strength: f32,
+    /// How fast attractors drift across the field, in pixels per second at normal speed.
+    pub drift_speed: f32,
+    /// Color of the glowing disc around the core.
+    pub glow_color: Color,
+}
+
+impl Default for AttractorSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: 0.5,
+            strength: 1.0,
+            drift_speed: 25.0,
+            glow_color: Color::rgb(1.0, 0.6, 0.25),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Component)]
+pub struct Attractor {
+    pub mass: f32,
+    /// Radius of the core, anything that comes closer than this is swallowed.
+    pub radius: f32,
+    /// Which way and how fast it drifts at normal speed, in pixels per second.
+    velocity: Vec2,
+}
+
+/// How hard the attractors pull on something at `position`, in pixels per second squared.
+pub fn pull(position: Vec2, attractors: &[(Vec2, Attractor)]) -> Vec2 {
+    attractors
+        .iter()
+        .fold(Vec2::ZERO, |total, (center, attractor)| {
+            let offset = *center - position;
+            let distance_squared = offset.length_squared() + SOFTENING * SOFTENING;
+            total + offset * attractor.mass / (distance_squared * distance_squared.sqrt())
+        })
+}
+
+/// Whether something at `position` has fallen into any of the attractors.
+pub fn swallowed(position: Vec2, attractors: &[(Vec2, Attractor)]) -> bool {
+    attractors
+        .iter()
+        .any(|(center, attractor)| center.distance(position) < attractor.radius)
+}
+
+/// Every core shares one pitch black material.
+struct CoreMaterial(Handle<StarMaterial>);
+
+fn create_core_material(mut commands: Commands, mut materials: ResMut<Assets<StarMaterial>>) {
+    // Nothing shows through the core, whatever blend mode the stars use.
+    let material = StarMaterial::new(Color::BLACK, BlendMode::Alpha);
+    commands.insert_resource(CoreMaterial(materials.add(material)));
+}
+
+/// Sends in new attractors at random from just outside the field.
+#[allow(clippy::too_many_arguments)]
+fn spawn_attractors(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    speed: Res<SimulationSpeed>,
+    star_mesh: Res<StarMesh>,
+    core: Res<CoreMaterial>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    field: Res<Field>,
+    mut rng: ResMut<StarfieldRng>,
+    attractors: Query<(), With<Attractor>>,
+) {
+    let attractor_settings = settings.attractors;
+    if !attractor_settings.enabled || attractors.iter().count() >= MAX_ATTRACTORS {
+        return;
+    }
+
+    let chance = attractor_settings.frequency / 60.0 * time.delta_seconds() * speed.current;
+    let rng = &mut rng.effects;
+    if rng.gen_range(0.0..1.0) >= chance {
+        return;
+    }
+
+    // Comes in over one edge and drifts across to somewhere on the other side.
+    let extents = field.extents() * EDGE_MARGIN;
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    let direction = Vec2::new(angle.cos(), angle.sin());
+    let across = direction.perp() * rng.gen_range(-0.5..=0.5) * extents.min_element();
+    let start = across - direction * extents.max_element();
+
+    let radius = rng.gen_range(RADIUS_RANGE);
+    let attractor = Attractor {
+        mass: radius * radius * DENSITY * attractor_settings.strength,
+        radius,
+        velocity: direction * attractor_settings.drift_speed,
+    };
+
+    commands
+        .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
+            start.extend(ATTRACTOR_Z),
+        )))
+        .insert(attractor)
+        .with_children(|parent| {
+            for (index, (size, opacity)) in GLOW_RINGS.iter().enumerate() {
+                let color = *attractor_settings.glow_color.clone().set_a(*opacity);
+                parent.spawn_bundle(MaterialMesh2dBundle {
+                    mesh: star_mesh.0.clone().into(),
+                    material: pool.get(color, &mut materials),
+                    transform: Transform::from_xyz(0.0, 0.0, index as f32 * 0.01)
+                        .with_scale(Vec3::new(radius * size, radius * size, 1.0)),
+                    ..default()
+                });
+            }
+
+            parent.spawn_bundle(MaterialMesh2dBundle {
+                mesh: star_mesh.0.clone().into(),
+                material: core.0.clone(),
+                transform: Transform::from_xyz(0.0, 0.0, GLOW_RINGS.len() as f32 * 0.01)
+                    .with_scale(Vec3::new(radius, radius, 1.0)),
+                ..default()
+            });
+        });
+}
+
+/// Drifts attractors across the field, and clears them away once they've left it.
+fn drift_attractors(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    field: Res<Field>,
+    mut query: Query<(Entity, &Attractor, &mut Transform)>,
+) {
+    let delta = time.delta_seconds() * speed.current;
+    let extents = field.extents() * EDGE_MARGIN;
+
+    for (entity, attractor, mut transform) in query.iter_mut() {
+        transform.translation += (attractor.velocity * delta).extend(0.0);
+
+        // Only once it's on its way out, it starts out beyond the edge on the other side.
+        let position = transform.translation.truncate();
+        let leaving = position.dot(attractor.velocity) > 0.0;
+        if leaving && position.abs().cmpgt(extents).any() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
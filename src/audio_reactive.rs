@@ -0,0 +1,270 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Listens to the microphone and moves the field to whatever's playing.
+//!
+//! The input is split into bass, mids and treble with a couple of simple filters rather than a full FFT.
+//! Bass pushes the field faster and treble makes stars twinkle harder. Only built with the `audio-reactive`
+//! feature, since it needs to open an input device.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use bevy::prelude::*;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Sample, SampleFormat, Stream};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::StarfieldSettings;
+use crate::speed::SimulationSpeed;
+use crate::state;
+use crate::twinkle::TwinkleBoost;
+
+/// Everything below this is bass, in hertz.
+const BASS_CUTOFF: f32 = 200.0;
+
+/// Everything above this is treble, in hertz.
+const TREBLE_CUTOFF: f32 = 4000.0;
+
+/// How quickly levels fall back once the music gets quieter, higher is quicker. They rise straight away.
+const RELEASE: f32 = 6.0;
+
+pub struct AudioReactivePlugin;
+impl Plugin for AudioReactivePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AudioReactiveSettings>()
+            .init_resource::<AudioLevels>()
+            .init_non_send_resource::<AudioInput>()
+            .add_system(open_input)
+            .add_system(measure_levels.after(open_input))
+            .add_system(
+                react_to_levels
+                    .with_run_criteria(state::running)
+                    .after(measure_levels),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioReactiveSettings {
+    pub enabled: bool,
+    /// How much the input is amplified before it's measured, raise it for quiet rooms or microphones.
+    pub gain: f32,
+    /// How much faster the field moves at full bass.
+    pub bass_speed: f32,
+    /// How much harder stars twinkle at full treble, 0.0 to 1.0.
+    pub treble_twinkle: f32,
+}
+
+impl Default for AudioReactiveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            gain: 4.0,
+            bass_speed: 1.5,
+            treble_twinkle: 0.8,
+        }
+    }
+}
+
+/// How loud the input is overall and in each band right now, each from 0.0 to 1.0.
+#[derive(Clone, Copy, Default)]
+pub struct AudioLevels {
+    pub amplitude: f32,
+    pub bass: f32,
+    pub mid: f32,
+    pub treble: f32,
+}
+
+/// The open input stream, which has to stay on the main thread.
+#[derive(Default)]
+struct AudioInput {
+    stream: Option<Stream>,
+    bands: Arc<Mutex<Bands>>,
+}
+
+/// Sums of squared samples in each band since the levels were last measured, filled in by the input stream.
+#[derive(Default)]
+struct Bands {
+    sample_rate: f32,
+    low: f32,
+    below_treble: f32,
+    amplitude: f32,
+    bass: f32,
+    mid: f32,
+    treble: f32,
+    samples: u32,
+}
+
+impl Bands {
+    fn push(&mut self, sample: f32) {
+        // One pole low pass filters, the bands are whatever falls between them.
+        let smoothing =
+            |cutoff: f32| 1.0 - (-std::f32::consts::TAU * cutoff / self.sample_rate).exp();
+        let (bass_smoothing, treble_smoothing) = (smoothing(BASS_CUTOFF), smoothing(TREBLE_CUTOFF));
+        self.low += bass_smoothing * (sample - self.low);
+        self.below_treble += treble_smoothing * (sample - self.below_treble);
+
+        self.amplitude += sample * sample;
+        self.bass += self.low * self.low;
+        self.mid += (self.below_treble - self.low).powi(2);
+        self.treble += (sample - self.below_treble).powi(2);
+        self.samples += 1;
+    }
+
+    /// The root mean square of each band, amplitude then bass, mids and treble, starting over afterwards.
+    fn take(&mut self) -> Option<[f32; 4]> {
+        if self.samples == 0 {
+            return None;
+        }
+
+        let count = self.samples as f32;
+        let levels =
+            [self.amplitude, self.bass, self.mid, self.treble].map(|sum| (sum / count).sqrt());
+        self.amplitude = 0.0;
+        self.bass = 0.0;
+        self.mid = 0.0;
+        self.treble = 0.0;
+        self.samples = 0;
+        Some(levels)
+    }
+}
+
+/// Starts listening when audio reactivity is turned on, and stops when it's turned off.
+/// If the microphone can't be opened, turning it off and on again tries again.
+fn open_input(
+    settings: Res<StarfieldSettings>,
+    mut input: NonSendMut<AudioInput>,
+    mut failed: Local<bool>,
+) {
+    if !settings.audio_reactive.enabled {
+        input.stream = None;
+        *failed = false;
+        return;
+    }
+    if input.stream.is_some() || *failed {
+        return;
+    }
+
+    match listen(&input.bands) {
+        Ok(stream) => {
+            info!("Listening to the microphone");
+            input.stream = Some(stream);
+        }
+        Err(error) => {
+            warn!("Couldn't listen to the microphone, {error}");
+            *failed = true;
+        }
+    }
+}
+
+/// Opens the default input device and feeds everything it hears into the bands.
+fn listen(bands: &Arc<Mutex<Bands>>) -> Result<Stream, String> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or("there's no input device")?;
+    let config = device
+        .default_input_config()
+        .map_err(|error| error.to_string())?;
+
+    let channels = config.channels().max(1) as usize;
+    bands
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .sample_rate = config.sample_rate().0 as f32;
+
+    let on_error = |error| warn!("Lost the microphone, {error}");
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => {
+            let bands = bands.clone();
+            device.build_input_stream(
+                &config.into(),
+                move |data: &[f32], _| push_frames(&bands, data, channels),
+                on_error,
+            )
+        }
+        SampleFormat::I16 => {
+            let bands = bands.clone();
+            device.build_input_stream(
+                &config.into(),
+                move |data: &[i16], _| push_frames(&bands, data, channels),
+                on_error,
+            )
+        }
+        SampleFormat::U16 => {
+            let bands = bands.clone();
+            device.build_input_stream(
+                &config.into(),
+                move |data: &[u16], _| push_frames(&bands, data, channels),
+                on_error,
+            )
+        }
+    }
+    .map_err(|error| error.to_string())?;
+
+    stream.play().map_err(|error| error.to_string())?;
+    Ok(stream)
+}
+
+/// Mixes each frame down to mono and adds it to the bands.
+fn push_frames<T: Sample>(bands: &Mutex<Bands>, data: &[T], channels: usize) {
+    let mut bands = bands.lock().unwrap_or_else(PoisonError::into_inner);
+    for frame in data.chunks(channels) {
+        let mono = frame.iter().map(Sample::to_f32).sum::<f32>() / frame.len() as f32;
+        bands.push(mono);
+    }
+}
+
+/// Turns what the input heard since last frame into levels, which jump up with the music and ease back down.
+fn measure_levels(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    input: NonSend<AudioInput>,
+    mut levels: ResMut<AudioLevels>,
+) {
+    let heard = match input.stream {
+        Some(_) => input
+            .bands
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take(),
+        None => Some([0.0; 4]),
+    };
+    let heard = match heard {
+        Some(heard) => heard,
+        None => return,
+    };
+
+    let gain = settings.audio_reactive.gain;
+    let release = 1.0 - (-RELEASE * time.delta_seconds()).exp();
+    let follow = |level: &mut f32, heard: f32| {
+        let heard = (heard * gain).clamp(0.0, 1.0);
+        *level = if heard > *level {
+            heard
+        } else {
+            *level + (heard - *level) * release
+        };
+    };
+
+    follow(&mut levels.amplitude, heard[0]);
+    follow(&mut levels.bass, heard[1]);
+    follow(&mut levels.mid, heard[2]);
+    follow(&mut levels.treble, heard[3]);
+}
+
+/// Bass speeds the field up and treble sets stars twinkling.
+fn react_to_levels(
+    settings: Res<StarfieldSettings>,
+    levels: Res<AudioLevels>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut twinkle: ResMut<TwinkleBoost>,
+) {
+    let audio = settings.audio_reactive;
+    if !audio.enabled {
+        speed.audio = 1.0;
+        twinkle.0 = 0.0;
+        return;
+    }
+
+    speed.audio = 1.0 + levels.bass * audio.bass_speed;
+    twinkle.0 = levels.treble * audio.treble_twinkle;
+}
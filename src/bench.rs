@@ -0,0 +1,210 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Runs the field at a series of star counts for a fixed number of frames each, then prints how long frames
+//! took and quits.
+//!
+//! Each count gets a moment to settle before it's measured, so spawning the new stars doesn't skew it. Run
+//! with `--bench` before and after touching how stars move or are drawn to see what it cost. Frames are
+//! rendered to a window, with vsync off, so drawing is measured along with everything else. Adding
+//! `--headless` skips the window and renderer, for measuring just the simulation or running on machines
+//! without a display.
+
+use bevy::app::AppExit;
+use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::settings::StarfieldSettings;
+
+/// The star counts measured unless told otherwise.
+pub const DEFAULT_STAR_COUNTS: [u32; 4] = [1_000, 10_000, 50_000, 100_000];
+
+/// Frames measured at each star count unless told otherwise.
+pub const DEFAULT_FRAMES: u32 = 600;
+
+/// Frames left unmeasured after each change of star count.
+const WARMUP_FRAMES: u32 = 60;
+
+pub struct BenchPlugin {
+    pub star_counts: Vec<u32>,
+    /// How many frames are measured at each star count.
+    pub frames: u32,
+    /// Prints the report as JSON instead of a table.
+    pub json: bool,
+}
+
+impl Plugin for BenchPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Bench {
+            star_counts: self.star_counts.clone(),
+            frames: self.frames.max(1),
+            json: self.json,
+            stage: 0,
+            warmup: WARMUP_FRAMES,
+            frame_times: Vec::new(),
+            stages: Vec::new(),
+        })
+        .add_plugin(FrameTimeDiagnosticsPlugin)
+        .add_system_to_stage(CoreStage::Last, run_bench);
+    }
+}
+
+struct Bench {
+    star_counts: Vec<u32>,
+    frames: u32,
+    json: bool,
+    /// Which star count is being measured.
+    stage: usize,
+    /// Frames left to settle before measuring starts.
+    warmup: u32,
+    /// Seconds each frame took so far at this star count.
+    frame_times: Vec<f64>,
+    stages: Vec<StageReport>,
+}
+
+/// Frame times at one star count, all in milliseconds.
+#[derive(Serialize)]
+struct StageReport {
+    stars: u32,
+    frames: usize,
+    mean: f64,
+    median: f64,
+    p95: f64,
+    p99: f64,
+    min: f64,
+    max: f64,
+    fps: f64,
+}
+
+impl StageReport {
+    fn new(stars: u32, frame_times: &[f64]) -> Self {
+        let mut sorted: Vec<f64> = frame_times.iter().map(|seconds| seconds * 1000.0).collect();
+        sorted.sort_by(f64::total_cmp);
+
+        let percentile = |fraction: f64| {
+            let index = ((sorted.len() - 1) as f64 * fraction).round() as usize;
+            sorted[index]
+        };
+        let mean = sorted.iter().sum::<f64>() / sorted.len() as f64;
+
+        Self {
+            stars,
+            frames: sorted.len(),
+            mean,
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            fps: 1000.0 / mean,
+        }
+    }
+}
+
+/// Steps through the star counts, measuring each in turn, and reports once they're all done.
+fn run_bench(
+    mut bench: ResMut<Bench>,
+    diagnostics: Res<Diagnostics>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let stars = match bench.star_counts.get(bench.stage) {
+        Some(stars) => *stars,
+        None => return,
+    };
+    if settings.star_count != stars {
+        settings.star_count = stars;
+        return;
+    }
+    if bench.warmup > 0 {
+        bench.warmup -= 1;
+        return;
+    }
+
+    let frame_time = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|diagnostic| diagnostic.value());
+    if let Some(frame_time) = frame_time {
+        bench.frame_times.push(frame_time);
+    }
+    if bench.frame_times.len() < bench.frames as usize {
+        return;
+    }
+
+    let stage = StageReport::new(stars, &bench.frame_times);
+    info!(
+        "Measured {stars} stars, {:.2} ms a frame on average",
+        stage.mean
+    );
+    bench.stages.push(stage);
+    bench.frame_times.clear();
+    bench.warmup = WARMUP_FRAMES;
+    bench.stage += 1;
+
+    if bench.stage == bench.star_counts.len() {
+        report(&bench);
+        exit.send(AppExit);
+    }
+}
+
+fn report(bench: &Bench) {
+    if bench.json {
+        match serde_json::to_string_pretty(&bench.stages) {
+            Ok(json) => println!("{json}"),
+            Err(error) => error!("Couldn't write the benchmark report, {error}"),
+        }
+        return;
+    }
+
+    println!(
+        "{:>9} {:>7} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>8}",
+        "stars", "frames", "mean ms", "median", "p95", "p99", "min", "max", "fps"
+    );
+    for stage in &bench.stages {
+        println!(
+            "{:>9} {:>7} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>8.1}",
+            stage.stars,
+            stage.frames,
+            stage.mean,
+            stage.median,
+            stage.p95,
+            stage.p99,
+            stage.min,
+            stage.max,
+            stage.fps
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_statistics() {
+        // A hundred frames of 1 to 100 ms, out of order.
+        let frame_times: Vec<f64> = (1..=100)
+            .rev()
+            .map(|milliseconds| milliseconds as f64 / 1000.0)
+            .collect();
+        let stage = StageReport::new(1000, &frame_times);
+
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert_eq!(stage.frames, 100);
+        assert!(close(stage.mean, 50.5));
+        assert!(close(stage.median, 51.0));
+        assert!(close(stage.p95, 95.0));
+        assert!(close(stage.p99, 99.0));
+        assert!(close(stage.min, 1.0));
+        assert!(close(stage.max, 100.0));
+        assert!(close(stage.fps, 1000.0 / 50.5));
+    }
+
+    #[test]
+    fn single_frame_stage() {
+        let stage = StageReport::new(10, &[0.02]);
+        assert_eq!(stage.frames, 1);
+        assert_eq!(stage.median, stage.p99);
+        assert_eq!(stage.min, stage.max);
+    }
+}
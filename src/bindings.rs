@@ -0,0 +1,391 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Turns input from every device into actions, so features only ever deal with actions.
+//!
+//! Keyboard and gamepad bindings can be changed in a `bindings.ron` file in the config directory.
+//! Anything else that wants to drive the field, like a MIDI controller or OSC messages, only has to
+//! send `Action` events.
+
+use std::fs;
+
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_inspector_egui::bevy_egui::EguiContext;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::palette::CommandPalette;
+use crate::persist::config_path;
+use crate::state;
+
+const FILE_NAME: &str = "bindings.ron";
+
+/// Which key triggers which action, unless the user has rebound them.
+const DEFAULT_KEYS: [(KeyCode, Action); 40] = [
+    (KeyCode::B, Action::ToggleBlendMode),
+    (KeyCode::L, Action::ToggleLightTheme),
+    (KeyCode::R, Action::CycleRetroTheme),
+    (KeyCode::Y, Action::CycleTheme),
+    (KeyCode::O, Action::CycleMotionMode),
+    (KeyCode::V, Action::ToggleCrt),
+    (KeyCode::X, Action::TogglePixelArt),
+    (KeyCode::F10, Action::ExportWallpaper),
+    (KeyCode::F12, Action::Screenshot),
+    (KeyCode::F9, Action::PrintShareCode),
+    (KeyCode::F2, Action::ToggleThemeEditor),
+    (KeyCode::Tab, Action::ToggleSettingsPanel),
+    (KeyCode::E, Action::ToggleStarEditor),
+    (KeyCode::F8, Action::ExportStarsJson),
+    (KeyCode::M, Action::ToggleMerging),
+    (KeyCode::K, Action::ToggleTrails),
+    (KeyCode::Space, Action::TogglePause),
+    (KeyCode::Equals, Action::SpeedUp),
+    (KeyCode::Minus, Action::SlowDown),
+    (KeyCode::Comma, Action::SlowMotion),
+    (KeyCode::Period, Action::FastForward),
+    (KeyCode::P, Action::ToggleParallax),
+    (KeyCode::T, Action::TapTempo),
+    (KeyCode::F3, Action::ToggleDepthHeatmap),
+    (KeyCode::F6, Action::SaveScene),
+    (KeyCode::F7, Action::TogglePhotoMode),
+    (KeyCode::W, Action::Warp),
+    (KeyCode::A, Action::FlyLeft),
+    (KeyCode::S, Action::FlyBack),
+    (KeyCode::D, Action::FlyRight),
+    (KeyCode::C, Action::ToggleSpectator),
+    (KeyCode::Home, Action::ReturnToDefaultView),
+    (KeyCode::F5, Action::ToggleFlightRecording),
+    (KeyCode::J, Action::HyperspaceJump),
+    (KeyCode::Q, Action::ToggleWarpDrive),
+    (KeyCode::U, Action::ToggleTunnel),
+    (KeyCode::Z, Action::ToggleConstellations),
+    (KeyCode::G, Action::ToggleDodgeGame),
+    (KeyCode::H, Action::ToggleSteering),
+    (KeyCode::N, Action::RandomizeEverything),
+];
+
+/// Which gamepad button triggers which action, unless the user has rebound them.
+const DEFAULT_BUTTONS: [(GamepadButtonType, Action); 7] = [
+    (GamepadButtonType::Start, Action::TogglePause),
+    (GamepadButtonType::Select, Action::TogglePhotoMode),
+    (GamepadButtonType::RightTrigger2, Action::Warp),
+    (GamepadButtonType::RightTrigger, Action::SpeedUp),
+    (GamepadButtonType::LeftTrigger, Action::SlowDown),
+    (GamepadButtonType::North, Action::CycleRetroTheme),
+    (GamepadButtonType::West, Action::ToggleBlendMode),
+];
+
+/// Keys the menus use to get around, which can't be bound to actions.
+const RESERVED_KEYS: [KeyCode; 6] = [
+    KeyCode::Escape,
+    KeyCode::Return,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+];
+
+/// Gamepad buttons the menus use to get around, which can't be bound to actions.
+const RESERVED_BUTTONS: [GamepadButtonType; 6] = [
+    GamepadButtonType::South,
+    GamepadButtonType::East,
+    GamepadButtonType::DPadUp,
+    GamepadButtonType::DPadDown,
+    GamepadButtonType::DPadLeft,
+    GamepadButtonType::DPadRight,
+];
+
+pub struct BindingsPlugin;
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Bindings::load())
+            .init_resource::<ActionInput>()
+            .add_system(reset_bindings)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                read_bindings
+                    .with_run_criteria(state::field_shown)
+                    .label(BindingsSystem),
+            );
+    }
+}
+
+/// Turns this frame's input into actions, anything reading `ActionInput` should run after it.
+#[derive(Clone, Hash, Debug, PartialEq, Eq, SystemLabel)]
+pub struct BindingsSystem;
+
+/// Which actions are being held down right now, on any device.
+#[derive(Default)]
+pub struct ActionInput {
+    held: HashSet<Action>,
+}
+
+impl ActionInput {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+}
+
+/// Which keys and buttons trigger which actions.
+pub struct Bindings {
+    keys: Vec<(KeyCode, Action)>,
+    buttons: Vec<(GamepadButtonType, Action)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            keys: DEFAULT_KEYS.to_vec(),
+            buttons: DEFAULT_BUTTONS.to_vec(),
+        }
+    }
+}
+
+/// How bindings are written in the config file, with the action first since that's what people look for.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct BindingsFile {
+    keyboard: Vec<(Action, KeyCode)>,
+    gamepad: Vec<(Action, GamepadButtonType)>,
+}
+
+impl Default for BindingsFile {
+    fn default() -> Self {
+        Self::from(&Bindings::default())
+    }
+}
+
+impl From<&Bindings> for BindingsFile {
+    fn from(bindings: &Bindings) -> Self {
+        Self {
+            keyboard: bindings
+                .keys
+                .iter()
+                .map(|(key, action)| (*action, *key))
+                .collect(),
+            gamepad: bindings
+                .buttons
+                .iter()
+                .map(|(button, action)| (*action, *button))
+                .collect(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Loads the user's bindings, writing out the defaults for them to edit if there aren't any yet.
+    fn load() -> Self {
+        let path = match config_path(FILE_NAME) {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => {
+                let defaults = Self::default();
+                defaults.save();
+                return defaults;
+            }
+        };
+
+        match ron::from_str::<BindingsFile>(&contents) {
+            Ok(file) => Self {
+                keys: without_conflicts(file.keyboard, &RESERVED_KEYS),
+                buttons: without_conflicts(file.gamepad, &RESERVED_BUTTONS),
+            },
+            Err(error) => {
+                eprintln!("Ignoring bindings in {}, {error}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        let path = match config_path(FILE_NAME) {
+            Some(path) => path,
+            None => {
+                warn!("Couldn't find a config directory to save bindings to");
+                return;
+            }
+        };
+
+        let file = BindingsFile::from(self);
+        let saved = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                if let Some(directory) = path.parent() {
+                    fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+                }
+                fs::write(&path, contents).map_err(|error| error.to_string())
+            });
+
+        if let Err(error) = saved {
+            warn!("Couldn't save bindings to {}, {error}", path.display());
+        }
+    }
+
+    /// The key bound to an action, if there is one.
+    pub fn key(&self, action: Action) -> Option<KeyCode> {
+        self.keys
+            .iter()
+            .find(|(_, bound)| *bound == action)
+            .map(|(key, _)| *key)
+    }
+}
+
+/// Drops bindings that would fight over an input, keeping whichever came first in the file.
+fn without_conflicts<T: Copy + PartialEq + std::fmt::Debug>(
+    bindings: Vec<(Action, T)>,
+    reserved: &[T],
+) -> Vec<(T, Action)> {
+    let mut kept: Vec<(T, Action)> = Vec::new();
+
+    for (action, input) in bindings {
+        if reserved.contains(&input) {
+            eprintln!(
+                "Ignoring {input:?} for \"{}\", it's reserved for menus",
+                action.name()
+            );
+        } else if let Some((_, bound)) = kept.iter().find(|(kept_input, _)| *kept_input == input) {
+            eprintln!(
+                "Ignoring {input:?} for \"{}\", it's already bound to \"{}\"",
+                action.name(),
+                bound.name()
+            );
+        } else {
+            kept.push((input, action));
+        }
+    }
+
+    kept
+}
+
+fn reset_bindings(mut actions: EventReader<Action>, mut bindings: ResMut<Bindings>) {
+    for action in actions.iter() {
+        if *action == Action::ResetBindings {
+            *bindings = Bindings::default();
+            bindings.save();
+            info!("Reset bindings to the defaults");
+        }
+    }
+}
+
+/// Works out which actions are held on any device, and sends an action for each one that was just pressed.
+#[allow(clippy::too_many_arguments)]
+fn read_bindings(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    touches: Res<Touches>,
+    bindings: Res<Bindings>,
+    palette: Option<Res<CommandPalette>>,
+    mut egui_context: Option<ResMut<EguiContext>>,
+    mut input: ResMut<ActionInput>,
+    mut actions: EventWriter<Action>,
+) {
+    let mut held = HashSet::default();
+
+    // Keys typed into the palette's search box or any other text field aren't meant as actions,
+    // and Ctrl combinations are handled elsewhere.
+    let typing = palette.map_or(false, |palette| palette.open)
+        || egui_context.as_mut().map_or(false, |egui_context| {
+            egui_context.ctx_mut().wants_keyboard_input()
+        });
+    if !typing && !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        held.extend(
+            bindings
+                .keys
+                .iter()
+                .filter(|(key, _)| keys.pressed(*key))
+                .map(|(_, action)| *action),
+        );
+    }
+
+    for gamepad in gamepads.iter() {
+        held.extend(
+            bindings
+                .buttons
+                .iter()
+                .filter(|(button, _)| buttons.pressed(GamepadButton::new(*gamepad, *button)))
+                .map(|(_, action)| *action),
+        );
+    }
+
+    // There's nothing to bind on a touch screen, so holding a finger anywhere off the UI warps.
+    let over_ui = egui_context.as_mut().map_or(false, |egui_context| {
+        egui_context.ctx_mut().wants_pointer_input()
+    });
+    if touches.iter().next().is_some() && !over_ui {
+        held.insert(Action::Warp);
+    }
+
+    for action in held.difference(&input.held) {
+        actions.send(*action);
+    }
+
+    input.held = held;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicting_bindings_are_dropped() {
+        let kept = without_conflicts(
+            vec![
+                (Action::Warp, KeyCode::W),
+                (Action::TogglePause, KeyCode::W),
+                (Action::SpeedUp, KeyCode::Escape),
+                (Action::SlowDown, KeyCode::Minus),
+            ],
+            &RESERVED_KEYS,
+        );
+        assert_eq!(
+            kept,
+            vec![
+                (KeyCode::W, Action::Warp),
+                (KeyCode::Minus, Action::SlowDown)
+            ]
+        );
+    }
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let defaults = Bindings::default();
+        let file = BindingsFile::default();
+        assert_eq!(
+            without_conflicts(file.keyboard, &RESERVED_KEYS),
+            defaults.keys
+        );
+        assert_eq!(
+            without_conflicts(file.gamepad, &RESERVED_BUTTONS),
+            defaults.buttons
+        );
+    }
+
+    #[test]
+    fn reset_goes_back_to_the_defaults() {
+        // Resetting saves the defaults, which should land somewhere out of the way.
+        let config = std::env::temp_dir().join(format!("starfield-test-{}", std::process::id()));
+        std::env::set_var("XDG_CONFIG_HOME", &config);
+        std::env::set_var("APPDATA", &config);
+        std::env::set_var("HOME", &config);
+
+        let mut app = App::new();
+        app.add_event::<Action>()
+            .insert_resource(Bindings {
+                keys: vec![(KeyCode::B, Action::Warp)],
+                buttons: Vec::new(),
+            })
+            .add_system(reset_bindings);
+        app.world.send_event(Action::ResetBindings);
+        app.update();
+
+        let bindings = app.world.resource::<Bindings>();
+        assert_eq!(bindings.key(Action::Warp), Some(KeyCode::W));
+        assert_eq!(bindings.key(Action::ToggleBlendMode), Some(KeyCode::B));
+        let _ = fs::remove_dir_all(config);
+    }
+}
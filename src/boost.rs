@@ -0,0 +1,97 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Holding warp speeds the field up, double tapping it punches a short boost with a camera shake.
+
+use bevy::prelude::*;
+
+use crate::actions::Action;
+use crate::bindings::{ActionInput, BindingsSystem};
+use crate::dodge::Dodge;
+use crate::event_log::LoggedEvent;
+use crate::settings::StarfieldSettings;
+use crate::shake::CameraShake;
+use crate::spectator::Spectator;
+use crate::speed::SimulationSpeed;
+use crate::state;
+
+/// Speed multiplier while warp is held.
+const WARP_SPEED: f32 = 3.0;
+
+/// Speed multiplier at the peak of a boost.
+const BOOST_SPEED: f32 = 12.0;
+
+/// Seconds a boost lasts before the speed starts recovering.
+const BOOST_DURATION: f32 = 0.8;
+
+/// Longest gap between two presses that still counts as a double tap, in seconds.
+const DOUBLE_TAP_WINDOW: f32 = 0.3;
+
+/// Camera shake trauma a boost adds.
+const BOOST_TRAUMA: f32 = 1.0;
+
+pub struct BoostPlugin;
+impl Plugin for BoostPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Boost>().add_system_to_stage(
+            CoreStage::PreUpdate,
+            warp.with_run_criteria(state::running).after(BindingsSystem),
+        );
+    }
+}
+
+#[derive(Default)]
+struct Boost {
+    /// When warp was last pressed, in seconds since startup.
+    last_press: Option<f64>,
+    /// Seconds left on the current boost.
+    remaining: f32,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn warp(
+    time: Res<Time>,
+    input: Res<ActionInput>,
+    settings: Res<StarfieldSettings>,
+    spectator: Res<Spectator>,
+    dodge: Res<Dodge>,
+    mut actions: EventReader<Action>,
+    mut boost: ResMut<Boost>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut shake: ResMut<CameraShake>,
+    mut logged: EventWriter<LoggedEvent>,
+) {
+    let now = time.seconds_since_startup();
+
+    // Warp flies the spectator forward, or steers in the dodge game or while steering, instead.
+    if spectator.flying() || dodge.active() || settings.steer.enabled {
+        actions.clear();
+        boost.remaining = 0.0;
+        speed.boost = 1.0;
+        return;
+    }
+
+    for _ in actions.iter().filter(|action| **action == Action::Warp) {
+        let double_tap =
+            matches!(boost.last_press, Some(last) if now - last <= DOUBLE_TAP_WINDOW as f64);
+
+        if double_tap {
+            boost.remaining = BOOST_DURATION;
+            shake.add_trauma(BOOST_TRAUMA);
+            logged.send(LoggedEvent::BoostEngaged);
+            boost.last_press = None;
+        } else {
+            boost.last_press = Some(now);
+            logged.send(LoggedEvent::WarpEngaged);
+        }
+    }
+
+    boost.remaining = (boost.remaining - time.delta_seconds()).max(0.0);
+
+    speed.boost = if boost.remaining > 0.0 {
+        BOOST_SPEED
+    } else if input.pressed(Action::Warp) {
+        WARP_SPEED
+    } else {
+        1.0
+    };
+}
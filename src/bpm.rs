@@ -0,0 +1,165 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Pulses the stars' size and speed in time with a tempo, set in the settings or tapped in.
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::parallax::ParallaxSystem;
+use crate::settings::StarfieldSettings;
+use crate::speed::SimulationSpeed;
+use crate::Star;
+
+/// The slowest and fastest tempo that can be tapped in.
+const BPM_LIMITS: (f32, f32) = (30.0, 300.0);
+
+/// Taps further apart than this start counting the tempo from scratch, in seconds.
+const TAP_TIMEOUT: f64 = 2.0;
+
+/// How many of the most recent taps the tempo is averaged over.
+const TAP_HISTORY: usize = 8;
+
+/// How quickly each pulse dies away, higher is snappier.
+const PULSE_DECAY: f32 = 8.0;
+
+/// Beats in a bar, the first of which gets the strongest accent.
+const BEATS_PER_BAR: u64 = 4;
+
+pub struct BpmPlugin;
+impl Plugin for BpmPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<BpmSettings>()
+            .init_resource::<TapTempo>()
+            .init_resource::<Beat>()
+            .add_system_to_stage(CoreStage::PreUpdate, tap_tempo)
+            .add_system_to_stage(CoreStage::PreUpdate, follow_beat.after(tap_tempo))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                pulse_stars
+                    .after(TransformSystem::TransformPropagate)
+                    .before(ParallaxSystem),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BpmSettings {
+    pub enabled: bool,
+    pub bpm: f32,
+    /// Pulses per beat, 1 only pulses on the beat while 2 or 4 add weaker pulses in between.
+    pub subdivision: u32,
+    /// How much bigger stars get at the peak of a pulse, 0.5 being half again their size.
+    pub size_pulse: f32,
+    /// How much faster the field moves at the peak of a pulse.
+    pub speed_pulse: f32,
+}
+
+impl Default for BpmSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bpm: 120.0,
+            subdivision: 1,
+            size_pulse: 0.5,
+            speed_pulse: 0.3,
+        }
+    }
+}
+
+#[derive(Default)]
+struct TapTempo {
+    taps: Vec<f64>,
+}
+
+/// How strongly the current pulse is still ringing, from 0.0 to 1.0.
+#[derive(Default)]
+struct Beat {
+    envelope: f32,
+}
+
+/// Sets the tempo from the average gap between taps.
+fn tap_tempo(
+    time: Res<Time>,
+    mut actions: EventReader<Action>,
+    mut tapped: ResMut<TapTempo>,
+    mut settings: ResMut<StarfieldSettings>,
+) {
+    for action in actions.iter() {
+        if *action != Action::TapTempo {
+            continue;
+        }
+
+        let now = time.seconds_since_startup();
+        if matches!(tapped.taps.last(), Some(last) if now - last > TAP_TIMEOUT) {
+            tapped.taps.clear();
+        }
+
+        tapped.taps.push(now);
+        if tapped.taps.len() > TAP_HISTORY {
+            tapped.taps.remove(0);
+        }
+
+        if let (Some(first), Some(last)) = (tapped.taps.first(), tapped.taps.last()) {
+            let gaps = tapped.taps.len() - 1;
+            if gaps > 0 {
+                let interval = (last - first) / gaps as f64;
+                let (min, max) = BPM_LIMITS;
+                settings.bpm.bpm = (60.0 / interval as f32).clamp(min, max);
+                settings.bpm.enabled = true;
+            }
+        }
+    }
+}
+
+/// Works out where we are in the beat and how strongly the stars should be pulsing.
+fn follow_beat(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    mut beat: ResMut<Beat>,
+    mut speed: ResMut<SimulationSpeed>,
+) {
+    let bpm = settings.bpm;
+    if !bpm.enabled || bpm.bpm <= 0.0 {
+        beat.envelope = 0.0;
+        speed.pulse = 1.0;
+        return;
+    }
+
+    let subdivision = bpm.subdivision.max(1) as u64;
+    let ticks = time.seconds_since_startup() * bpm.bpm as f64 / 60.0 * subdivision as f64;
+    let tick = ticks.floor() as u64;
+    let since_tick = (ticks.fract() * 60.0 / bpm.bpm as f64 / subdivision as f64) as f32;
+
+    // Bars start with the strongest pulse, the other beats are a bit softer and subdivisions softer still.
+    let (beat_in_bar, step) = ((tick / subdivision) % BEATS_PER_BAR, tick % subdivision);
+    let accent = match (beat_in_bar, step) {
+        (0, 0) => 1.0,
+        (_, 0) => 0.75,
+        _ => 0.35,
+    };
+
+    beat.envelope = accent * (-PULSE_DECAY * since_tick).exp();
+    speed.pulse = 1.0 + beat.envelope * bpm.speed_pulse;
+}
+
+/// Swells the stars with the beat. Only what's drawn changes, so this never fights over the simulation's scale.
+fn pulse_stars(
+    settings: Res<StarfieldSettings>,
+    beat: Res<Beat>,
+    mut query: Query<&mut GlobalTransform, With<Star>>,
+) {
+    if beat.envelope <= 0.0 {
+        return;
+    }
+
+    let scale = 1.0 + beat.envelope * settings.bpm.size_pulse;
+
+    for mut global_transform in query.iter_mut() {
+        let mut transform = global_transform.compute_transform();
+        transform.scale *= scale;
+        *global_transform = transform.into();
+    }
+}
@@ -0,0 +1,410 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Renders the starfield into offscreen images of any size and saves them as PNGs.
+
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+    ImageDataLayout, MapMode, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::texture::BevyDefault;
+use bevy::render::{Extract, RenderApp, RenderStage};
+
+use crate::MainCamera;
+
+/// Frames a capture camera renders before we read it back, giving new pipelines time to get ready.
+const WARMUP_FRAMES: u32 = 2;
+
+/// Only pixels brighter than this contribute to bloom.
+const BLOOM_THRESHOLD: f32 = 0.5;
+
+pub struct CapturePlugin;
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = mpsc::channel();
+
+        app.add_event::<CaptureRequest>()
+            .add_event::<CaptureSaved>()
+            .init_resource::<PendingCaptures>()
+            .insert_resource(CaptureReceiver(Mutex::new(receiver)))
+            .add_system(start_captures)
+            .add_system(finish_captures)
+            .add_system_to_stage(CoreStage::Last, advance_captures);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(CaptureSender(sender))
+                .init_resource::<ExtractedCaptures>()
+                .add_system_to_stage(RenderStage::Extract, extract_captures)
+                .add_system_to_stage(RenderStage::Cleanup, read_back_captures);
+        }
+    }
+}
+
+/// Asks for the current view of the starfield to be rendered at some size and saved to a file.
+pub struct CaptureRequest {
+    pub size: UVec2,
+    pub path: PathBuf,
+    /// Strength of the glow added around bright pixels, 0.0 disables it.
+    pub bloom: f32,
+}
+
+/// Sent once a capture has been written to disk.
+pub struct CaptureSaved {
+    pub path: PathBuf,
+}
+
+enum CaptureState {
+    Warming(u32),
+    Ready,
+    Reading,
+}
+
+struct PendingCapture {
+    id: u32,
+    image: Handle<Image>,
+    camera: Entity,
+    path: PathBuf,
+    bloom: f32,
+    state: CaptureState,
+}
+
+#[derive(Default)]
+struct PendingCaptures {
+    next_id: u32,
+    captures: Vec<PendingCapture>,
+}
+
+/// The pixels of a finished capture, in the render target's BGRA layout.
+struct CapturedPixels {
+    id: u32,
+    width: u32,
+    height: u32,
+    bgra: Vec<u8>,
+}
+
+struct CaptureSender(Sender<CapturedPixels>);
+
+// Receivers can't be shared between threads, but we only ever read from one system.
+struct CaptureReceiver(Mutex<Receiver<CapturedPixels>>);
+
+/// Captures the render world should read back this frame.
+#[derive(Default)]
+struct ExtractedCaptures(Vec<(u32, Handle<Image>)>);
+
+/// Creates an image and a camera looking at the same part of the world as the main camera for every request.
+fn start_captures(
+    mut commands: Commands,
+    mut requests: EventReader<CaptureRequest>,
+    mut pending: ResMut<PendingCaptures>,
+    mut images: ResMut<Assets<Image>>,
+    render_device: Res<RenderDevice>,
+    windows: Res<Windows>,
+    main_camera: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let (camera_transform, main_projection) = match main_camera.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let window_height = windows.get_primary().map_or(1.0, |window| window.height());
+    let max_dimension = render_device.limits().max_texture_dimension_2d;
+
+    for request in requests.iter() {
+        let size = request.size.clamp(UVec2::ONE, UVec2::splat(max_dimension));
+        if size != request.size {
+            warn!(
+                "Capture size {}x{} is beyond what the GPU supports, using {}x{}",
+                request.size.x, request.size.y, size.x, size.y
+            );
+        }
+
+        let image = images.add(capture_image(size));
+
+        // Keep the same world height in view, the width follows from the capture's aspect ratio.
+        let projection = OrthographicProjection {
+            scale: main_projection.scale * window_height / size.y as f32,
+            ..main_projection.clone()
+        };
+
+        let camera = commands
+            .spawn_bundle(Camera2dBundle {
+                camera: Camera {
+                    target: RenderTarget::Image(image.clone()),
+                    priority: -1,
+                    ..default()
+                },
+                projection,
+                transform: *camera_transform,
+                ..default()
+            })
+            .id();
+
+        let id = pending.next_id;
+        pending.next_id += 1;
+        pending.captures.push(PendingCapture {
+            id,
+            image,
+            camera,
+            path: request.path.clone(),
+            bloom: request.bloom,
+            state: CaptureState::Warming(WARMUP_FRAMES),
+        });
+    }
+}
+
+/// Counts down the warmup frames. Runs last so a capture is only marked ready right before extraction.
+fn advance_captures(mut pending: ResMut<PendingCaptures>) {
+    for capture in pending.captures.iter_mut() {
+        capture.state = match capture.state {
+            CaptureState::Warming(0) => CaptureState::Ready,
+            CaptureState::Warming(frames) => CaptureState::Warming(frames - 1),
+            CaptureState::Ready | CaptureState::Reading => CaptureState::Reading,
+        };
+    }
+}
+
+fn extract_captures(
+    mut extracted: ResMut<ExtractedCaptures>,
+    pending: Extract<Res<PendingCaptures>>,
+) {
+    extracted.0 = pending
+        .captures
+        .iter()
+        .filter(|capture| matches!(capture.state, CaptureState::Ready))
+        .map(|capture| (capture.id, capture.image.clone()))
+        .collect();
+}
+
+/// Copies the finished capture images into buffers we can map, and sends the pixels back to the main world.
+/// This blocks until the GPU is done, which is fine for the odd still image.
+fn read_back_captures(
+    extracted: Res<ExtractedCaptures>,
+    images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    sender: Res<CaptureSender>,
+) {
+    for (id, handle) in extracted.0.iter() {
+        let gpu_image = match images.get(handle) {
+            Some(gpu_image) => gpu_image,
+            None => continue,
+        };
+
+        let width = gpu_image.size.x as u32;
+        let height = gpu_image.size.y as u32;
+        let row_bytes = width as usize * 4;
+        let padded_row_bytes = RenderDevice::align_copy_bytes_per_row(row_bytes);
+
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("starfield_capture_buffer"),
+            size: (padded_row_bytes * height as usize) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("starfield_capture_encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            gpu_image.texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row_bytes as u32),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        render_queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        render_device.map_buffer(&slice, MapMode::Read, |_| {});
+        render_device.poll(wgpu::Maintain::Wait);
+
+        // Strip the padding wgpu needs at the end of each row.
+        let bgra = slice
+            .get_mapped_range()
+            .chunks(padded_row_bytes)
+            .flat_map(|row| &row[..row_bytes])
+            .copied()
+            .collect();
+        buffer.unmap();
+
+        let pixels = CapturedPixels {
+            id: *id,
+            width,
+            height,
+            bgra,
+        };
+        if sender.0.send(pixels).is_err() {
+            error!("The main world stopped listening for captures");
+        }
+    }
+}
+
+/// Saves captures whose pixels came back from the render world and cleans up their camera.
+fn finish_captures(
+    mut commands: Commands,
+    mut pending: ResMut<PendingCaptures>,
+    mut images: ResMut<Assets<Image>>,
+    mut saved: EventWriter<CaptureSaved>,
+    receiver: Res<CaptureReceiver>,
+) {
+    let receiver = match receiver.0.lock() {
+        Ok(receiver) => receiver,
+        Err(_) => return,
+    };
+
+    for pixels in receiver.try_iter() {
+        let index = match pending.captures.iter().position(|c| c.id == pixels.id) {
+            Some(index) => index,
+            None => continue,
+        };
+        let capture = pending.captures.remove(index);
+
+        commands.entity(capture.camera).despawn();
+        images.remove(&capture.image);
+
+        let mut rgba = bgra_to_rgba(pixels.bgra);
+        if capture.bloom > 0.0 {
+            add_bloom(&mut rgba, pixels.width, pixels.height, capture.bloom);
+        }
+
+        match image::save_buffer(
+            &capture.path,
+            &rgba,
+            pixels.width,
+            pixels.height,
+            image::ColorType::Rgba8,
+        ) {
+            Ok(()) => {
+                info!("Saved capture to {}", capture.path.display());
+                saved.send(CaptureSaved { path: capture.path });
+            }
+            Err(error) => error!("Couldn't save {}: {error}", capture.path.display()),
+        }
+    }
+}
+
+/// A render target in the same format as the window so the existing pipelines can draw into it.
+fn capture_image(size: UVec2) -> Image {
+    let size = Extent3d {
+        width: size.x,
+        height: size.y,
+        ..default()
+    };
+
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("starfield_capture_image"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..default()
+    };
+
+    image.resize(size);
+    image
+}
+
+fn bgra_to_rgba(mut pixels: Vec<u8>) -> Vec<u8> {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+    pixels
+}
+
+/// Blurs the bright parts of the image and adds them back on top, so stars get a soft glow.
+fn add_bloom(rgba: &mut [u8], width: u32, height: u32, strength: f32) {
+    let (width, height) = (width as usize, height as usize);
+
+    // Glow radius scales with the image so it looks the same at any resolution.
+    let radius = (height / 400).max(1);
+
+    let mut bright: Vec<[f32; 3]> = rgba
+        .chunks_exact(4)
+        .map(|pixel| {
+            let color = [0, 1, 2].map(|channel| pixel[channel] as f32 / 255.0);
+            let luminance = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+            if luminance > BLOOM_THRESHOLD {
+                color
+            } else {
+                [0.0; 3]
+            }
+        })
+        .collect();
+
+    // Two passes of a separable box blur get reasonably close to a gaussian.
+    for _ in 0..2 {
+        bright = box_blur(&bright, width, height, radius, 1, width);
+        bright = box_blur(&bright, width, height, radius, width, 1);
+    }
+
+    for (pixel, glow) in rgba.chunks_exact_mut(4).zip(bright) {
+        for channel in 0..3 {
+            let value = pixel[channel] as f32 / 255.0 + glow[channel] * strength;
+            pixel[channel] = (value.min(1.0) * 255.0) as u8;
+        }
+    }
+}
+
+/// Averages each pixel with its neighbors along one axis.
+/// `step` is the distance between neighbors and `line_step` the distance between lines.
+fn box_blur(
+    source: &[[f32; 3]],
+    width: usize,
+    height: usize,
+    radius: usize,
+    step: usize,
+    line_step: usize,
+) -> Vec<[f32; 3]> {
+    let (lines, length) = if step == 1 {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let mut output = vec![[0.0; 3]; source.len()];
+    let window = (radius * 2 + 1) as f32;
+
+    for line in 0..lines {
+        let start = line * line_step;
+        let at = |i: usize| source[start + i.min(length - 1) * step];
+
+        // Running sum over the window, clamping at the edges.
+        let mut sum = [0.0; 3];
+        for i in 0..=radius * 2 {
+            let sample = at(i.saturating_sub(radius));
+            (0..3).for_each(|c| sum[c] += sample[c]);
+        }
+
+        for i in 0..length {
+            output[start + i * step] = sum.map(|value| value / window);
+
+            let leaving = at(i.saturating_sub(radius));
+            let entering = at(i + radius + 1);
+            (0..3).for_each(|c| sum[c] += entering[c] - leaving[c]);
+        }
+    }
+
+    output
+}
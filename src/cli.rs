@@ -0,0 +1,140 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+/// Options passed on the command line. Anything that isn't understood prints the usage and exits.
+#[derive(Debug, Parser)]
+#[command(name = "starfield", version, about = "Flies through a field of stars.")]
+pub struct Args {
+    /// Draw the starfield as text in the terminal instead of opening a window.
+    #[arg(long)]
+    pub terminal: bool,
+    /// Draw the flat field, or fly through stars in 3D with a perspective camera.
+    #[arg(long, value_enum, default_value_t = Mode::Flat)]
+    pub mode: Mode,
+    /// Export a wallpaper of this width and height, then quit.
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    pub wallpaper: Option<(u32, u32)>,
+    /// A share code to recreate someone else's starfield from.
+    #[arg(long)]
+    pub code: Option<String>,
+    /// Neither load nor save settings from the user's config directory.
+    #[arg(long)]
+    pub no_persist: bool,
+    /// A CSV or JSON file of stars to show alongside the procedural ones.
+    #[arg(long, value_name = "FILE")]
+    pub layout: Option<PathBuf>,
+    /// Skip the main menu and start the field right away.
+    #[arg(long)]
+    pub autostart: bool,
+    /// Show today's field, the same one everyone else sees.
+    #[arg(long)]
+    pub daily: bool,
+    /// Show the loading screen, filling up over this many seconds.
+    #[arg(long, value_name = "SECONDS")]
+    pub loading_demo: Option<f32>,
+    /// Log frame times, star counts and asset counts every second.
+    #[arg(long)]
+    pub diagnostics: bool,
+    /// How many stars to show, instead of the saved setting.
+    #[arg(long)]
+    pub stars: Option<u32>,
+    /// The seed to generate the field from, instead of a random one.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// The speed multiplier to start at, instead of the saved setting.
+    #[arg(long, value_name = "MULTIPLIER")]
+    pub speed: Option<f32>,
+    /// Open the window fullscreen.
+    #[arg(long)]
+    pub fullscreen: bool,
+    /// Run fullscreen on every monitor with the cursor hidden, and quit on any input.
+    #[arg(long)]
+    pub screensaver: bool,
+    /// Record numbered frames into this directory, then quit.
+    #[arg(long, value_name = "DIRECTORY")]
+    pub record: Option<PathBuf>,
+    /// How many frames to record, or to measure at each star count when benchmarking.
+    #[arg(long)]
+    pub frames: Option<u32>,
+    /// Measure frame times at several star counts, print a report, then quit.
+    #[arg(long)]
+    pub bench: bool,
+    /// Print the benchmark report as JSON instead of a table.
+    #[arg(long, requires = "bench")]
+    pub json: bool,
+    /// Benchmark without a window or renderer, measuring only the simulation.
+    #[arg(long, requires = "bench")]
+    pub headless: bool,
+    /// A scene saved earlier to restore, instead of generating a new field.
+    #[arg(long, value_name = "FILE")]
+    pub load: Option<PathBuf>,
+}
+
+/// Which kind of field to show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Mode {
+    /// The flat field, with all its menus, editors and effects.
+    #[value(name = "2d")]
+    Flat,
+    /// Stars flying past a perspective camera.
+    #[value(name = "3d")]
+    Volume,
+}
+
+/// Parses sizes written like `1920x1080`.
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("`{value}` isn't a size like 7680x4320");
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let size = (
+        width.parse().map_err(|_| invalid())?,
+        height.parse().map_err(|_| invalid())?,
+    );
+    if size.0 > 0 && size.1 > 0 {
+        Ok(size)
+    } else {
+        Err(invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn arguments_are_consistent() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("1920x1080"), Ok((1920, 1080)));
+        for invalid in [
+            "", "1920", "1920x", "x1080", "0x1080", "1920x0", "-1x5", "axb",
+        ] {
+            assert!(parse_size(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parses_modes() {
+        let args = Args::try_parse_from(["starfield", "--mode", "3d"]).unwrap();
+        assert_eq!(args.mode, Mode::Volume);
+        assert_eq!(
+            Args::try_parse_from(["starfield"]).unwrap().mode,
+            Mode::Flat
+        );
+        assert!(Args::try_parse_from(["starfield", "--mode", "4d"]).is_err());
+    }
+
+    #[test]
+    fn bench_options_need_bench() {
+        assert!(Args::try_parse_from(["starfield", "--json"]).is_err());
+        assert!(Args::try_parse_from(["starfield", "--headless"]).is_err());
+        assert!(Args::try_parse_from(["starfield", "--bench", "--json", "--headless"]).is_ok());
+    }
+}
@@ -0,0 +1,22 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! The time of day, for naming saved files and picking the daily field.
+//!
+//! `SystemTime::now` panics on the web, where the time has to come from the browser instead.
+
+/// Whole seconds since the Unix epoch, in UTC.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn unix_seconds() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
+
+/// Whole seconds since the Unix epoch, in UTC.
+#[cfg(target_arch = "wasm32")]
+pub fn unix_seconds() -> u64 {
+    // The browser counts in milliseconds.
+    (js_sys::Date::now() / 1000.0).max(0.0) as u64
+}
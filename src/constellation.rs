@@ -0,0 +1,343 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Named constellations pinned in place over the field, joined up with faint lines and labeled.
+//!
+//! Constellations are read from a RON list, each with a `name`, its `stars` as `(x, y)` offsets in pixels
+//! from where it's pinned, and its `lines` as pairs of indices into its stars. A `constellations.ron` in
+//! the config directory replaces the built in ones. A few are picked at random and pinned somewhere in the
+//! field, and their stars stay put while the rest of the field streams past.
+
+use std::fmt;
+use std::fs;
+
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::persist::config_path;
+use crate::settings::StarfieldSettings;
+use crate::sim::{Field, StarfieldRng};
+use crate::star_mesh::StarMesh;
+use crate::state;
+use crate::theme::Theme;
+use crate::MainCamera;
+
+const FILE_NAME: &str = "constellations.ron";
+
+/// The constellations shown when there's no file of them in the config directory.
+const BUILT_IN: &str = include_str!("constellations.ron");
+
+/// In front of every star, but behind shooting stars.
+const CONSTELLATION_Z: f32 = 550.0;
+
+/// How far in from the field's edges constellations are pinned, as a fraction of the way to the edge.
+const PLACEMENT_REACH: f32 = 0.7;
+
+/// How many spots are tried for each constellation before giving up on keeping it clear of the others.
+const PLACEMENT_ATTEMPTS: usize = 20;
+
+/// Pixels between a constellation's lowest star and its label.
+const LABEL_GAP: f32 = 16.0;
+
+pub struct ConstellationPlugin;
+impl Plugin for ConstellationPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ConstellationSettings>()
+            .insert_resource(Catalog::load())
+            .add_startup_system(create_line_mesh)
+            .add_system(show_constellations)
+            .add_system(label_constellations.with_run_criteria(state::field_shown));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConstellationSettings {
+    pub enabled: bool,
+    /// How many constellations are pinned in the field at once.
+    pub count: u32,
+    /// Shows each constellation's name below it.
+    pub labels: bool,
+    /// Color of the lines joining the stars, usually faint.
+    pub line_color: Color,
+    /// How wide the lines are, in pixels.
+    pub line_width: f32,
+    /// Radius of a constellation's stars, in pixels.
+    pub star_size: f32,
+}
+
+impl Default for ConstellationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            count: 3,
+            labels: true,
+            line_color: Color::rgba(0.6, 0.7, 1.0, 0.25),
+            line_width: 1.0,
+            star_size: 2.5,
+        }
+    }
+}
+
+/// One constellation as it's written in the file.
+#[derive(Clone, Deserialize)]
+struct ConstellationData {
+    name: String,
+    stars: Vec<(f32, f32)>,
+    #[serde(default)]
+    lines: Vec<(usize, usize)>,
+}
+
+impl ConstellationData {
+    fn offsets(&self) -> impl Iterator<Item = Vec2> + '_ {
+        self.stars.iter().map(|(x, y)| Vec2::new(*x, *y))
+    }
+
+    /// How far the farthest star is from where the constellation is pinned.
+    fn radius(&self) -> f32 {
+        self.offsets().map(Vec2::length).fold(0.0, f32::max)
+    }
+}
+
+#[derive(Debug)]
+enum CatalogError {
+    /// The file wasn't a list of constellations.
+    Ron(String),
+    /// A line joined a star the constellation doesn't have.
+    Line(String),
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CatalogError::Ron(error) => write!(f, "it isn't a list of constellations, {error}"),
+            CatalogError::Line(name) => write!(f, "{name} has a line to a star it doesn't have"),
+        }
+    }
+}
+
+/// Every constellation that can be shown.
+struct Catalog(Vec<ConstellationData>);
+
+impl Catalog {
+    /// Reads the user's constellations if they have any, otherwise the built in ones.
+    fn load() -> Self {
+        let path = config_path(FILE_NAME).filter(|path| path.exists());
+        if let Some(path) = path {
+            let parsed = fs::read_to_string(&path)
+                .map_err(|error| CatalogError::Ron(error.to_string()))
+                .and_then(|contents| Self::parse(&contents));
+            match parsed {
+                Ok(catalog) => return catalog,
+                Err(error) => warn!("Ignoring constellations in {}, {error}", path.display()),
+            }
+        }
+
+        Self::parse(BUILT_IN).expect("the built in constellations are valid")
+    }
+
+    fn parse(contents: &str) -> Result<Self, CatalogError> {
+        let constellations: Vec<ConstellationData> =
+            ron::from_str(contents).map_err(|error| CatalogError::Ron(error.to_string()))?;
+
+        for constellation in &constellations {
+            let count = constellation.stars.len();
+            if constellation
+                .lines
+                .iter()
+                .any(|(from, to)| *from >= count || *to >= count)
+            {
+                return Err(CatalogError::Line(constellation.name.clone()));
+            }
+        }
+
+        Ok(Self(constellations))
+    }
+}
+
+/// A constellation pinned in the field. Its stars and lines are its children.
+#[derive(Component)]
+pub struct Constellation {
+    pub name: String,
+    /// Where the label goes, relative to where the constellation is pinned.
+    label_offset: Vec2,
+}
+
+/// A unit square that's stretched and turned into each line.
+struct LineMesh(Mesh2dHandle);
+
+fn create_line_mesh(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let mesh = meshes.add(shape::Quad::new(Vec2::ONE).into());
+    commands.insert_resource(LineMesh(mesh.into()));
+}
+
+/// Places a unit square so that it covers the line from `from` to `to`.
+fn line_transform(from: Vec2, to: Vec2, width: f32, z: f32) -> Transform {
+    let along = to - from;
+    Transform::from_translation(((from + to) / 2.0).extend(z))
+        .with_rotation(Quat::from_rotation_z(along.y.atan2(along.x)))
+        .with_scale(Vec3::new(along.length(), width, 1.0))
+}
+
+/// Pins a fresh set of constellations whenever their settings, the theme or the field's size change.
+#[allow(clippy::too_many_arguments)]
+fn show_constellations(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+    catalog: Res<Catalog>,
+    star_mesh: Res<StarMesh>,
+    line_mesh: Res<LineMesh>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    field: Res<Field>,
+    mut rng: ResMut<StarfieldRng>,
+    shown: Query<Entity, With<Constellation>>,
+    mut applied: Local<Option<(ConstellationSettings, Vec2)>>,
+) {
+    let constellations = settings.constellations;
+    let extents = field.extents();
+    let previous = applied.replace((constellations, extents));
+    if previous == Some((constellations, extents)) && !theme.is_changed() {
+        return;
+    }
+
+    for entity in shown.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !constellations.enabled {
+        return;
+    }
+
+    let star_material = pool.get(theme.star_color(0.0), &mut materials);
+    let line_material = pool.get(constellations.line_color, &mut materials);
+
+    let rng = &mut rng.effects;
+    let mut placed: Vec<(Vec2, f32)> = Vec::new();
+    for constellation in pick(&catalog.0, constellations.count as usize, rng) {
+        let radius = constellation.radius();
+        let anchor = match place(extents, radius, &placed, rng) {
+            Some(anchor) => anchor,
+            None => continue,
+        };
+        placed.push((anchor, radius));
+
+        let lowest = constellation
+            .offsets()
+            .map(|offset| offset.y)
+            .fold(0.0, f32::min);
+        let offsets: Vec<Vec2> = constellation.offsets().collect();
+
+        commands
+            .spawn_bundle(SpatialBundle::from_transform(Transform::from_translation(
+                anchor.extend(CONSTELLATION_Z),
+            )))
+            .insert(Constellation {
+                name: constellation.name.clone(),
+                label_offset: Vec2::new(0.0, lowest - LABEL_GAP),
+            })
+            .with_children(|parent| {
+                for offset in &offsets {
+                    parent.spawn_bundle(MaterialMesh2dBundle {
+                        mesh: star_mesh.0.clone().into(),
+                        material: star_material.clone(),
+                        transform: Transform::from_translation(offset.extend(0.0)).with_scale(
+                            Vec3::new(constellations.star_size, constellations.star_size, 1.0),
+                        ),
+                        ..default()
+                    });
+                }
+
+                // Just behind the stars, so lines meet underneath them.
+                for (from, to) in &constellation.lines {
+                    parent.spawn_bundle(MaterialMesh2dBundle {
+                        mesh: line_mesh.0.clone(),
+                        material: line_material.clone(),
+                        transform: line_transform(
+                            offsets[*from],
+                            offsets[*to],
+                            constellations.line_width,
+                            -0.01,
+                        ),
+                        ..default()
+                    });
+                }
+            });
+    }
+}
+
+/// Up to `count` different constellations, in a random order.
+fn pick<'a>(
+    catalog: &'a [ConstellationData],
+    count: usize,
+    rng: &mut impl Rng,
+) -> Vec<&'a ConstellationData> {
+    let mut remaining: Vec<&ConstellationData> = catalog.iter().collect();
+    let mut picked = Vec::new();
+    while picked.len() < count && !remaining.is_empty() {
+        let index = rng.gen_range(0..remaining.len());
+        picked.push(remaining.swap_remove(index));
+    }
+    picked
+}
+
+/// Somewhere in the field for a constellation of this radius, clear of the ones already placed if possible.
+fn place(extents: Vec2, radius: f32, placed: &[(Vec2, f32)], rng: &mut impl Rng) -> Option<Vec2> {
+    let reach = (extents * PLACEMENT_REACH - Vec2::splat(radius)).max(Vec2::ZERO);
+
+    (0..PLACEMENT_ATTEMPTS)
+        .map(|_| {
+            Vec2::new(
+                rng.gen_range(-reach.x..=reach.x),
+                rng.gen_range(-reach.y..=reach.y),
+            )
+        })
+        .find(|anchor| {
+            placed
+                .iter()
+                .all(|(other, other_radius)| anchor.distance(*other) > radius + other_radius)
+        })
+}
+
+/// Writes each constellation's name under it.
+fn label_constellations(
+    mut egui_context: ResMut<EguiContext>,
+    settings: Res<StarfieldSettings>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    constellations: Query<(&Constellation, &GlobalTransform)>,
+) {
+    if !settings.constellations.labels {
+        return;
+    }
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let height = match camera.logical_viewport_size() {
+        Some(size) => size.y,
+        None => return,
+    };
+
+    let color = settings.constellations.line_color.as_rgba_f32();
+    let color = egui::Rgba::from_rgba_unmultiplied(color[0], color[1], color[2], 0.8);
+    let painter = egui_context
+        .ctx_mut()
+        .layer_painter(egui::LayerId::background());
+
+    for (constellation, transform) in constellations.iter() {
+        let position = transform.translation() + constellation.label_offset.extend(0.0);
+        if let Some(screen) = camera.world_to_viewport(camera_transform, position) {
+            // Viewport coordinates count up from the bottom, egui's count down from the top.
+            painter.text(
+                egui::pos2(screen.x, height - screen.y),
+                egui::Align2::CENTER_TOP,
+                &constellation.name,
+                egui::FontId::proportional(14.0),
+                color.into(),
+            );
+        }
+    }
+}
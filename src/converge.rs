@@ -0,0 +1,95 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Streams every star towards one point and holds them there until released, for loading screens.
+//!
+//! Host games send a [`Converge`] event when loading starts, usually aimed behind their logo, and release it
+//! when loading is done so the stars burst back out into their normal flow.
+
+use bevy::prelude::*;
+
+use crate::actions::Action;
+use crate::Star;
+
+/// How strongly stars are pulled towards the target once the pull has fully ramped up, higher is snappier.
+const CONVERGE_PULL: f32 = 3.0;
+
+/// Seconds the pull takes to ramp up, so stars start by curving towards the target rather than jumping.
+const RAMP_UP: f32 = 0.6;
+
+pub struct ConvergePlugin;
+impl Plugin for ConvergePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Converge>()
+            .init_resource::<Convergence>()
+            .add_system(converge_on_action)
+            .add_system(start_convergence.after(converge_on_action))
+            .add_system(pull_stars.after(start_convergence));
+    }
+}
+
+/// Starts or ends a convergence.
+#[derive(Clone, Copy, Debug)]
+pub enum Converge {
+    /// Streams stars towards a point in world space until released.
+    Start { target: Vec2 },
+    /// Lets the stars go back to their normal flow.
+    Release,
+}
+
+#[derive(Default)]
+struct Convergence {
+    target: Option<Vec2>,
+    /// Seconds since the convergence started.
+    elapsed: f32,
+}
+
+/// The converge action aims at the middle of the field, so it can be tried out from the palette or IPC.
+fn converge_on_action(
+    convergence: Res<Convergence>,
+    mut actions: EventReader<Action>,
+    mut converge: EventWriter<Converge>,
+) {
+    for action in actions.iter() {
+        if *action == Action::ToggleConverge {
+            converge.send(match convergence.target {
+                Some(_) => Converge::Release,
+                None => Converge::Start { target: Vec2::ZERO },
+            });
+        }
+    }
+}
+
+fn start_convergence(mut events: EventReader<Converge>, mut convergence: ResMut<Convergence>) {
+    for event in events.iter() {
+        *convergence = match *event {
+            Converge::Start { target } => Convergence {
+                target: Some(target),
+                elapsed: 0.0,
+            },
+            Converge::Release => Convergence::default(),
+        };
+    }
+}
+
+fn pull_stars(
+    time: Res<Time>,
+    mut convergence: ResMut<Convergence>,
+    mut query: Query<(&mut Star, &mut Transform)>,
+) {
+    let target = match convergence.target {
+        Some(target) => target,
+        None => return,
+    };
+
+    let delta = time.delta_seconds();
+    convergence.elapsed += delta;
+
+    let ramp = (convergence.elapsed / RAMP_UP).min(1.0);
+    let pull = 1.0 - (-CONVERGE_PULL * ramp * ramp * delta).exp();
+
+    for (mut star, mut transform) in query.iter_mut() {
+        let position = star.position(&transform);
+        let pulled = position.truncate().lerp(target, pull).extend(position.z);
+        star.place(&mut transform, pulled);
+    }
+}
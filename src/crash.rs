@@ -0,0 +1,174 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Turns panics into a readable error dialog and a crash log, instead of a backtrace in a console nobody sees.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic;
+use std::path::PathBuf;
+
+use crate::persist::config_path;
+
+const FILE_NAME: &str = "crash.log";
+
+/// Where a known failure comes from.
+enum Cause {
+    /// Part of the panic message.
+    Message(&'static str),
+    /// The source file that panicked, for failures whose message is just an unwrapped error.
+    Source(&'static str),
+}
+
+impl Cause {
+    fn matches(&self, message: &str, file: &str) -> bool {
+        match self {
+            Cause::Message(pattern) => message.contains(pattern),
+            Cause::Source(pattern) => file.ends_with(pattern),
+        }
+    }
+}
+
+/// Known failures and what to tell people about them.
+const HINTS: [(Cause, &str); 4] = [
+    // Bevy's renderer when no adapter suits it.
+    (
+        Cause::Message("Unable to find a GPU!"),
+        "Starfield couldn't find a graphics card it can draw with. Updating your graphics drivers usually fixes this.",
+    ),
+    // wgpu when the adapter won't give Bevy a device.
+    (
+        Cause::Message("RequestDeviceError"),
+        "Starfield couldn't set up your graphics card. Updating your graphics drivers usually fixes this.",
+    ),
+    // winit on Linux when there's neither a Wayland nor an X11 display.
+    (
+        Cause::Message("Failed to initialize any backend!"),
+        "Starfield couldn't open a window. If you're connecting remotely, make sure a display is available.",
+    ),
+    // bevy_winit unwraps the error when the window itself can't be created.
+    (
+        Cause::Source("winit_windows.rs"),
+        "Starfield couldn't open a window. If you're connecting remotely, make sure a display is available.",
+    ),
+];
+
+/// Shows a dialog and writes a crash log whenever the program panics.
+/// Only for windowed runs, anything run from a terminal should leave its panics on the terminal.
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        // Still print the usual message for anyone running from a terminal.
+        default_hook(info);
+
+        let message = panic_message(info.payload());
+        let file = info.location().map_or("", |location| location.file());
+        let location = info
+            .location()
+            .map_or(String::new(), |location| format!(" at {location}"));
+        let log = write_crash_log(&format!("{message}{location}"));
+
+        let hint = HINTS
+            .iter()
+            .find(|(cause, _)| cause.matches(&message, file))
+            .map_or(
+                "Something went wrong and Starfield has to close.",
+                |(_, hint)| hint,
+            );
+
+        let mut text = format!("{hint}\n\n{message}");
+        if let Some(log) = log {
+            text += &format!("\n\nDetails were saved to {}", log.display());
+        }
+
+        show_dialog("Starfield", &text);
+    }));
+}
+
+/// Tells someone about a problem that doesn't stop the field, like a settings file that couldn't be read.
+/// Windowed runs often have no console to print it to.
+pub fn show_problem(message: &str) {
+    show_dialog("Starfield", message);
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown error".to_string())
+}
+
+fn write_crash_log(message: &str) -> Option<PathBuf> {
+    let path = config_path(FILE_NAME)?;
+    let contents = format!("{message}\n\n{}\n", Backtrace::force_capture());
+
+    fs::create_dir_all(path.parent()?).ok()?;
+    fs::write(&path, contents).ok()?;
+    Some(path)
+}
+
+#[cfg(target_os = "windows")]
+fn show_dialog(title: &str, message: &str) {
+    use std::ffi::c_void;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn MessageBoxW(
+            window: *mut c_void,
+            text: *const u16,
+            caption: *const u16,
+            kind: u32,
+        ) -> i32;
+    }
+
+    const MB_ICONERROR: u32 = 0x10;
+
+    let wide = |text: &str| text.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let (text, caption) = (wide(message), wide(title));
+
+    // Safety: both strings are null terminated and outlive the call.
+    unsafe {
+        MessageBoxW(
+            std::ptr::null_mut(),
+            text.as_ptr(),
+            caption.as_ptr(),
+            MB_ICONERROR,
+        );
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn show_dialog(title: &str, message: &str) {
+    let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+    let script = format!(
+        "display alert {} message {} as critical",
+        quote(title),
+        quote(message)
+    );
+
+    let _ = std::process::Command::new("osascript")
+        .args(["-e", &script])
+        .status();
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn show_dialog(title: &str, message: &str) {
+    use std::process::Command;
+
+    // There's no dialog built into Linux, so try the common desktop tools in turn.
+    let shown = Command::new("zenity")
+        .args(["--error", "--title", title, "--text", message])
+        .status()
+        .or_else(|_| {
+            Command::new("kdialog")
+                .args(["--title", title, "--error", message])
+                .status()
+        })
+        .is_ok();
+
+    if !shown {
+        eprintln!("{message}");
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Hides stars no camera can see, so they keep simulating but skip extraction and rendering.
+
+use bevy::prelude::*;
+use bevy::render::camera::OrthographicProjection;
+
+use crate::layout::ImportedStar;
+use crate::spectator::Spectator;
+use crate::star_editor::PinnedStar;
+use crate::tunnel::Tunnel;
+use crate::Star;
+
+/// Extra room around each view so glows and parallax near the edges don't pop, in pixels.
+const CULL_MARGIN: f32 = 64.0;
+
+pub struct CullPlugin;
+impl Plugin for CullPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PostUpdate, cull_stars);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn cull_stars(
+    spectator: Option<Res<Spectator>>,
+    tunnel: Res<Tunnel>,
+    cameras: Query<(&Camera, &GlobalTransform, &OrthographicProjection)>,
+    mut stars: Query<
+        (&Transform, &mut Visibility),
+        Or<(With<Star>, With<PinnedStar>, With<ImportedStar>)>,
+    >,
+) {
+    // Every active camera counts, so captures rendering a wider view than the window still see everything.
+    // Each view is the corners of the area it covers, bottom left then top right.
+    let views: Vec<(Vec2, Vec2)> = cameras
+        .iter()
+        .filter(|(camera, _, _)| camera.is_active)
+        .map(|(_, transform, projection)| {
+            let center = transform.translation().truncate();
+            let min = Vec2::new(projection.left, projection.bottom) * projection.scale;
+            let max = Vec2::new(projection.right, projection.top) * projection.scale;
+            (center + min - CULL_MARGIN, center + max + CULL_MARGIN)
+        })
+        .collect();
+
+    // Runs without the spectator camera, like benchmarks, don't have one at all.
+    let spectating = spectator.map_or(false, |spectator| spectator.in_view());
+
+    for (transform, mut visibility) in stars.iter_mut() {
+        let position = transform.translation.truncate();
+        let radius = Vec2::splat(transform.scale.x);
+
+        // The spectator and tunnel draw stars away from where they really are, so anything could be on screen.
+        let visible = spectating
+            || tunnel.in_view()
+            || views.iter().any(|(min, max)| {
+                (position + radius).cmpge(*min).all() && (position - radius).cmple(*max).all()
+            });
+
+        if visibility.is_visible != visible {
+            visibility.is_visible = visible;
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Seed of the day, where the seed and palette both come from today's date.
+//!
+//! Days are counted in UTC so everyone running the app sees the same field on the same day.
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::clock;
+use crate::settings::StarfieldSettings;
+use crate::sim::{Field, StarfieldRng};
+use crate::theme::Theme;
+use crate::{reseed_field, Star};
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+pub struct DailyPlugin;
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(follow_the_day);
+    }
+}
+
+/// Days since the Unix epoch, in UTC.
+fn today() -> u64 {
+    clock::unix_seconds() / SECONDS_PER_DAY
+}
+
+/// The seed and palette for a day, the same for everyone.
+fn field_for_day(day: u64) -> (u64, Theme) {
+    // Spreads neighbouring days far apart, so one day's field looks nothing like the next.
+    let mut seed = day.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    seed ^= seed >> 31;
+
+    // The palette gets its own generator so it doesn't use up any of the field's randomness.
+    let mut rng = StdRng::seed_from_u64(seed.rotate_left(17));
+    (seed, Theme::random(&mut rng))
+}
+
+/// Sets up today's field before any stars are spawned.
+pub fn apply_today(settings: &mut StarfieldSettings, theme: &mut Theme) {
+    let (seed, today_theme) = field_for_day(today());
+    settings.seed = seed;
+    *theme = today_theme;
+}
+
+/// Switches to today's field when the mode is turned on, and again whenever the date rolls over.
+fn follow_the_day(
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+    mut field: ResMut<Field>,
+    mut rng: ResMut<StarfieldRng>,
+    mut stars: Query<(&mut Star, &mut Transform)>,
+    mut shown: Local<Option<u64>>,
+) {
+    if !settings.daily {
+        *shown = None;
+        return;
+    }
+
+    let day = today();
+    if *shown == Some(day) {
+        return;
+    }
+
+    // Turned on at startup, when today's field has already been spawned.
+    let first_run = shown.is_none() && settings.seed == field_for_day(day).0;
+    *shown = Some(day);
+    if first_run {
+        return;
+    }
+
+    let (seed, today_theme) = field_for_day(day);
+    settings.seed = seed;
+    *theme = today_theme;
+    reseed_field(seed, &mut field, &mut rng, &mut stars);
+}
@@ -0,0 +1,144 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! How far away stars are, and how that shows in their speed, size and brightness.
+
+use bevy::prelude::*;
+use bevy::reflect::impl_reflect_value;
+use serde::{Deserialize, Serialize};
+
+use crate::heatmap::DepthHeatmap;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::merge::Supernova;
+use crate::settings::StarfieldSettings;
+use crate::sim::{DepthDistribution, Field, DEPTH_RANGE};
+use crate::spectral::SpectralClass;
+use crate::theme::Theme;
+use crate::twinkle::{Twinkle, TwinkleBoost};
+use crate::Star;
+
+impl_reflect_value!(DepthDistribution(PartialEq, Serialize, Deserialize));
+
+pub struct DepthPlugin;
+impl Plugin for DepthPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DepthDistribution>()
+            .register_type::<DepthCues>()
+            .add_system(apply_depth_distribution)
+            .add_system(shade_stars);
+    }
+}
+
+/// How much each star's depth shows, each being how much the farthest stars are reduced compared to the nearest.
+/// 0.0 turns a cue off, 1.0 takes it all the way down to nothing at the far plane.
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DepthCues {
+    pub speed: f32,
+    pub size: f32,
+    pub brightness: f32,
+}
+
+impl Default for DepthCues {
+    fn default() -> Self {
+        Self {
+            speed: 0.5,
+            size: 0.5,
+            brightness: 0.6,
+        }
+    }
+}
+
+impl DepthCues {
+    pub fn speed(&self, depth: f32) -> f32 {
+        cue(self.speed, depth)
+    }
+
+    pub fn size(&self, depth: f32) -> f32 {
+        cue(self.size, depth)
+    }
+
+    pub fn brightness(&self, depth: f32) -> f32 {
+        cue(self.brightness, depth)
+    }
+}
+
+/// Scales linearly from 1.0 at the near plane down by `amount` at the far plane.
+fn cue(amount: f32, depth: f32) -> f32 {
+    let depth = depth.clamp(*DEPTH_RANGE.start(), *DEPTH_RANGE.end());
+    1.0 - amount.clamp(0.0, 1.0) * depth
+}
+
+/// Hands the depth distribution setting to the simulation, which uses it for every star spawned after.
+fn apply_depth_distribution(settings: Res<StarfieldSettings>, mut field: ResMut<Field>) {
+    if settings.is_changed() {
+        field.set_depth_distribution(settings.depth_distribution);
+    }
+}
+
+/// Dims stars by how far away they are and how they're twinkling, recycled stars get a new depth so this
+/// runs every frame. Stars are colored by their spectral class instead of the theme while classes are turned on.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn shade_stars(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+    heatmap: Option<Res<DepthHeatmap>>,
+    boost: Res<TwinkleBoost>,
+    field: Res<Field>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    mut query: Query<
+        (
+            &Star,
+            &Transform,
+            Option<&SpectralClass>,
+            Option<&Twinkle>,
+            &mut Handle<StarMaterial>,
+        ),
+        Without<Supernova>,
+    >,
+) {
+    // The heatmap is in charge of star colors while it's showing.
+    if matches!(heatmap, Some(heatmap) if heatmap.enabled) {
+        return;
+    }
+
+    let seconds = time.seconds_since_startup() as f32;
+
+    for (star, transform, class, twinkle, mut material) in query.iter_mut() {
+        let color = match class {
+            Some(class) if settings.spectral.enabled => class.color(),
+            _ => theme.star_color(star.tint),
+        };
+        let twinkle = twinkle.map_or(1.0, |twinkle| {
+            let position = transform.translation.truncate();
+            settings
+                .twinkle
+                .brightness(twinkle, &field, position, seconds, boost.0)
+        });
+        let fade_in = settings.fade.fade_in(star.age);
+        let alpha = color.a() * settings.depth_cues.brightness(star.depth) * twinkle * fade_in;
+        let shaded = pool.get(*color.clone().set_a(alpha), &mut materials);
+        if *material != shaded {
+            *material = shaded;
+        }
+    }
+}
+
+/// The band of 2D z values stars are drawn in.
+/// This has to sit inside the default 2D camera's visible range of -0.1 to 999.9.
+const STAR_Z_NEAR: f32 = 500.0;
+const STAR_Z_FAR: f32 = 1.0;
+
+/// Maps a simulation depth to the z used for draw ordering.
+/// Nearer stars get a higher z so they always draw over the ones behind them.
+pub fn depth_to_z(depth: f32) -> f32 {
+    let depth = depth.clamp(*DEPTH_RANGE.start(), *DEPTH_RANGE.end());
+    STAR_Z_NEAR + (STAR_Z_FAR - STAR_Z_NEAR) * depth
+}
+
+/// The inverse of [`depth_to_z`].
+pub fn z_to_depth(z: f32) -> f32 {
+    let depth = (z - STAR_Z_NEAR) / (STAR_Z_FAR - STAR_Z_NEAR);
+    depth.clamp(*DEPTH_RANGE.start(), *DEPTH_RANGE.end())
+}
@@ -0,0 +1,50 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Logs frame times alongside how many stars there are and how many assets they're drawn with.
+//!
+//! Every star shares one mesh and a small pool of materials, so the asset counts should stay flat however
+//! many stars there are. Run with `--diagnostics` at different star counts to see where the time goes.
+
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticId, Diagnostics, EntityCountDiagnosticsPlugin,
+    FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin,
+};
+use bevy::prelude::*;
+
+use crate::materials::StarMaterial;
+use crate::Star;
+
+pub const STAR_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(0x3f6b_92d1_0c47_4e8a_b215_7d90_e3a6_1c58);
+pub const MESH_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(0x8a14_5e03_d7b2_4c69_9f31_06e8_b4d7_2a95);
+pub const MATERIAL_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(0xc529_7a6e_41f0_4b3d_8e62_d1b5_09c4_f713);
+
+pub struct StarfieldDiagnosticsPlugin;
+impl Plugin for StarfieldDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
+            .add_plugin(EntityCountDiagnosticsPlugin)
+            .add_plugin(LogDiagnosticsPlugin::default())
+            .add_startup_system(add_diagnostics)
+            .add_system(measure);
+    }
+}
+
+fn add_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    diagnostics.add(Diagnostic::new(STAR_COUNT, "stars", 1));
+    diagnostics.add(Diagnostic::new(MESH_COUNT, "meshes", 1));
+    diagnostics.add(Diagnostic::new(MATERIAL_COUNT, "star_materials", 1));
+}
+
+fn measure(
+    mut diagnostics: ResMut<Diagnostics>,
+    stars: Query<(), With<Star>>,
+    meshes: Res<Assets<Mesh>>,
+    materials: Res<Assets<StarMaterial>>,
+) {
+    diagnostics.add_measurement(STAR_COUNT, || stars.iter().count() as f64);
+    diagnostics.add_measurement(MESH_COUNT, || meshes.len() as f64);
+    diagnostics.add_measurement(MATERIAL_COUNT, || materials.len() as f64);
+}
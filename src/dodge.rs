@@ -0,0 +1,334 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! An optional arcade game, steering through the field to dodge incoming asteroids.
+//!
+//! Steering turns the ship, which slides the whole field across the screen, so asteroids that were heading
+//! straight for the camera end up passing by. Crashing shakes the camera and slows the ship down for a bit,
+//! and once the ship is out of lives the run's score goes up against the high score table.
+//! The screensaver is untouched until the game is started.
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::asteroid::{Asteroid, AsteroidArrived};
+use crate::bindings::ActionInput;
+use crate::event_log::LoggedEvent;
+use crate::high_scores::{HighScore, HighScores, INITIALS_LENGTH};
+use crate::settings::StarfieldSettings;
+use crate::shake::CameraShake;
+use crate::spectator::Spectator;
+use crate::speed::SimulationSpeed;
+use crate::state;
+use crate::Star;
+
+/// How close to the middle of the screen an asteroid has to arrive to hit the ship, on top of its own radius.
+const SHIP_RADIUS: f32 = 40.0;
+
+/// Camera shake trauma a crash adds.
+const CRASH_TRAUMA: f32 = 1.0;
+
+/// Points for every second survived.
+const POINTS_PER_SECOND: f32 = 10.0;
+
+/// Points for every asteroid that goes by without hitting the ship.
+const POINTS_PER_DODGE: u32 = 50;
+
+pub struct DodgePlugin;
+impl Plugin for DodgePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DodgeSettings>()
+            .init_resource::<Dodge>()
+            .insert_resource(HighScores::load())
+            .add_system(toggle_dodge)
+            .add_system(steer.with_run_criteria(state::running).after(toggle_dodge))
+            .add_system(check_collisions.with_run_criteria(state::running))
+            .add_system(dodge_hud.with_run_criteria(state::ui_shown))
+            .add_system(high_score_ui.with_run_criteria(state::ui_shown));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DodgeSettings {
+    /// How fast steering slides the field across the screen, in pixels per second.
+    pub steer_speed: f32,
+    /// On average how many asteroids come at the ship a minute.
+    pub asteroid_density: f32,
+    /// What the speed drops to after a crash, as a multiplier.
+    pub crash_speed: f32,
+    /// Seconds it takes to get back up to speed after a crash.
+    pub recovery: f32,
+    /// How many crashes end a run.
+    pub lives: u32,
+}
+
+impl Default for DodgeSettings {
+    fn default() -> Self {
+        Self {
+            steer_speed: 600.0,
+            asteroid_density: 40.0,
+            crash_speed: 0.3,
+            recovery: 3.0,
+            lives: 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Phase {
+    #[default]
+    Off,
+    Playing,
+    /// The run made the high score table and is waiting for initials.
+    EnteringInitials,
+    ShowingScores,
+}
+
+#[derive(Default)]
+pub struct Dodge {
+    phase: Phase,
+    dodged: u32,
+    hits: u32,
+    /// Seconds the current run has lasted.
+    survived: f32,
+    /// Seconds since the last crash, while still recovering from it.
+    since_crash: Option<f32>,
+    initials: String,
+}
+
+impl Dodge {
+    /// Whether a run is being played, rather than the game being off or showing scores.
+    pub fn active(&self) -> bool {
+        self.phase == Phase::Playing
+    }
+
+    fn score(&self) -> u32 {
+        (self.survived * POINTS_PER_SECOND) as u32 + self.dodged * POINTS_PER_DODGE
+    }
+
+    fn start(&mut self) {
+        *self = Dodge {
+            phase: Phase::Playing,
+            ..default()
+        };
+    }
+}
+
+fn toggle_dodge(
+    mut actions: EventReader<Action>,
+    mut dodge: ResMut<Dodge>,
+    mut speed: ResMut<SimulationSpeed>,
+) {
+    for action in actions.iter() {
+        if *action == Action::ToggleDodgeGame {
+            if dodge.phase == Phase::Off {
+                dodge.start();
+            } else {
+                *dodge = Dodge::default();
+            }
+            speed.penalty = 1.0;
+        }
+    }
+}
+
+/// Slides stars and asteroids the opposite way to the ship turning.
+#[allow(clippy::type_complexity)]
+fn steer(
+    time: Res<Time>,
+    input: Res<ActionInput>,
+    settings: Res<StarfieldSettings>,
+    dodge: Res<Dodge>,
+    spectator: Res<Spectator>,
+    mut query: Query<&mut Transform, Or<(With<Star>, With<Asteroid>)>>,
+) {
+    // The spectator uses the same actions to fly around.
+    if !dodge.active() || spectator.active() {
+        return;
+    }
+
+    let axis = |positive: Action, negative: Action| {
+        input.pressed(positive) as i32 as f32 - input.pressed(negative) as i32 as f32
+    };
+    let direction = Vec2::new(
+        axis(Action::FlyRight, Action::FlyLeft),
+        axis(Action::Warp, Action::FlyBack),
+    );
+    if direction == Vec2::ZERO {
+        return;
+    }
+
+    let shift = direction.normalize() * settings.dodge.steer_speed * time.delta_seconds();
+    for mut transform in query.iter_mut() {
+        transform.translation -= shift.extend(0.0);
+    }
+}
+
+/// Counts every asteroid that reaches the camera as either dodged or a crash, and ends the run when
+/// the ship is out of lives.
+#[allow(clippy::too_many_arguments)]
+fn check_collisions(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    high_scores: Res<HighScores>,
+    mut arrived: EventReader<AsteroidArrived>,
+    mut dodge: ResMut<Dodge>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut shake: ResMut<CameraShake>,
+    mut logged: EventWriter<LoggedEvent>,
+) {
+    if !dodge.active() {
+        arrived.clear();
+        return;
+    }
+
+    dodge.survived += time.delta_seconds();
+
+    for asteroid in arrived.iter() {
+        if asteroid.position.length() < asteroid.radius + SHIP_RADIUS {
+            dodge.hits += 1;
+            dodge.since_crash = Some(0.0);
+            shake.add_trauma(CRASH_TRAUMA);
+            logged.send(LoggedEvent::AsteroidHit);
+        } else {
+            dodge.dodged += 1;
+        }
+    }
+
+    // Speed climbs back from the crash speed in a straight line.
+    if let Some(since_crash) = dodge.since_crash.as_mut() {
+        *since_crash += time.delta_seconds();
+        let recovered = (*since_crash / settings.dodge.recovery.max(0.001)).min(1.0);
+        let crash_speed = settings.dodge.crash_speed;
+        speed.penalty = crash_speed + (1.0 - crash_speed) * recovered;
+
+        if recovered >= 1.0 {
+            dodge.since_crash = None;
+        }
+    }
+
+    if dodge.hits >= settings.dodge.lives {
+        dodge.since_crash = None;
+        speed.penalty = 1.0;
+        dodge.phase = if high_scores.qualifies(dodge.score()) {
+            Phase::EnteringInitials
+        } else {
+            Phase::ShowingScores
+        };
+    }
+}
+
+/// Keeps score in the corner while the game is on.
+fn dodge_hud(
+    mut egui_context: ResMut<EguiContext>,
+    settings: Res<StarfieldSettings>,
+    dodge: Res<Dodge>,
+) {
+    if !dodge.active() {
+        return;
+    }
+
+    let lives = settings.dodge.lives.saturating_sub(dodge.hits);
+    egui::Area::new("dodge_hud")
+        .anchor(egui::Align2::LEFT_TOP, [12.0, 12.0])
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(format!("Score {}", dodge.score()));
+            ui.label(format!(
+                "Time {}   Dodged {}   Lives {}",
+                format_time(dodge.survived),
+                dodge.dodged,
+                lives
+            ));
+        });
+}
+
+/// Asks for initials after a high scoring run, then shows the table.
+fn high_score_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut dodge: ResMut<Dodge>,
+    mut high_scores: ResMut<HighScores>,
+) {
+    let window = egui::Window::new("Game over")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false);
+
+    match dodge.phase {
+        Phase::Off | Phase::Playing => {}
+        Phase::EnteringInitials => {
+            let mut submitted = false;
+
+            window.show(egui_context.ctx_mut(), |ui| {
+                ui.label(format!(
+                    "Score {} in {}",
+                    dodge.score(),
+                    format_time(dodge.survived)
+                ));
+                ui.label("A new high score! Enter your initials");
+
+                let response =
+                    ui.add(egui::TextEdit::singleline(&mut dodge.initials).desired_width(60.0));
+                response.request_focus();
+
+                let entered = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+                submitted = ui.button("Save").clicked() || entered;
+            });
+
+            // Initials are letters and numbers only, shouted like an arcade cabinet.
+            let initials: String = dodge
+                .initials
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .map(|character| character.to_ascii_uppercase())
+                .take(INITIALS_LENGTH)
+                .collect();
+            dodge.initials = initials;
+
+            if submitted && !dodge.initials.is_empty() {
+                high_scores.insert(HighScore {
+                    initials: dodge.initials.clone(),
+                    score: dodge.score(),
+                    survived: dodge.survived,
+                });
+                dodge.phase = Phase::ShowingScores;
+            }
+        }
+        Phase::ShowingScores => {
+            let mut play_again = false;
+            let mut close = false;
+
+            window.show(egui_context.ctx_mut(), |ui| {
+                ui.label(format!("Score {}", dodge.score()));
+                ui.separator();
+
+                egui::Grid::new("high_scores").striped(true).show(ui, |ui| {
+                    for (rank, entry) in high_scores.entries.iter().enumerate() {
+                        ui.label(format!("{}.", rank + 1));
+                        ui.label(&entry.initials);
+                        ui.label(entry.score.to_string());
+                        ui.label(format_time(entry.survived));
+                        ui.end_row();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    play_again = ui.button("Play again").clicked();
+                    close = ui.button("Close").clicked();
+                });
+            });
+
+            if play_again {
+                dodge.start();
+            } else if close {
+                *dodge = Dodge::default();
+            }
+        }
+    }
+}
+
+/// Minutes and seconds, like 1:07.
+fn format_time(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
@@ -0,0 +1,200 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Writes notable events to a rotating log file, so whoever runs a kiosk can check what happened overnight.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::clock;
+use crate::persist::config_path;
+use crate::settings::StarfieldSettings;
+use crate::theme::Theme;
+use crate::Star;
+
+const FILE_NAME: &str = "events.log";
+
+/// Frames slower than this get logged, in seconds.
+const SLOW_FRAME: f32 = 0.05;
+
+/// Slow frames are logged at most this often, so a struggling machine doesn't fill the log, in seconds.
+const SLOW_FRAME_COOLDOWN: f64 = 10.0;
+
+pub struct EventLogPlugin;
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<EventLogSettings>()
+            .add_event::<LoggedEvent>()
+            .add_system(watch_theme)
+            .add_system(watch_star_count)
+            .add_system(watch_frame_time)
+            .add_system_to_stage(CoreStage::Last, write_events);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventLogSettings {
+    pub enabled: bool,
+    /// Writes one JSON object per line instead of plain text.
+    pub json: bool,
+    /// Size in bytes the log can grow to before it's rotated.
+    pub max_size: u64,
+    /// How many rotated logs are kept around.
+    pub max_files: u32,
+}
+
+impl Default for EventLogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            json: false,
+            max_size: 1_000_000,
+            max_files: 3,
+        }
+    }
+}
+
+/// Something worth writing down.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LoggedEvent {
+    ThemeChanged { preset: String },
+    WarpEngaged,
+    BoostEngaged,
+    AsteroidHit,
+    StarCountChanged { count: usize },
+    SlowFrame { milliseconds: f32 },
+}
+
+impl LoggedEvent {
+    fn describe(&self) -> String {
+        match self {
+            LoggedEvent::ThemeChanged { preset } => format!("theme changed to {preset}"),
+            LoggedEvent::WarpEngaged => "warp engaged".to_string(),
+            LoggedEvent::BoostEngaged => "boost engaged".to_string(),
+            LoggedEvent::AsteroidHit => "hit an asteroid".to_string(),
+            LoggedEvent::StarCountChanged { count } => format!("star count changed to {count}"),
+            LoggedEvent::SlowFrame { milliseconds } => {
+                format!("slow frame took {milliseconds:.1}ms")
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LogLine<'a> {
+    time: u64,
+    #[serde(flatten)]
+    event: &'a LoggedEvent,
+}
+
+fn watch_theme(theme: Res<Theme>, mut events: EventWriter<LoggedEvent>) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    let preset = Theme::PRESETS
+        .iter()
+        .find(|(_, preset)| *preset == *theme)
+        .map_or("a custom theme", |(name, _)| name);
+
+    events.send(LoggedEvent::ThemeChanged {
+        preset: preset.to_string(),
+    });
+}
+
+fn watch_star_count(
+    stars: Query<(), With<Star>>,
+    mut last_count: Local<Option<usize>>,
+    mut events: EventWriter<LoggedEvent>,
+) {
+    let count = stars.iter().count();
+    if *last_count != Some(count) {
+        *last_count = Some(count);
+        events.send(LoggedEvent::StarCountChanged { count });
+    }
+}
+
+fn watch_frame_time(
+    time: Res<Time>,
+    mut last_logged: Local<Option<f64>>,
+    mut events: EventWriter<LoggedEvent>,
+) {
+    let now = time.seconds_since_startup();
+    let cooling_down = matches!(*last_logged, Some(last) if now - last < SLOW_FRAME_COOLDOWN);
+
+    // The first frames are always slow while everything loads.
+    if time.delta_seconds() > SLOW_FRAME && !cooling_down && now > 1.0 {
+        *last_logged = Some(now);
+        events.send(LoggedEvent::SlowFrame {
+            milliseconds: time.delta_seconds() * 1000.0,
+        });
+    }
+}
+
+fn write_events(settings: Res<StarfieldSettings>, mut events: EventReader<LoggedEvent>) {
+    let log = settings.event_log;
+    if !log.enabled {
+        events.clear();
+        return;
+    }
+
+    let path = match config_path(FILE_NAME) {
+        Some(path) => path,
+        None => return,
+    };
+
+    let time = clock::unix_seconds();
+
+    let mut lines = String::new();
+    for event in events.iter() {
+        let line = if log.json {
+            serde_json::to_string(&LogLine { time, event }).unwrap_or_default()
+        } else {
+            format!("{time} {}", event.describe())
+        };
+        lines += &line;
+        lines.push('\n');
+    }
+
+    if lines.is_empty() {
+        return;
+    }
+
+    rotate(&path, log);
+
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| file.write_all(lines.as_bytes()));
+
+    if let Err(error) = written {
+        warn!("Couldn't write to {}, {error}", path.display());
+    }
+}
+
+/// Moves `events.log` to `events.log.1` and so on once it gets too big, dropping the oldest.
+fn rotate(path: &Path, log: EventLogSettings) {
+    let size = fs::metadata(path).map_or(0, |metadata| metadata.len());
+    if size < log.max_size {
+        return;
+    }
+
+    let rotated = |index: u32| path.with_extension(format!("log.{index}"));
+
+    let _ = fs::remove_file(rotated(log.max_files));
+    for index in (1..log.max_files).rev() {
+        let _ = fs::rename(rotated(index), rotated(index + 1));
+    }
+
+    if log.max_files > 0 {
+        let _ = fs::rename(path, rotated(1));
+    } else {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Dumps every star on screen to a JSON or CSV file for use in other tools.
+//! The columns match what [`crate::layout`] imports, so an export can be loaded back in as a layout.
+
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::actions::Action;
+use crate::clock;
+use crate::depth::z_to_depth;
+use crate::layout::ImportedStar;
+use crate::materials::StarMaterial;
+use crate::star_editor::PinnedStar;
+use crate::Star;
+
+pub struct ExportPlugin;
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(export_stars);
+    }
+}
+
+#[derive(Serialize)]
+struct ExportedStar {
+    x: f32,
+    y: f32,
+    z: f32,
+    size: f32,
+    color: String,
+    vx: f32,
+    vy: f32,
+}
+
+#[allow(clippy::type_complexity)]
+fn export_stars(
+    mut actions: EventReader<Action>,
+    materials: Res<Assets<StarMaterial>>,
+    query: Query<
+        (&Transform, &Handle<StarMaterial>, Option<&Star>),
+        Or<(With<Star>, With<PinnedStar>, With<ImportedStar>)>,
+    >,
+) {
+    for action in actions.iter() {
+        let extension = match action {
+            Action::ExportStarsJson => "json",
+            Action::ExportStarsCsv => "csv",
+            _ => continue,
+        };
+
+        let stars: Vec<_> = query
+            .iter()
+            .map(|(transform, material, star)| {
+                let color = materials
+                    .get(material)
+                    .map_or(Color::WHITE, |material| material.color);
+                let velocity = star.map_or(Vec3::ZERO, |star| star.velocity);
+
+                ExportedStar {
+                    x: transform.translation.x,
+                    y: transform.translation.y,
+                    z: z_to_depth(transform.translation.z),
+                    size: transform.scale.x,
+                    color: hex(color),
+                    vx: velocity.x,
+                    vy: velocity.y,
+                }
+            })
+            .collect();
+
+        let contents = match extension {
+            "json" => serde_json::to_string_pretty(&stars).unwrap_or_default(),
+            _ => csv(&stars),
+        };
+
+        let timestamp = clock::unix_seconds();
+        let path = PathBuf::from(format!("starfield-stars-{timestamp}.{extension}"));
+
+        match fs::write(&path, contents) {
+            Ok(()) => info!("Exported {} stars to {}", stars.len(), path.display()),
+            Err(error) => warn!("Couldn't export stars to {}, {error}", path.display()),
+        }
+    }
+}
+
+fn csv(stars: &[ExportedStar]) -> String {
+    let mut csv = String::from("x,y,z,size,color,vx,vy\n");
+    for star in stars {
+        csv += &format!(
+            "{},{},{},{},{},{},{}\n",
+            star.x, star.y, star.z, star.size, star.color, star.vx, star.vy
+        );
+    }
+    csv
+}
+
+/// Writes a color like `#ffcc00ff`.
+fn hex(color: Color) -> String {
+    let [r, g, b, a] = color
+        .as_rgba_f32()
+        .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+}
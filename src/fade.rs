@@ -0,0 +1,82 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Eases stars in when they spawn and out before they're recycled, so they never pop in or out at full size.
+//!
+//! Fading in grows stars from nothing here, and [`crate::depth`] brings their brightness up alongside.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::merge::Supernova;
+use crate::settings::StarfieldSettings;
+use crate::sim::Field;
+use crate::spectral::SpectralClass;
+use crate::Star;
+
+pub struct FadePlugin;
+impl Plugin for FadePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<FadeSettings>().add_system(fade_stars);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FadeSettings {
+    /// Seconds a new or recycled star takes to grow to its full size and brightness, 0.0 disables it.
+    pub fade_in: f32,
+    /// Fraction of the way to the edge of the space extent over which stars shrink away, 0.0 disables it.
+    pub fade_out: f32,
+}
+
+impl Default for FadeSettings {
+    fn default() -> Self {
+        Self {
+            fade_in: 0.5,
+            fade_out: 0.05,
+        }
+    }
+}
+
+impl FadeSettings {
+    /// How far a star of this age is through fading in, 0.0 when it's just appeared and 1.0 once it's done.
+    pub fn fade_in(&self, age: f32) -> f32 {
+        if self.fade_in <= 0.0 {
+            return 1.0;
+        }
+
+        let progress = (age / self.fade_in).min(1.0);
+        // Smoothstep, so the star neither jumps in nor stops growing abruptly.
+        progress * progress * (3.0 - 2.0 * progress)
+    }
+}
+
+/// Scales stars by how far along their fade in and fade out they are.
+fn fade_stars(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    field: Res<Field>,
+    mut query: Query<(&mut Star, Option<&SpectralClass>, &mut Transform), Without<Supernova>>,
+) {
+    let fade = settings.fade;
+
+    for (mut star, class, mut transform) in query.iter_mut() {
+        star.age += time.delta_seconds();
+
+        let fade_in = fade.fade_in(star.age);
+
+        let fade_out = if fade.fade_out > 0.0 {
+            let progress = field.extent_progress(transform.translation.truncate());
+            ((1.0 - progress) / fade.fade_out).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        let depth = settings.depth_cues.size(star.depth);
+        let size = match class {
+            Some(class) if settings.spectral.enabled => class.size(),
+            _ => 1.0,
+        };
+        transform.scale = Vec3::splat(star.mass.sqrt() * size * depth * fade_in * fade_out);
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Records the spectator's path as keyframes and plays saved paths back as rails, so a flight can be
+//! authored once and replayed for captures.
+
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::clock;
+use crate::spectator::{Pose, Spectator};
+use crate::state;
+
+const FLIGHT_EXTENSION: &str = ".flight.ron";
+
+/// Seconds between recorded keyframes.
+const KEYFRAME_INTERVAL: f32 = 0.1;
+
+pub struct FlightPlugin;
+impl Plugin for FlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recording>()
+            .init_resource::<Rail>()
+            .add_system(toggle_recording)
+            .add_system(load_dropped_rails)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(state::running)
+                    .with_system(record_flight.after(toggle_recording))
+                    .with_system(ride_rail.after(load_dropped_rails)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Keyframe {
+    /// Seconds since the start of the path.
+    time: f32,
+    pose: Pose,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct FlightPath {
+    keyframes: Vec<Keyframe>,
+}
+
+impl FlightPath {
+    fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// Where along the path the spectator is at some time, smoothly curving through the keyframes.
+    fn sample(&self, time: f32) -> Option<Pose> {
+        let keyframes = &self.keyframes;
+        let next = keyframes.iter().position(|keyframe| keyframe.time > time);
+
+        let index = match next {
+            Some(0) => return keyframes.first().map(|keyframe| keyframe.pose),
+            Some(index) => index,
+            None => return keyframes.last().map(|keyframe| keyframe.pose),
+        };
+
+        // The keyframes either side of the segment, and one beyond each end to shape the curve.
+        let at = |index: usize| keyframes[index.min(keyframes.len() - 1)].pose;
+        let (p0, p1, p2, p3) = (
+            at(index.saturating_sub(2)),
+            at(index - 1),
+            at(index),
+            at(index + 1),
+        );
+
+        let (start, end) = (keyframes[index - 1].time, keyframes[index].time);
+        let t = ((time - start) / (end - start).max(f32::EPSILON)).clamp(0.0, 1.0);
+
+        Some(Pose {
+            offset: catmull_rom(p0.offset, p1.offset, p2.offset, p3.offset, t),
+            yaw: catmull_rom(p0.yaw, p1.yaw, p2.yaw, p3.yaw, t),
+            pitch: catmull_rom(p0.pitch, p1.pitch, p2.pitch, p3.pitch, t),
+        })
+    }
+}
+
+/// A Catmull-Rom spline between `p1` and `p2`, which passes through every keyframe without sharp corners.
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T
+where
+    T: Copy
+        + std::ops::Add<Output = T>
+        + std::ops::Sub<Output = T>
+        + std::ops::Mul<f32, Output = T>,
+{
+    let (t2, t3) = (t * t, t * t * t);
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+#[derive(Default)]
+struct Recording {
+    path: Option<FlightPath>,
+    elapsed: f32,
+    /// Seconds until the next keyframe is due.
+    next_keyframe: f32,
+}
+
+/// A saved path the spectator is following.
+#[derive(Default)]
+struct Rail {
+    path: Option<FlightPath>,
+    elapsed: f32,
+}
+
+/// Starts recording, or stops and saves the recording so far.
+fn toggle_recording(mut actions: EventReader<Action>, mut recording: ResMut<Recording>) {
+    for action in actions.iter() {
+        if *action != Action::ToggleFlightRecording {
+            continue;
+        }
+
+        match recording.path.take() {
+            Some(path) => save(&path),
+            None => {
+                *recording = Recording {
+                    path: Some(FlightPath::default()),
+                    ..default()
+                };
+                info!("Recording the spectator's flight");
+            }
+        }
+    }
+}
+
+fn save(path: &FlightPath) {
+    if path.keyframes.is_empty() {
+        warn!("Nothing was recorded, the spectator camera has to be in use");
+        return;
+    }
+
+    let timestamp = clock::unix_seconds();
+    let file = format!("starfield-{timestamp}{FLIGHT_EXTENSION}");
+
+    let saved = ron::ser::to_string_pretty(path, ron::ser::PrettyConfig::default())
+        .map_err(|error| error.to_string())
+        .and_then(|contents| fs::write(&file, contents).map_err(|error| error.to_string()));
+
+    match saved {
+        Ok(()) => info!("Saved {} keyframes to {file}", path.keyframes.len()),
+        Err(error) => warn!("Couldn't save the flight to {file}, {error}"),
+    }
+}
+
+fn record_flight(time: Res<Time>, spectator: Res<Spectator>, mut recording: ResMut<Recording>) {
+    // Only the time spent out in the field counts, so paths don't start with a long wait.
+    if recording.path.is_none() || !spectator.active() {
+        return;
+    }
+
+    let delta = time.delta_seconds();
+    recording.next_keyframe -= delta;
+    if recording.next_keyframe <= 0.0 {
+        recording.next_keyframe += KEYFRAME_INTERVAL;
+        let keyframe = Keyframe {
+            time: recording.elapsed,
+            pose: spectator.pose(),
+        };
+        if let Some(path) = &mut recording.path {
+            path.keyframes.push(keyframe);
+        }
+    }
+
+    recording.elapsed += delta;
+}
+
+/// Starts following any flight path dropped onto the window.
+fn load_dropped_rails(mut dropped: EventReader<FileDragAndDrop>, mut rail: ResMut<Rail>) {
+    for event in dropped.iter() {
+        let path = match event {
+            FileDragAndDrop::DroppedFile { path_buf, .. } => path_buf,
+            _ => continue,
+        };
+
+        if !path.to_string_lossy().ends_with(FLIGHT_EXTENSION) {
+            continue;
+        }
+
+        match load(path) {
+            Ok(flight) => {
+                info!("Following the flight in {}", path.display());
+                *rail = Rail {
+                    path: Some(flight),
+                    elapsed: 0.0,
+                };
+            }
+            Err(error) => warn!("Couldn't load the flight in {}, {error}", path.display()),
+        }
+    }
+}
+
+fn load(path: &Path) -> Result<FlightPath, String> {
+    let contents = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let flight: FlightPath = ron::from_str(&contents).map_err(|error| error.to_string())?;
+
+    if flight.keyframes.is_empty() {
+        return Err("it has no keyframes".to_string());
+    }
+
+    Ok(flight)
+}
+
+/// Moves the spectator along the rail, and flies it home once the rail runs out.
+fn ride_rail(time: Res<Time>, mut rail: ResMut<Rail>, mut spectator: ResMut<Spectator>) {
+    let elapsed = rail.elapsed;
+    let path = match &rail.path {
+        Some(path) => path,
+        None => return,
+    };
+
+    // Toggling the spectator off, or anything else taking it off the rail, ends the ride.
+    let interrupted = elapsed > 0.0 && !spectator.on_rail();
+    if interrupted || elapsed > path.duration() {
+        if !interrupted {
+            spectator.leave_rail();
+        }
+        rail.path = None;
+        return;
+    }
+
+    if let Some(pose) = path.sample(elapsed) {
+        spectator.follow_rail(pose);
+    }
+    rail.elapsed += time.delta_seconds();
+}
@@ -0,0 +1,101 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::theme::Theme;
+use crate::Star;
+
+/// How much bigger the glow is than the star it surrounds.
+const GLOW_SCALE: f32 = 3.0;
+
+pub struct GlowPlugin;
+impl Plugin for GlowPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GlowBoost>()
+            .add_system_to_stage(CoreStage::PreUpdate, clear_glows)
+            .add_system(add_glows)
+            .add_system(boost_glows.after(add_glows));
+    }
+}
+
+/// Points a star at the faint halo drawn around it.
+#[derive(Component)]
+struct StarGlow(Entity);
+
+/// The halo itself.
+#[derive(Component)]
+struct Glow;
+
+/// How much bigger than usual glows are drawn, for effects that briefly light the field up.
+pub struct GlowBoost(pub f32);
+
+impl Default for GlowBoost {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Removes every glow when the theme changes so they get rebuilt with the new colors.
+fn clear_glows(mut commands: Commands, theme: Res<Theme>, query: Query<(Entity, &StarGlow)>) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    for (star, glow) in query.iter() {
+        commands.entity(glow.0).despawn();
+        commands.entity(star).remove::<StarGlow>();
+    }
+}
+
+/// Gives every star without a glow a translucent, scaled-up copy of itself as a child.
+#[allow(clippy::type_complexity)]
+fn add_glows(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    boost: Res<GlowBoost>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    query: Query<(Entity, &Star, &Mesh2dHandle), Without<StarGlow>>,
+) {
+    if theme.glow <= 0.0 {
+        return;
+    }
+
+    for (entity, star, mesh) in query.iter() {
+        let material = pool.get(
+            *theme.star_color(star.tint).set_a(theme.glow),
+            &mut materials,
+        );
+
+        // Sits just behind the star so the star itself stays crisp.
+        let transform =
+            Transform::from_xyz(0.0, 0.0, -0.01).with_scale(Vec3::splat(GLOW_SCALE * boost.0));
+
+        let glow = commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: mesh.clone(),
+                material,
+                transform,
+                ..default()
+            })
+            .insert(Glow)
+            .id();
+
+        commands
+            .entity(entity)
+            .add_child(glow)
+            .insert(StarGlow(glow));
+    }
+}
+
+fn boost_glows(boost: Res<GlowBoost>, mut query: Query<&mut Transform, With<Glow>>) {
+    if !boost.is_changed() {
+        return;
+    }
+
+    for mut transform in query.iter_mut() {
+        transform.scale = Vec3::splat(GLOW_SCALE * boost.0);
+    }
+}
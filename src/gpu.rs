@@ -0,0 +1,560 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Another way of drawing the field, which keeps up with a million stars or more.
+//!
+//! Instead of an entity each, the stars live in one storage buffer on the GPU. A compute shader moves and
+//! recycles them every frame and a single instanced draw puts them all on screen. It's picked with the
+//! `renderer` setting. The theme, depth cues, fading in and every kind of motion carry over, but effects
+//! that work star by star, like trails, glows, twinkling, merging and attractors, need entity stars.
+//! Compute shaders aren't available on WebGL2 or on some older adapters, so there the field falls back to
+//! entity stars.
+
+use std::num::NonZeroU64;
+
+use bevy::asset::load_internal_asset;
+use bevy::core_pipeline::core_2d::Transparent2d;
+use bevy::ecs::system::lifetimeless::SRes;
+use bevy::ecs::system::SystemParamItem;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::render_graph::{self, NodeRunError, RenderGraph, RenderGraphContext};
+use bevy::render::render_phase::{
+    AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+    SetItemPipeline, TrackedRenderPass,
+};
+use bevy::render::render_resource::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages,
+    CachedComputePipelineId, ColorTargetState, ColorWrites, ComputePassDescriptor,
+    ComputePipelineDescriptor, FragmentState, MultisampleState, PipelineCache, PrimitiveState,
+    RenderPipelineDescriptor, ShaderStages, ShaderType, SpecializedRenderPipeline,
+    SpecializedRenderPipelines, TextureFormat, UniformBuffer, VertexState,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice, RenderQueue};
+use bevy::render::texture::BevyDefault;
+use bevy::render::{main_graph, RenderApp, RenderStage};
+use bevy::sprite::{Mesh2dPipeline, SetMesh2dViewBindGroup};
+use bevy::utils::FloatOrd;
+use serde::{Deserialize, Serialize};
+
+use crate::materials::BlendMode;
+use crate::settings::StarfieldSettings;
+use crate::sim::{self, Field, MotionMode};
+use crate::speed::SimulationSpeed;
+use crate::theme::Theme;
+use crate::PHYSICS_STEP;
+
+const SIMULATE_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x41b7_e60d_93c2_5af8);
+
+const DRAW_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0xc93e_0a54_17f6_b2d1);
+
+/// Bytes each star takes up in the storage buffer, the size of `Star` in the shaders.
+const STAR_STRIDE: u64 = 32;
+
+/// Stars each compute workgroup moves, the `workgroup_size` of the simulate shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Most stars the GPU can hold. Any more and they'd need more workgroups than can be dispatched at once.
+const MAX_GPU_STARS: u32 = 65_535 * WORKGROUP_SIZE;
+
+/// Level with the farthest entity stars.
+const GPU_STARS_Z: f32 = 1.0;
+
+const SIMULATE_NODE: &str = "gpu_stars_simulate";
+
+pub struct GpuStarsPlugin;
+impl Plugin for GpuStarsPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            SIMULATE_SHADER_HANDLE,
+            "shaders/gpu_stars_simulate.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            DRAW_SHADER_HANDLE,
+            "shaders/gpu_stars.wgsl",
+            Shader::from_wgsl
+        );
+
+        let supported = GpuStarsSupported(
+            app.world
+                .get_resource::<RenderDevice>()
+                .map_or(false, supports_gpu_stars),
+        );
+
+        app.register_type::<Renderer>()
+            .insert_resource(supported)
+            .init_resource::<GpuStars>()
+            .add_system(fall_back_to_entities.before(update_gpu_stars))
+            .add_system(update_gpu_stars);
+
+        if !supported.0 {
+            return;
+        }
+
+        app.add_plugin(ExtractResourcePlugin::<GpuStars>::default());
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<GpuStarsPipeline>()
+                .init_resource::<SpecializedRenderPipelines<GpuStarsPipeline>>()
+                .init_resource::<GpuStarBuffers>()
+                .add_render_command::<Transparent2d, DrawGpuStars>()
+                .add_system_to_stage(RenderStage::Prepare, prepare_gpu_stars)
+                .add_system_to_stage(RenderStage::Queue, queue_gpu_stars);
+
+            let mut graph = render_app.world.resource_mut::<RenderGraph>();
+            graph.add_node(SIMULATE_NODE, SimulateNode);
+            graph
+                .add_node_edge(SIMULATE_NODE, main_graph::node::CAMERA_DRIVER)
+                .expect("the camera driver is always in the render graph");
+        }
+    }
+}
+
+/// Whether the adapter can run the GPU renderer at all.
+#[derive(Clone, Copy, Debug)]
+pub struct GpuStarsSupported(pub bool);
+
+/// The GPU renderer needs compute shaders and storage buffers, which WebGL2 and some older adapters lack.
+fn supports_gpu_stars(device: &RenderDevice) -> bool {
+    if cfg!(target_arch = "wasm32") {
+        return false;
+    }
+
+    let limits = device.limits();
+    limits.max_storage_buffers_per_shader_stage >= 1
+        && limits.max_compute_workgroups_per_dimension >= 1
+        && limits.max_compute_invocations_per_workgroup >= WORKGROUP_SIZE
+}
+
+/// Switches back to entity stars if the GPU renderer is picked somewhere it can't run.
+fn fall_back_to_entities(
+    supported: Res<GpuStarsSupported>,
+    mut settings: ResMut<StarfieldSettings>,
+) {
+    if !supported.0 && settings.renderer == Renderer::Gpu {
+        warn!("This adapter can't run compute shaders, drawing the stars as entities instead");
+        settings.renderer = Renderer::Entities;
+    }
+}
+
+/// How the stars in the field are simulated and drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Renderer {
+    /// Every star is an entity, so every effect works. Comfortable up to tens of thousands of stars.
+    #[default]
+    Entities,
+    /// Stars are moved and drawn entirely on the GPU, for counts in the millions.
+    Gpu,
+}
+
+/// Everything the shaders need to know about the field this frame, laid out as `Params` in the shaders.
+#[derive(Clone, Copy, Default, ShaderType)]
+struct GpuStarParams {
+    star_color: Vec4,
+    star_tint: Vec4,
+    vanishing_point: Vec2,
+    extents: Vec2,
+    speed_range: Vec2,
+    delta: f32,
+    step: f32,
+    acceleration: f32,
+    depth_speed: f32,
+    depth_size: f32,
+    depth_brightness: f32,
+    fade_in: f32,
+    drift_speed: f32,
+    orbit_speed: f32,
+    rain_speed: f32,
+    count: u32,
+    seed: u32,
+    frame: u32,
+    /// Nonzero when the shader should scatter every star afresh instead of moving it.
+    reset: u32,
+    motion: u32,
+}
+
+/// The GPU stars as the main world sees them, copied to the render world each frame.
+#[derive(Clone, Default)]
+pub struct GpuStars {
+    params: GpuStarParams,
+    blend_mode: BlendMode,
+    /// Bumped whenever the stars need scattering afresh, because the seed or count changed.
+    generation: u32,
+}
+
+impl GpuStars {
+    /// How many stars are on the GPU, none unless the GPU renderer is picked.
+    pub fn count(&self) -> u32 {
+        self.params.count
+    }
+}
+
+impl ExtractResource for GpuStars {
+    type Source = GpuStars;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+fn motion_index(motion: MotionMode) -> u32 {
+    match motion {
+        MotionMode::FlyThrough => 0,
+        MotionMode::Drift => 1,
+        MotionMode::Orbit => 2,
+        MotionMode::Rain => 3,
+    }
+}
+
+/// Gathers up this frame's settings for the shaders.
+fn update_gpu_stars(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+    speed: Res<SimulationSpeed>,
+    field: Res<Field>,
+    mut gpu_stars: ResMut<GpuStars>,
+    mut applied: Local<Option<(u64, u32)>>,
+) {
+    let count = match settings.renderer {
+        Renderer::Entities => 0,
+        Renderer::Gpu => settings.star_count.min(MAX_GPU_STARS),
+    };
+
+    // The buffer is remade for a new seed or count, and every star scattered afresh.
+    let buffer = (settings.seed, count);
+    if applied.replace(buffer) != Some(buffer) {
+        gpu_stars.generation = gpu_stars.generation.wrapping_add(1);
+    }
+
+    let shape = settings.field;
+    let cues = settings.depth_cues;
+    let frame = gpu_stars.params.frame.wrapping_add(1);
+    gpu_stars.blend_mode = settings.blend_mode;
+    gpu_stars.params = GpuStarParams {
+        star_color: Vec4::from(theme.star_color.as_linear_rgba_f32()),
+        star_tint: Vec4::from(theme.star_tint.as_linear_rgba_f32()),
+        vanishing_point: field.vanishing_point(),
+        extents: field.extents(),
+        speed_range: Vec2::new(shape.speed_range.0, shape.speed_range.1),
+        delta: time.delta_seconds() * speed.current * speed.pulse * speed.audio,
+        step: PHYSICS_STEP as f32,
+        acceleration: shape.acceleration,
+        depth_speed: cues.speed,
+        depth_size: cues.size,
+        depth_brightness: cues.brightness,
+        fade_in: settings.fade.fade_in,
+        drift_speed: sim::DRIFT_SPEED,
+        orbit_speed: sim::ORBIT_SPEED,
+        rain_speed: sim::RAIN_SPEED,
+        count,
+        seed: (settings.seed ^ (settings.seed >> 32)) as u32,
+        frame,
+        reset: 0,
+        motion: motion_index(settings.motion),
+    };
+}
+
+struct GpuStarsPipeline {
+    simulate_layout: BindGroupLayout,
+    draw_layout: BindGroupLayout,
+    view_layout: BindGroupLayout,
+    simulate: CachedComputePipelineId,
+}
+
+impl FromWorld for GpuStarsPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let device = world.resource::<RenderDevice>();
+        let layout = |label, visibility, read_only| {
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: Some(GpuStarParams::min_size()),
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only },
+                            has_dynamic_offset: false,
+                            min_binding_size: NonZeroU64::new(STAR_STRIDE),
+                        },
+                        count: None,
+                    },
+                ],
+            })
+        };
+        let simulate_layout = layout("gpu_stars_simulate_layout", ShaderStages::COMPUTE, false);
+        let draw_layout = layout("gpu_stars_draw_layout", ShaderStages::VERTEX, true);
+        let view_layout = world.resource::<Mesh2dPipeline>().view_layout.clone();
+
+        let simulate = world
+            .resource_mut::<PipelineCache>()
+            .queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some("gpu_stars_simulate_pipeline".into()),
+                layout: Some(vec![simulate_layout.clone()]),
+                shader: SIMULATE_SHADER_HANDLE.typed(),
+                shader_defs: vec![],
+                entry_point: "simulate".into(),
+            });
+
+        Self {
+            simulate_layout,
+            draw_layout,
+            view_layout,
+            simulate,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct GpuStarsKey {
+    samples: u32,
+    blend_mode: BlendMode,
+}
+
+impl SpecializedRenderPipeline for GpuStarsPipeline {
+    type Key = GpuStarsKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("gpu_stars_pipeline".into()),
+            layout: Some(vec![self.view_layout.clone(), self.draw_layout.clone()]),
+            vertex: VertexState {
+                shader: DRAW_SHADER_HANDLE.typed(),
+                shader_defs: vec![],
+                entry_point: "vertex".into(),
+                buffers: vec![],
+            },
+            fragment: Some(FragmentState {
+                shader: DRAW_SHADER_HANDLE.typed(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(key.blend_mode.blend_state()),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        }
+    }
+}
+
+/// The GPU stars' buffers and bind groups in the render world.
+#[derive(Default)]
+struct GpuStarBuffers {
+    params: UniformBuffer<GpuStarParams>,
+    stars: Option<Buffer>,
+    count: u32,
+    /// Which generation of [`GpuStars`] the stars buffer was made for.
+    generation: u32,
+    /// Set while a fresh stars buffer is waiting for the simulate shader to scatter its stars.
+    unscattered: bool,
+    simulate_bind_group: Option<BindGroup>,
+    draw_bind_group: Option<BindGroup>,
+}
+
+/// Makes a fresh stars buffer when the field changes and uploads this frame's params.
+fn prepare_gpu_stars(
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    pipeline: Res<GpuStarsPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    gpu_stars: Res<GpuStars>,
+    mut buffers: ResMut<GpuStarBuffers>,
+) {
+    let mut params = gpu_stars.params;
+    if params.count == 0 {
+        *buffers = GpuStarBuffers::default();
+        return;
+    }
+
+    if buffers.stars.is_none() || buffers.generation != gpu_stars.generation {
+        buffers.stars = Some(device.create_buffer(&BufferDescriptor {
+            label: Some("gpu_stars"),
+            size: u64::from(params.count) * STAR_STRIDE,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        }));
+        buffers.count = params.count;
+        buffers.generation = gpu_stars.generation;
+        buffers.unscattered = true;
+    }
+
+    // The shader might still be compiling, in which case nothing runs this frame and scattering waits.
+    if buffers.unscattered {
+        params.reset = 1;
+        buffers.unscattered = pipeline_cache
+            .get_compute_pipeline(pipeline.simulate)
+            .is_none();
+    }
+
+    buffers.params.set(params);
+    buffers.params.write_buffer(&device, &queue);
+
+    let bind_group = |label, layout| {
+        let params = buffers.params.binding()?;
+        let stars = buffers.stars.as_ref()?.as_entire_binding();
+        Some(device.create_bind_group(&BindGroupDescriptor {
+            label: Some(label),
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: params,
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: stars,
+                },
+            ],
+        }))
+    };
+    let simulate_bind_group =
+        bind_group("gpu_stars_simulate_bind_group", &pipeline.simulate_layout);
+    let draw_bind_group = bind_group("gpu_stars_draw_bind_group", &pipeline.draw_layout);
+    buffers.simulate_bind_group = simulate_bind_group;
+    buffers.draw_bind_group = draw_bind_group;
+}
+
+/// Adds the GPU stars to every 2D view.
+#[allow(clippy::too_many_arguments)]
+fn queue_gpu_stars(
+    mut commands: Commands,
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    pipeline: Res<GpuStarsPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<GpuStarsPipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    msaa: Res<Msaa>,
+    gpu_stars: Res<GpuStars>,
+    buffers: Res<GpuStarBuffers>,
+    mut views: Query<&mut RenderPhase<Transparent2d>>,
+) {
+    if buffers.draw_bind_group.is_none() {
+        return;
+    }
+
+    let draw_function = draw_functions
+        .read()
+        .get_id::<DrawGpuStars>()
+        .expect("the GPU stars draw function is registered");
+    let key = GpuStarsKey {
+        samples: msaa.samples,
+        blend_mode: gpu_stars.blend_mode,
+    };
+    let pipeline = pipelines.specialize(&mut pipeline_cache, &pipeline, key);
+
+    // Phase items have to point at an entity, even though everything the draw needs is in resources.
+    let entity = commands.spawn().id();
+    for mut phase in views.iter_mut() {
+        phase.add(Transparent2d {
+            sort_key: FloatOrd(GPU_STARS_Z),
+            entity,
+            pipeline,
+            draw_function,
+            batch_range: None,
+        });
+    }
+}
+
+type DrawGpuStars = (
+    SetItemPipeline,
+    SetMesh2dViewBindGroup<0>,
+    SetGpuStarsBindGroup<1>,
+    DrawGpuStarInstances,
+);
+
+struct SetGpuStarsBindGroup<const I: usize>;
+impl<const I: usize> EntityRenderCommand for SetGpuStarsBindGroup<I> {
+    type Param = SRes<GpuStarBuffers>;
+
+    fn render<'w>(
+        _view: Entity,
+        _item: Entity,
+        buffers: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        match &buffers.into_inner().draw_bind_group {
+            Some(bind_group) => {
+                pass.set_bind_group(I, bind_group, &[]);
+                RenderCommandResult::Success
+            }
+            None => RenderCommandResult::Failure,
+        }
+    }
+}
+
+/// Draws a quad for every star, the vertex shader places each one from the stars buffer.
+struct DrawGpuStarInstances;
+impl EntityRenderCommand for DrawGpuStarInstances {
+    type Param = SRes<GpuStarBuffers>;
+
+    fn render<'w>(
+        _view: Entity,
+        _item: Entity,
+        buffers: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        pass.draw(0..6, 0..buffers.count);
+        RenderCommandResult::Success
+    }
+}
+
+/// Runs the simulate shader over every star, before any camera draws them.
+struct SimulateNode;
+impl render_graph::Node for SimulateNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let buffers = world.resource::<GpuStarBuffers>();
+        let pipeline = world.resource::<GpuStarsPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+
+        let bind_group = match &buffers.simulate_bind_group {
+            Some(bind_group) => bind_group,
+            None => return Ok(()),
+        };
+        let simulate = match pipeline_cache.get_compute_pipeline(pipeline.simulate) {
+            Some(simulate) => simulate,
+            None => return Ok(()),
+        };
+
+        let mut pass = render_context
+            .command_encoder
+            .begin_compute_pass(&ComputePassDescriptor {
+                label: Some("gpu_stars_simulate"),
+            });
+        pass.set_pipeline(simulate);
+        pass.set_bind_group(0, bind_group, &[]);
+        // Enough workgroups to cover every star, the last one only partly filled.
+        let workgroups = (buffers.count + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+        pass.dispatch_workgroups(workgroups, 1, 1);
+        Ok(())
+    }
+}
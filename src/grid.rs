@@ -0,0 +1,44 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Buckets points into square cells so finding close neighbors doesn't mean checking every pair.
+
+use std::collections::HashMap;
+
+use glam::Vec2;
+
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(Vec2, T)>>,
+}
+
+impl<T> SpatialGrid<T> {
+    /// Neighbors are only found reliably within `cell_size` of each other.
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, position: Vec2, item: T) {
+        self.cells
+            .entry(self.cell(position))
+            .or_default()
+            .push((position, item));
+    }
+
+    /// Everything in the cell containing `position` and the eight cells around it.
+    pub fn nearby(&self, position: Vec2) -> impl Iterator<Item = &(Vec2, T)> {
+        let (x, y) = self.cell(position);
+
+        (-1..=1)
+            .flat_map(move |dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+    }
+
+    fn cell(&self, position: Vec2) -> (i32, i32) {
+        let cell = (position / self.cell_size).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+}
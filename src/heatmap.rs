@@ -0,0 +1,89 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A debug view that colors every star by its depth, so problems with how depth is handed out stand out.
+
+use bevy::prelude::*;
+
+use crate::actions::Action;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::theme::Theme;
+use crate::Star;
+
+/// A handful of evenly spaced stops along the viridis color map, from dark purple to yellow.
+const VIRIDIS: [[f32; 3]; 5] = [
+    [0.267, 0.005, 0.329],
+    [0.229, 0.322, 0.546],
+    [0.128, 0.567, 0.551],
+    [0.369, 0.789, 0.383],
+    [0.993, 0.906, 0.144],
+];
+
+pub struct HeatmapPlugin;
+impl Plugin for HeatmapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DepthHeatmap>()
+            .add_system(toggle_heatmap)
+            .add_system(color_by_depth.after(toggle_heatmap));
+    }
+}
+
+#[derive(Default)]
+pub struct DepthHeatmap {
+    pub enabled: bool,
+}
+
+fn toggle_heatmap(
+    mut actions: EventReader<Action>,
+    mut heatmap: ResMut<DepthHeatmap>,
+    theme: Res<Theme>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    mut query: Query<(&Star, &mut Handle<StarMaterial>)>,
+) {
+    for action in actions.iter() {
+        if *action != Action::ToggleDepthHeatmap {
+            continue;
+        }
+
+        heatmap.enabled = !heatmap.enabled;
+
+        // Put the theme's colors back when turning it off.
+        if !heatmap.enabled {
+            for (star, mut material) in query.iter_mut() {
+                *material = pool.get(theme.star_color(star.tint), &mut materials);
+            }
+        }
+    }
+}
+
+/// Keeps stars colored by depth while the heatmap is on, recycled stars get a new depth so this runs every frame.
+fn color_by_depth(
+    heatmap: Res<DepthHeatmap>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    mut query: Query<(&Star, &mut Handle<StarMaterial>)>,
+) {
+    if !heatmap.enabled {
+        return;
+    }
+
+    for (star, mut material) in query.iter_mut() {
+        // Near stars are the bright end of the map.
+        let colored = pool.get(viridis(1.0 - star.depth), &mut materials);
+        if *material != colored {
+            *material = colored;
+        }
+    }
+}
+
+/// Samples the viridis color map, `t` going from 0.0 to 1.0.
+fn viridis(t: f32) -> Color {
+    let scaled = t.clamp(0.0, 1.0) * (VIRIDIS.len() - 1) as f32;
+    let index = (scaled.floor() as usize).min(VIRIDIS.len() - 2);
+    let blend = scaled - index as f32;
+
+    let from = Vec3::from(VIRIDIS[index]);
+    let to = Vec3::from(VIRIDIS[index + 1]);
+    let [r, g, b] = from.lerp(to, blend).to_array();
+    Color::rgb(r, g, b)
+}
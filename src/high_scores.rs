@@ -0,0 +1,100 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! The best dodge game runs, kept in a `high_scores.ron` file in the config directory.
+
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::persist::config_path;
+
+const FILE_NAME: &str = "high_scores.ron";
+
+/// How many runs the table keeps.
+pub const TABLE_SIZE: usize = 10;
+
+/// How many letters of initials are kept with a score.
+pub const INITIALS_LENGTH: usize = 3;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub initials: String,
+    pub score: u32,
+    /// Seconds the run lasted.
+    pub survived: f32,
+}
+
+/// The table of best runs, highest score first.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    /// Loads the table, starting an empty one if there isn't a readable file.
+    pub fn load() -> Self {
+        let path = match config_path(FILE_NAME) {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        match ron::from_str::<HighScores>(&contents) {
+            Ok(scores) => scores,
+            Err(error) => {
+                eprintln!("Ignoring high scores in {}, {error}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        let path = match config_path(FILE_NAME) {
+            Some(path) => path,
+            None => {
+                warn!("Couldn't find a config directory to save high scores to");
+                return;
+            }
+        };
+
+        let saved = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                if let Some(directory) = path.parent() {
+                    fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+                }
+                fs::write(&path, contents).map_err(|error| error.to_string())
+            });
+
+        if let Err(error) = saved {
+            warn!("Couldn't save high scores to {}, {error}", path.display());
+        }
+    }
+
+    /// Whether a score would make it onto the table.
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0
+            && (self.entries.len() < TABLE_SIZE
+                || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    /// Adds a run to the table, dropping whatever falls off the bottom, and saves it.
+    pub fn insert(&mut self, entry: HighScore) {
+        // Ties go to whoever got there first.
+        let position = self
+            .entries
+            .iter()
+            .position(|existing| entry.score > existing.score)
+            .unwrap_or(self.entries.len());
+
+        self.entries.insert(position, entry);
+        self.entries.truncate(TABLE_SIZE);
+        self.save();
+    }
+}
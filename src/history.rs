@@ -0,0 +1,123 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Undo and redo for settings changes, wherever they came from.
+
+use bevy::prelude::*;
+
+use crate::actions::Action;
+use crate::settings::StarfieldSettings;
+use crate::theme::Theme;
+
+/// Changes closer together than this are merged into one step, so dragging a slider doesn't fill the history.
+const MERGE_WINDOW: f64 = 0.5;
+
+/// How many steps back we remember.
+const MAX_HISTORY: usize = 100;
+
+pub struct HistoryPlugin;
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(start_history)
+            .add_system_to_stage(CoreStage::PreUpdate, send_undo_redo)
+            .add_system(undo_redo)
+            .add_system_to_stage(CoreStage::Last, record_history);
+    }
+}
+
+#[derive(Clone, PartialEq)]
+struct Snapshot {
+    settings: StarfieldSettings,
+    theme: Theme,
+}
+
+struct History {
+    current: Snapshot,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_recorded: f64,
+}
+
+fn start_history(mut commands: Commands, settings: Res<StarfieldSettings>, theme: Res<Theme>) {
+    commands.insert_resource(History {
+        current: Snapshot {
+            settings: settings.clone(),
+            theme: theme.clone(),
+        },
+        undo: Vec::new(),
+        redo: Vec::new(),
+        last_recorded: f64::NEG_INFINITY,
+    });
+}
+
+/// Ctrl+Z undoes, Ctrl+Shift+Z or Ctrl+Y redoes.
+fn send_undo_redo(keys: Res<Input<KeyCode>>, mut actions: EventWriter<Action>) {
+    if !keys.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+
+    let shift = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if keys.just_pressed(KeyCode::Z) {
+        actions.send(if shift { Action::Redo } else { Action::Undo });
+    } else if keys.just_pressed(KeyCode::Y) {
+        actions.send(Action::Redo);
+    }
+}
+
+fn undo_redo(
+    mut actions: EventReader<Action>,
+    mut history: ResMut<History>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+) {
+    for action in actions.iter() {
+        let history = &mut *history;
+        let (from, to) = match action {
+            Action::Undo => (&mut history.undo, &mut history.redo),
+            Action::Redo => (&mut history.redo, &mut history.undo),
+            _ => continue,
+        };
+
+        if let Some(snapshot) = from.pop() {
+            to.push(std::mem::replace(&mut history.current, snapshot));
+            *settings = history.current.settings.clone();
+            *theme = history.current.theme.clone();
+
+            // Never merge whatever comes next into the step we just restored.
+            history.last_recorded = f64::NEG_INFINITY;
+        }
+    }
+}
+
+/// Remembers the state before every change. Undo and redo themselves restore `current`, so they're never recorded.
+fn record_history(
+    time: Res<Time>,
+    mut history: ResMut<History>,
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+) {
+    if !settings.is_changed() && !theme.is_changed() {
+        return;
+    }
+
+    let snapshot = Snapshot {
+        settings: settings.clone(),
+        theme: theme.clone(),
+    };
+    if snapshot == history.current {
+        return;
+    }
+
+    let now = time.seconds_since_startup();
+    if now - history.last_recorded > MERGE_WINDOW {
+        let previous = std::mem::replace(&mut history.current, snapshot);
+        history.undo.push(previous);
+        if history.undo.len() > MAX_HISTORY {
+            history.undo.remove(0);
+        }
+    } else {
+        history.current = snapshot;
+    }
+
+    history.redo.clear();
+    history.last_recorded = now;
+}
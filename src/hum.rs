@@ -0,0 +1,100 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A synthesized engine hum, so sound effects don't need audio files shipped alongside the binary.
+
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use bevy::audio::{play_queued_audio_system, AudioOutput, Decodable};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+
+const SAMPLE_RATE: u32 = 44_100;
+
+/// Seconds the hum takes to fade out at the end, so it doesn't stop with a click.
+const FADE_OUT: f32 = 0.1;
+
+pub struct HumPlugin;
+impl Plugin for HumPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Hum>()
+            .init_non_send_resource::<AudioOutput<Hum>>()
+            .init_resource::<Audio<Hum>>()
+            .add_system_to_stage(CoreStage::PostUpdate, play_queued_audio_system::<Hum>);
+    }
+}
+
+/// A hum that swells and rises in pitch over its duration, like an engine spooling up.
+#[derive(Clone, Copy, TypeUuid)]
+#[uuid = "5d3b7c1e-92a4-4f6d-b0e8-3c71a9f4d2b6"]
+pub struct Hum {
+    pub duration: f32,
+    /// Pitch at the start and end, in hertz.
+    pub pitch: (f32, f32),
+    pub volume: f32,
+}
+
+impl Decodable for Hum {
+    type Decoder = HumDecoder;
+    type DecoderItem = f32;
+
+    fn decoder(&self) -> Self::Decoder {
+        HumDecoder {
+            hum: *self,
+            sample: 0,
+            phase: 0.0,
+        }
+    }
+}
+
+pub struct HumDecoder {
+    hum: Hum,
+    sample: u32,
+    phase: f32,
+}
+
+impl Iterator for HumDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let hum = self.hum;
+        let time = self.sample as f32 / SAMPLE_RATE as f32;
+        if time >= hum.duration {
+            return None;
+        }
+        self.sample += 1;
+
+        // Rises slowly at first and faster towards the end.
+        let progress = time / hum.duration;
+        let (start, end) = hum.pitch;
+        let pitch = start + (end - start) * progress * progress;
+        self.phase = (self.phase + pitch / SAMPLE_RATE as f32) % 1.0;
+
+        let swell = progress.sqrt();
+        let fade = ((hum.duration - time) / FADE_OUT).min(1.0);
+
+        // A few harmonics on top of the base tone give it some grit.
+        let angle = self.phase * TAU;
+        let tone = angle.sin() + 0.5 * (angle * 2.0).sin() + 0.25 * (angle * 3.0).sin();
+
+        Some(tone / 1.75 * hum.volume * swell * fade)
+    }
+}
+
+impl rodio::Source for HumDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(self.hum.duration))
+    }
+}
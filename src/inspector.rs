@@ -0,0 +1,36 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Debug windows for editing the settings and theme live through reflection.
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+use bevy_inspector_egui::reflect::ui_for_reflect;
+use bevy_inspector_egui::Context;
+
+use crate::settings::StarfieldSettings;
+use crate::theme::Theme;
+
+/// Shows every tunable in the settings and theme, only writing them back when something was edited.
+pub fn inspect_resources(
+    mut egui_context: ResMut<EguiContext>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+) {
+    let ctx = egui_context.ctx_mut();
+
+    let mut edited_settings = settings.clone();
+    egui::Window::new("Settings").vscroll(true).show(ctx, |ui| {
+        ui_for_reflect(&mut edited_settings, ui, &mut Context::new_shared(None));
+    });
+    if edited_settings != *settings {
+        *settings = edited_settings;
+    }
+
+    let mut edited_theme = theme.clone();
+    egui::Window::new("Theme settings").show(ctx, |ui| {
+        ui_for_reflect(&mut edited_theme, ui, &mut Context::new_shared(None));
+    });
+    if edited_theme != *theme {
+        *theme = edited_theme;
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Pulling and pushing stars around with the mouse.
+//!
+//! Holding the left button pulls nearby stars in towards the cursor and holding the right pushes them away,
+//! with a little sideways twist so they swirl around it. The push adds to the way stars would move anyway,
+//! and wears off the same way an attractor's pull does once the button is let go.
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::EguiContext;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::StarfieldSettings;
+use crate::star_editor::StarEditor;
+use crate::MainCamera;
+
+pub struct InteractionPlugin;
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<InteractionSettings>()
+            .init_resource::<PointerForce>()
+            .add_system(track_pointer);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InteractionSettings {
+    pub enabled: bool,
+    /// How far from the cursor stars feel it, in pixels.
+    pub radius: f32,
+    /// How hard the cursor pulls or pushes right next to it, in pixels per second squared.
+    pub strength: f32,
+    /// How much stars are turned around the cursor rather than straight towards or away from it, 0.0 to 1.0.
+    pub swirl: f32,
+}
+
+impl Default for InteractionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            radius: 250.0,
+            strength: 4000.0,
+            swirl: 0.35,
+        }
+    }
+}
+
+/// The force field around the cursor this frame, which pushes nothing while no button is held.
+#[derive(Default)]
+pub struct PointerForce {
+    center: Vec2,
+    /// Positive while pulling stars in, negative while pushing them away.
+    strength: f32,
+    radius: f32,
+    swirl: f32,
+}
+
+impl PointerForce {
+    pub fn active(&self) -> bool {
+        self.strength != 0.0 && self.radius > 0.0
+    }
+
+    /// How hard the cursor pulls or pushes on something at `position`, in pixels per second squared.
+    pub fn force(&self, position: Vec2) -> Vec2 {
+        let offset = self.center - position;
+        let distance = offset.length();
+        if !self.active() || distance >= self.radius || distance <= f32::EPSILON {
+            return Vec2::ZERO;
+        }
+
+        // Strongest at the cursor, easing off to nothing at the edge of the radius.
+        let falloff = (1.0 - distance / self.radius).powi(2);
+        let towards = offset / distance;
+        (towards + towards.perp() * self.swirl) * self.strength * falloff
+    }
+}
+
+/// Where the cursor is in the world, as seen through `camera`.
+pub fn cursor_to_world(
+    window: &Window,
+    camera: &Camera,
+    transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    let size = Vec2::new(window.width(), window.height());
+    if size.cmple(Vec2::ZERO).any() {
+        return None;
+    }
+
+    let ndc = cursor / size * 2.0 - Vec2::ONE;
+    let ndc_to_world = transform.compute_matrix() * camera.projection_matrix().inverse();
+    Some(ndc_to_world.project_point3(ndc.extend(0.0)).truncate())
+}
+
+/// Points the force field at the cursor while a mouse button is held over the field.
+fn track_pointer(
+    settings: Res<StarfieldSettings>,
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    egui_context: Option<ResMut<EguiContext>>,
+    editor: Option<Res<StarEditor>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut pointer: ResMut<PointerForce>,
+) {
+    let interaction = settings.interaction;
+    let direction = match (
+        buttons.pressed(MouseButton::Left),
+        buttons.pressed(MouseButton::Right),
+    ) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => 0.0,
+    };
+    // Holding a button over a menu or window shouldn't reach through to the stars behind it.
+    let over_ui = egui_context.map_or(false, |mut egui_context| {
+        egui_context.ctx_mut().wants_pointer_input()
+    });
+    // Clicks place and remove stars while the star editor is open.
+    let editing = editor.map_or(false, |editor| editor.open);
+
+    let center = windows
+        .get_primary()
+        .zip(cameras.get_single().ok())
+        .and_then(|(window, (camera, transform))| cursor_to_world(window, camera, transform));
+
+    *pointer = match center {
+        Some(center) if interaction.enabled && direction != 0.0 && !over_ui && !editing => {
+            PointerForce {
+                center,
+                strength: interaction.strength * direction,
+                radius: interaction.radius,
+                swirl: interaction.swirl.clamp(0.0, 1.0),
+            }
+        }
+        _ => PointerForce::default(),
+    };
+}
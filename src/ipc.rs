@@ -0,0 +1,99 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Lets other programs drive the field by sending action names, one per line, to a local TCP port.
+//!
+//! For example `echo HyperspaceJump | nc localhost 7878`.
+
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::settings::StarfieldSettings;
+
+pub struct IpcPlugin;
+impl Plugin for IpcPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<IpcSettings>()
+            .add_startup_system(start_listening)
+            .add_system(receive_actions);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpcSettings {
+    pub enabled: bool,
+    /// Only connections from this machine are accepted. Changes take effect on the next start.
+    pub port: u16,
+}
+
+impl Default for IpcSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7878,
+        }
+    }
+}
+
+struct IpcListener {
+    listener: TcpListener,
+    /// Each open connection, and whatever part of a line has arrived from it so far.
+    connections: Vec<(BufReader<TcpStream>, String)>,
+}
+
+fn start_listening(mut commands: Commands, settings: Res<StarfieldSettings>) {
+    if !settings.ipc.enabled {
+        return;
+    }
+
+    let port = settings.ipc.port;
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener));
+
+    match listener {
+        Ok(listener) => {
+            info!("Listening for actions on port {port}");
+            commands.insert_resource(IpcListener {
+                listener,
+                connections: Vec::new(),
+            });
+        }
+        Err(error) => warn!("Couldn't listen for actions on port {port}, {error}"),
+    }
+}
+
+/// Accepts new connections and sends an action for every complete line received.
+fn receive_actions(ipc: Option<ResMut<IpcListener>>, mut actions: EventWriter<Action>) {
+    let mut ipc = match ipc {
+        Some(ipc) => ipc,
+        None => return,
+    };
+
+    while let Ok((stream, _)) = ipc.listener.accept() {
+        if stream.set_nonblocking(true).is_ok() {
+            ipc.connections
+                .push((BufReader::new(stream), String::new()));
+        }
+    }
+
+    ipc.connections.retain_mut(|(connection, line)| loop {
+        match connection.read_line(line) {
+            // The other end hung up.
+            Ok(0) => return false,
+            Ok(_) if line.ends_with('\n') => {
+                match ron::from_str::<Action>(line.trim()) {
+                    Ok(action) => actions.send(action),
+                    Err(_) => warn!("Ignoring unknown action `{}`", line.trim()),
+                }
+                line.clear();
+            }
+            // Half a line, the rest will come later.
+            Ok(_) => {}
+            Err(error) => return error.kind() == ErrorKind::WouldBlock,
+        }
+    });
+}
@@ -0,0 +1,222 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A scripted hyperspace jump: the engines charge up with a hum and the stars glow, everything whites out,
+//! the field streaks past in a brief tunnel, and it arrives in a freshly seeded field with a new palette.
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::glow::GlowBoost;
+use crate::hum::Hum;
+use crate::settings::StarfieldSettings;
+use crate::sim::{Field, StarfieldRng};
+use crate::speed::SimulationSpeed;
+use crate::state::{self, AppState};
+use crate::theme::Theme;
+use crate::{reseed_field, Star};
+
+/// Seconds each part of the jump takes.
+const CHARGE_DURATION: f32 = 2.5;
+const FLASH_DURATION: f32 = 0.4;
+const TUNNEL_DURATION: f32 = 1.2;
+
+/// How much faster the field moves at the end of the charge, and in the tunnel.
+const CHARGE_SPEED: f32 = 3.0;
+const TUNNEL_SPEED: f32 = 25.0;
+
+/// How much bigger glows get at the end of the charge.
+const CHARGE_GLOW: f32 = 3.0;
+
+/// The flash sits in front of every star, inside the 2D camera's visible range.
+const FLASH_Z: f32 = 999.0;
+
+pub struct JumpPlugin;
+impl Plugin for JumpPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<JumpSettings>()
+            .init_resource::<Jump>()
+            .add_startup_system(setup_jump)
+            .add_system(start_jump.with_run_criteria(state::field_shown))
+            .add_system(run_jump.with_run_criteria(state::running).after(start_jump));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JumpSettings {
+    /// Seconds between automatic jumps, 0 only jumps when asked to.
+    pub interval: f32,
+    pub hum_volume: f32,
+}
+
+impl Default for JumpSettings {
+    fn default() -> Self {
+        Self {
+            interval: 0.0,
+            hum_volume: 0.4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Phase {
+    #[default]
+    Idle,
+    Charging,
+    Flash,
+    Tunnel,
+}
+
+#[derive(Default)]
+struct Jump {
+    phase: Phase,
+    /// Seconds into the current phase.
+    elapsed: f32,
+    /// Seconds since the last jump finished, for automatic jumps.
+    since_last: f32,
+}
+
+/// The white sprite covering the screen during the flash.
+#[derive(Component)]
+struct JumpFlash;
+
+fn setup_jump(mut commands: Commands) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(1.0, 1.0, 1.0, 0.0),
+                // Comfortably bigger than any window or capture.
+                custom_size: Some(Vec2::splat(100_000.0)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, FLASH_Z),
+            ..default()
+        })
+        .insert(JumpFlash);
+}
+
+/// Starts a jump when asked to or when the next automatic one is due.
+fn start_jump(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    state: Res<State<AppState>>,
+    mut actions: EventReader<Action>,
+    mut jump: ResMut<Jump>,
+    mut hums: ResMut<Assets<Hum>>,
+    audio: Res<Audio<Hum>>,
+) {
+    let requested = actions
+        .iter()
+        .any(|action| *action == Action::HyperspaceJump);
+
+    if *state.current() == AppState::Running {
+        jump.since_last += time.delta_seconds();
+    }
+    let interval = settings.jump.interval;
+    let due = interval > 0.0 && jump.since_last >= interval;
+
+    if jump.phase != Phase::Idle || !(requested || due) {
+        return;
+    }
+
+    *jump = Jump {
+        phase: Phase::Charging,
+        ..default()
+    };
+
+    audio.play(hums.add(Hum {
+        duration: CHARGE_DURATION + FLASH_DURATION,
+        pitch: (40.0, 160.0),
+        volume: settings.jump.hum_volume,
+    }));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_jump(
+    time: Res<Time>,
+    mut jump: ResMut<Jump>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut glow: ResMut<GlowBoost>,
+    mut field: ResMut<Field>,
+    mut rng: ResMut<StarfieldRng>,
+    mut flash: Query<&mut Sprite, With<JumpFlash>>,
+    mut stars: Query<(&mut Star, &mut Transform)>,
+) {
+    if jump.phase == Phase::Idle {
+        return;
+    }
+
+    jump.elapsed += time.delta_seconds();
+
+    let duration = match jump.phase {
+        Phase::Idle => return,
+        Phase::Charging => CHARGE_DURATION,
+        Phase::Flash => FLASH_DURATION,
+        Phase::Tunnel => TUNNEL_DURATION,
+    };
+    let progress = (jump.elapsed / duration).min(1.0);
+
+    // How fast the field moves, how big glows are and how white the screen is for this part of the jump.
+    let (speed_multiplier, glow_boost, whiteness) = match jump.phase {
+        Phase::Idle => (1.0, 1.0, 0.0),
+        Phase::Charging => (
+            1.0 + (CHARGE_SPEED - 1.0) * progress,
+            1.0 + (CHARGE_GLOW - 1.0) * progress,
+            0.0,
+        ),
+        Phase::Flash => (CHARGE_SPEED, CHARGE_GLOW, progress),
+        Phase::Tunnel => (TUNNEL_SPEED, 1.0, (1.0 - progress * 4.0).max(0.0)),
+    };
+
+    speed.jump = speed_multiplier;
+    if glow.0 != glow_boost {
+        glow.0 = glow_boost;
+    }
+    for mut sprite in flash.iter_mut() {
+        sprite.color.set_a(whiteness);
+    }
+
+    if progress < 1.0 {
+        return;
+    }
+
+    jump.elapsed = 0.0;
+    jump.phase = match jump.phase {
+        Phase::Idle => Phase::Idle,
+        Phase::Charging => Phase::Flash,
+        Phase::Flash => {
+            // The screen is fully white, so the new field can be swapped in unseen.
+            arrive(&mut settings, &mut theme, &mut field, &mut rng, &mut stars);
+            Phase::Tunnel
+        }
+        Phase::Tunnel => {
+            speed.jump = 1.0;
+            jump.since_last = 0.0;
+            Phase::Idle
+        }
+    };
+}
+
+/// Reseeds the field, scatters every star through it again and picks a different palette.
+fn arrive(
+    settings: &mut StarfieldSettings,
+    theme: &mut Theme,
+    field: &mut Field,
+    rng: &mut StarfieldRng,
+    stars: &mut Query<(&mut Star, &mut Transform)>,
+) {
+    settings.seed = rand::random();
+    reseed_field(settings.seed, field, rng, stars);
+
+    let others: Vec<&Theme> = Theme::PRESETS
+        .iter()
+        .map(|(_, preset)| preset)
+        .filter(|preset| *preset != theme)
+        .collect();
+    let pick = rng.effects.gen_range(0..others.len());
+    *theme = others[pick].clone();
+}
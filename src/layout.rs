@@ -0,0 +1,204 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Shows star layouts loaded from CSV or JSON files as a static layer alongside the procedural field.
+//!
+//! Each entry has an `x` and `y` in pixels from the middle of the screen, and optionally a `z` depth from
+//! 0.0 (nearest) to 1.0 (farthest), a `size` radius in pixels, and a hex `color` like `#ffcc00`.
+//! CSV files list them as columns in that order, with an optional header row.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use serde::Deserialize;
+
+use crate::depth::depth_to_z;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::star_mesh::StarMesh;
+
+pub struct LayoutPlugin {
+    /// A layout to show from the start.
+    pub path: Option<PathBuf>,
+}
+
+impl Plugin for LayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ImportedStar>()
+            .insert_resource(LayoutImports(self.path.iter().cloned().collect()))
+            .add_system(import_dropped_layouts)
+            .add_system(import_layouts.after(import_dropped_layouts));
+    }
+}
+
+/// A star loaded from a layout file.
+#[derive(Clone, Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct ImportedStar {
+    pub color: Color,
+}
+
+/// Layout files waiting to be imported.
+struct LayoutImports(Vec<PathBuf>);
+
+#[derive(Deserialize)]
+struct LayoutEntry {
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    z: f32,
+    #[serde(default = "default_size")]
+    size: f32,
+    #[serde(default)]
+    color: Option<String>,
+}
+
+fn default_size() -> f32 {
+    1.0
+}
+
+#[derive(Debug)]
+enum LayoutError {
+    /// The file couldn't be read at all.
+    Read,
+    /// Neither a `.csv` nor a `.json` file.
+    Format,
+    /// The JSON didn't describe a list of stars.
+    Json,
+    /// A line of the CSV file didn't make sense.
+    Line(usize),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::Read => write!(f, "the file couldn't be read"),
+            LayoutError::Format => write!(f, "only .csv and .json layouts are supported"),
+            LayoutError::Json => write!(f, "the file isn't a JSON list of stars"),
+            LayoutError::Line(line) => write!(f, "line {line} isn't a valid star"),
+        }
+    }
+}
+
+/// Reads a layout file, picking the format from its extension.
+fn load(path: &Path) -> Result<Vec<LayoutEntry>, LayoutError> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    let contents = fs::read_to_string(path).map_err(|_| LayoutError::Read)?;
+
+    match extension.as_deref() {
+        Some("json") => serde_json::from_str(&contents).map_err(|_| LayoutError::Json),
+        Some("csv") => parse_csv(&contents),
+        _ => Err(LayoutError::Format),
+    }
+}
+
+fn parse_csv(contents: &str) -> Result<Vec<LayoutEntry>, LayoutError> {
+    let mut entries = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+
+        let number = |column: usize| fields.get(column).and_then(|field| field.parse().ok());
+        let (x, y) = match (number(0), number(1)) {
+            (Some(x), Some(y)) => (x, y),
+            // Anything on the first line that isn't a number is taken to be a header.
+            _ if index == 0 => continue,
+            _ => return Err(LayoutError::Line(index + 1)),
+        };
+
+        entries.push(LayoutEntry {
+            x,
+            y,
+            z: number(2).unwrap_or_default(),
+            size: number(3).unwrap_or_else(default_size),
+            color: fields
+                .get(4)
+                .filter(|field| !field.is_empty())
+                .map(|field| field.to_string()),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Queues up layout files dropped onto the window.
+fn import_dropped_layouts(
+    mut dropped: EventReader<FileDragAndDrop>,
+    mut imports: ResMut<LayoutImports>,
+) {
+    for event in dropped.iter() {
+        if let FileDragAndDrop::DroppedFile { path_buf, .. } = event {
+            // Anything else dropped on the window is for someone else to handle.
+            let extension = path_buf
+                .extension()
+                .and_then(|extension| extension.to_str());
+            if matches!(extension, Some("csv" | "json" | "CSV" | "JSON")) {
+                imports.0.push(path_buf.clone());
+            }
+        }
+    }
+}
+
+/// Replaces the imported layer with the newest queued layout.
+fn import_layouts(
+    mut commands: Commands,
+    mut imports: ResMut<LayoutImports>,
+    star_mesh: Res<StarMesh>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    imported: Query<Entity, With<ImportedStar>>,
+) {
+    let path = match imports.0.pop() {
+        Some(path) => path,
+        None => return,
+    };
+    imports.0.clear();
+
+    let entries = match load(&path) {
+        Ok(entries) => entries,
+        Err(error) => {
+            warn!("Couldn't import {}, {error}", path.display());
+            return;
+        }
+    };
+
+    for entity in imported.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let mesh = Mesh2dHandle(star_mesh.0.clone());
+
+    for entry in &entries {
+        let color = match entry.color.as_deref() {
+            Some(hex) => Color::hex(hex.trim_start_matches('#')).unwrap_or_else(|_| {
+                warn!("Ignoring invalid color `{hex}` in {}", path.display());
+                Color::WHITE
+            }),
+            None => Color::WHITE,
+        };
+
+        let translation = Vec3::new(entry.x, entry.y, depth_to_z(entry.z));
+        let transform =
+            Transform::from_translation(translation).with_scale(Vec3::splat(entry.size));
+
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: mesh.clone(),
+                material: pool.get(color, &mut materials),
+                transform,
+                ..default()
+            })
+            .insert(ImportedStar { color });
+    }
+
+    info!("Imported {} stars from {}", entries.len(), path.display());
+}
@@ -0,0 +1,637 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A warp speed starfield for Bevy.
+//!
+//! Add [`StarfieldPlugin`] to draw the field as the background of any 2D app, or run the binary for the full
+//! screensaver with its menus, editors and exports. Every module is public so the app's other plugins can be
+//! picked and mixed in as well. [`volume::VolumePlugin`] draws the same field in true 3D instead.
+
+use bevy::prelude::*;
+use bevy::reflect::impl_reflect_value;
+use bevy::render::view::RenderLayers;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::time::{FixedTimestep, FixedTimesteps};
+use bevy::transform::TransformSystem;
+use bevy::window::{WindowCreated, WindowResized};
+use rand::Rng;
+
+use actions::ActionsPlugin;
+use afterimage::AfterimagePlugin;
+use asteroid::AsteroidPlugin;
+use attractor::{Attractor, AttractorPlugin};
+use bpm::BpmPlugin;
+use converge::ConvergePlugin;
+use cull::CullPlugin;
+use daily::DailyPlugin;
+use depth::{depth_to_z, DepthPlugin};
+use fade::FadePlugin;
+use glow::GlowPlugin;
+use gpu::{GpuStarsPlugin, Renderer};
+use hum::HumPlugin;
+use interaction::{InteractionPlugin, PointerForce};
+use jump::JumpPlugin;
+use loading_screen::StarfieldLoadingScreenPlugin;
+use materials::{MaterialPool, MaterialsPlugin, StarMaterial};
+use merge::MergePlugin;
+use nebula::NebulaPlugin;
+use parallax::ParallaxPlugin;
+use settings::StarfieldSettings;
+use shake::ShakePlugin;
+use shooting_star::ShootingStarPlugin;
+use sim::{Field, FieldShape, MotionMode, RngState, StarfieldRng};
+use spawn_distribution::SpawnDistribution;
+use spectral::SpectralPlugin;
+use speed::{SimulationSpeed, SpeedPlugin};
+use star_mesh::{StarMesh, StarMeshPlugin};
+use state::StatePlugin;
+use theme::{Theme, ThemePlugin};
+use trail::TrailPlugin;
+use tunnel::TunnelPlugin;
+use twinkle::TwinklePlugin;
+use warp::WarpPlugin;
+
+pub mod actions;
+pub mod afterimage;
+pub mod asteroid;
+pub mod attractor;
+#[cfg(feature = "audio-reactive")]
+pub mod audio_reactive;
+pub mod bench;
+pub mod bindings;
+pub mod boost;
+pub mod bpm;
+pub mod capture;
+pub mod clock;
+pub mod constellation;
+pub mod converge;
+pub mod crash;
+pub mod cull;
+pub mod daily;
+pub mod depth;
+pub mod diagnostics;
+pub mod dodge;
+pub mod event_log;
+pub mod export;
+pub mod fade;
+pub mod flight;
+pub mod glow;
+pub mod gpu;
+pub mod grid;
+pub mod heatmap;
+pub mod high_scores;
+pub mod history;
+pub mod hum;
+#[cfg(debug_assertions)]
+pub mod inspector;
+pub mod interaction;
+pub mod ipc;
+pub mod jump;
+pub mod layout;
+pub mod loading_screen;
+pub mod materials;
+pub mod menu;
+pub mod merge;
+pub mod nebula;
+pub mod palette;
+pub mod parallax;
+pub mod pause_menu;
+pub mod persist;
+pub mod post;
+pub mod randomize;
+pub mod record;
+pub mod scene;
+pub mod screensaver;
+pub mod settings;
+pub mod settings_panel;
+pub mod shake;
+pub mod share;
+pub mod shooting_star;
+pub mod sim;
+pub mod spawn_distribution;
+pub mod spectator;
+pub mod spectral;
+pub mod speed;
+pub mod star_editor;
+pub mod star_mesh;
+pub mod state;
+pub mod steer;
+pub mod sync;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod terminal;
+pub mod theme;
+pub mod theme_editor;
+pub mod trail;
+pub mod tunnel;
+pub mod twinkle;
+pub mod volume;
+pub mod wallpaper;
+pub mod warp;
+
+impl_reflect_value!(FieldShape(PartialEq, Serialize, Deserialize));
+impl_reflect_value!(MotionMode(PartialEq, Serialize, Deserialize));
+impl_reflect_value!(SpawnDistribution(PartialEq, Serialize, Deserialize));
+impl_reflect_value!(RngState(PartialEq, Serialize, Deserialize));
+
+/// Seconds between physics steps. Stars move in fixed steps so the field behaves the same at any frame rate.
+pub const PHYSICS_STEP: f64 = 1.0 / 60.0;
+
+const PHYSICS_TIMESTEP: &str = "star_physics";
+
+/// Runs after `Update` as many times a frame as there are physics steps due.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct PhysicsStage;
+
+/// How far past the window's edges a field fitted to it reaches, so stars are off screen before they're recycled.
+const FIT_MARGIN: f32 = 1.1;
+
+/// Everything needed to draw the moving starfield, without any of the app's menus, editors or hotkeys.
+///
+/// The field is set up from the [`StarfieldSettings`] and [`Theme`] resources, which start out with their
+/// defaults unless they've been inserted before the plugin is added.
+pub struct StarfieldPlugin {
+    /// Spawns a 2D camera looking at the field. Turn this off to look at it through a camera of your own.
+    pub camera: bool,
+    /// Draws the field only on this render layer, so only cameras on the same layer see it.
+    pub render_layer: Option<u8>,
+    /// Goes straight to the running field. Turn this off to show a menu first, like the app does.
+    pub autostart: bool,
+}
+
+impl Default for StarfieldPlugin {
+    fn default() -> Self {
+        Self {
+            camera: true,
+            render_layer: None,
+            autostart: true,
+        }
+    }
+}
+
+impl Plugin for StarfieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StarfieldSettings>()
+            .init_resource::<Field>()
+            .init_resource::<StarfieldRng>()
+            .add_plugin(StatePlugin {
+                autostart: self.autostart,
+            })
+            .add_plugin(ActionsPlugin)
+            .add_plugin(MaterialsPlugin)
+            .add_plugin(StarMeshPlugin)
+            .add_plugin(DepthPlugin)
+            .add_plugin(SpectralPlugin)
+            .add_plugin(TwinklePlugin)
+            .add_plugin(ThemePlugin)
+            .add_plugin(NebulaPlugin)
+            .add_plugin(GlowPlugin)
+            .add_plugin(AfterimagePlugin)
+            .add_plugin(TrailPlugin)
+            .add_plugin(MergePlugin)
+            .add_plugin(FadePlugin)
+            .add_plugin(SpeedPlugin)
+            .add_plugin(ParallaxPlugin)
+            .add_plugin(TunnelPlugin)
+            .add_plugin(AsteroidPlugin)
+            .add_plugin(AttractorPlugin)
+            .add_plugin(InteractionPlugin)
+            .add_plugin(GpuStarsPlugin)
+            .add_plugin(ShootingStarPlugin)
+            .add_plugin(ConvergePlugin)
+            .add_plugin(StarfieldLoadingScreenPlugin)
+            .add_plugin(DailyPlugin)
+            .add_plugin(HumPlugin)
+            .add_plugin(JumpPlugin)
+            .add_plugin(WarpPlugin)
+            .add_plugin(ShakePlugin)
+            .add_plugin(BpmPlugin)
+            .add_plugin(CullPlugin)
+            .register_type::<StarfieldSettings>()
+            .register_type::<FieldShape>()
+            .register_type::<MotionMode>()
+            .register_type::<SpawnDistribution>()
+            .register_type::<Star>()
+            .register_type::<MainCamera>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, configure_simulation)
+            .add_startup_system(setup)
+            .add_system(match_star_count)
+            .add_system(apply_field_shape)
+            .add_system(apply_motion_mode)
+            .add_system(apply_spawn_distribution)
+            .add_stage_after(
+                CoreStage::Update,
+                PhysicsStage,
+                SystemStage::parallel()
+                    .with_run_criteria(
+                        FixedTimestep::step(PHYSICS_STEP).with_label(PHYSICS_TIMESTEP),
+                    )
+                    .with_system(catch_up_stars)
+                    .with_system(reset_stars.after(catch_up_stars))
+                    .with_system(calculate_velocity.after(reset_stars))
+                    .with_system(push_stars.after(calculate_velocity))
+                    .with_system(move_stars.after(push_stars)),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate_stars.before(TransformSystem::TransformPropagate),
+            );
+
+        if self.camera {
+            app.add_startup_system(spawn_camera);
+        }
+
+        if let Some(layer) = self.render_layer {
+            app.insert_resource(StarfieldLayer(RenderLayers::layer(layer)))
+                .add_system_to_stage(CoreStage::PostUpdate, assign_render_layer);
+        }
+    }
+}
+
+/// A star in the field.
+#[derive(Clone, Reflect, Component)]
+#[reflect(Component)]
+pub struct Star {
+    velocity: Vec3,
+    base_speed: f32,
+    depth: f32,
+    /// Where in the theme's tint range this star's color sits.
+    tint: f32,
+    /// Grows as stars merge together, every star starts out at 1.
+    mass: f32,
+    /// Seconds since the star was spawned or last recycled.
+    age: f32,
+    /// Extra velocity from being pulled around by attractors, which wears off once they're left behind.
+    deflection: Vec3,
+    /// How far the star moved in the last physics step.
+    #[reflect(ignore)]
+    displacement: Vec3,
+    /// How far behind where the simulation has it the star is drawn, see [`interpolate_stars`].
+    #[reflect(ignore)]
+    lag: Vec3,
+}
+
+/// A star that's yet to be given anything random, like ones being loaded from a scene.
+impl Default for Star {
+    fn default() -> Self {
+        Self {
+            velocity: Vec3::ZERO,
+            base_speed: 0.0,
+            depth: 0.0,
+            tint: 0.0,
+            mass: 1.0,
+            age: 0.0,
+            deflection: Vec3::ZERO,
+            displacement: Vec3::ZERO,
+            lag: Vec3::ZERO,
+        }
+    }
+}
+
+impl Star {
+    fn new(field: &Field, rng: &mut impl Rng) -> Self {
+        Self {
+            base_speed: field.random_base_speed(rng),
+            depth: field.random_depth(rng),
+            tint: rng.gen_range(0.0..=1.0),
+            ..default()
+        }
+    }
+
+    /// Where the simulation has the star, which is a little ahead of where it's drawn between physics steps.
+    fn position(&self, transform: &Transform) -> Vec3 {
+        transform.translation + self.lag
+    }
+
+    /// Moves the star straight to `translation`, dropping whatever it had left to catch up on from being
+    /// drawn between physics steps. Anything that puts a star somewhere should go through this.
+    fn place(&mut self, transform: &mut Transform, translation: Vec3) {
+        transform.translation = translation;
+        self.lag = Vec3::ZERO;
+    }
+
+    /// Sends the star back into the field as if it were brand new.
+    fn respawn(&mut self, transform: &mut Transform, field: &Field, rng: &mut impl Rng) {
+        self.depth = field.random_depth(rng);
+        let translation = field.respawn_position(rng).extend(depth_to_z(self.depth));
+        self.place(transform, translation);
+        transform.scale = Vec3::ONE;
+        self.base_speed = field.random_base_speed(rng);
+        self.mass = 1.0;
+        self.age = 0.0;
+        self.deflection = Vec3::ZERO;
+        self.displacement = Vec3::ZERO;
+    }
+}
+
+/// The camera looking at the starfield.
+/// Tag your own camera with this when the plugin isn't spawning one, so effects like camera shake can find it.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct MainCamera;
+
+/// The render layer everything in the field is drawn on, when it's scoped to one.
+struct StarfieldLayer(RenderLayers);
+
+/// Hands the field's settings to the simulation before anything is spawned.
+/// The seed is logged so a field someone liked can be brought back with `--seed`.
+fn configure_simulation(
+    settings: Res<StarfieldSettings>,
+    mut field: ResMut<Field>,
+    mut rng: ResMut<StarfieldRng>,
+) {
+    info!("Generating the field from seed {}", settings.seed);
+    rng.reseed(settings.seed);
+    field.reseed(settings.seed);
+    field.set_depth_distribution(settings.depth_distribution);
+    field.set_shape(settings.field);
+    field.set_motion_mode(settings.motion);
+    field.set_spawn_distribution(settings.spawn_distribution);
+}
+
+fn spawn_camera(mut commands: Commands, layer: Option<Res<StarfieldLayer>>) {
+    let mut camera = commands.spawn_bundle(Camera2dBundle::default());
+    camera.insert(MainCamera);
+
+    if let Some(layer) = layer {
+        camera.insert(layer.0);
+    }
+}
+
+/// Sets up the starfield.
+#[allow(clippy::too_many_arguments)]
+fn setup(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    star_mesh: Res<StarMesh>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    mut material_pool: ResMut<MaterialPool>,
+    theme: Res<Theme>,
+    field: Res<Field>,
+    mut rng: ResMut<StarfieldRng>,
+) {
+    for _ in 0..entity_star_count(&settings) {
+        spawn_star(
+            &mut commands,
+            &star_mesh,
+            &mut material_pool,
+            &mut materials,
+            &theme,
+            &field,
+            &mut rng.field,
+        );
+    }
+}
+
+fn spawn_star(
+    commands: &mut Commands,
+    star_mesh: &StarMesh,
+    material_pool: &mut MaterialPool,
+    materials: &mut Assets<StarMaterial>,
+    theme: &Theme,
+    field: &Field,
+    rng: &mut impl Rng,
+) {
+    let star = Star::new(field, rng);
+
+    // Random (x, y) position, with z derived from the star's depth so near stars draw on top.
+    let position = field.spawn_position(rng).extend(depth_to_z(star.depth));
+    let transform = Transform::from_translation(position);
+
+    // Spawn the star.
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: star_mesh.0.clone().into(),
+            material: material_pool.get(theme.star_color(star.tint), materials),
+            transform,
+            ..default()
+        })
+        .insert(star);
+}
+
+/// How many stars should be entities, none when the GPU is drawing them instead.
+fn entity_star_count(settings: &StarfieldSettings) -> u32 {
+    match settings.renderer {
+        Renderer::Entities => settings.star_count,
+        Renderer::Gpu => 0,
+    }
+}
+
+/// Adds or removes stars when the star count or renderer settings change.
+/// Only changes to the setting count, so stars loaded from a scene are left alone.
+#[allow(clippy::too_many_arguments)]
+fn match_star_count(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    star_mesh: Res<StarMesh>,
+    theme: Res<Theme>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    mut material_pool: ResMut<MaterialPool>,
+    field: Res<Field>,
+    mut rng: ResMut<StarfieldRng>,
+    stars: Query<Entity, With<Star>>,
+    mut applied: Local<Option<u32>>,
+) {
+    let wanted = entity_star_count(&settings);
+    let previous = applied.replace(wanted);
+    if previous.is_none() || previous == Some(wanted) {
+        return;
+    }
+
+    let count = stars.iter().count();
+    let wanted = wanted as usize;
+    for _ in count..wanted {
+        spawn_star(
+            &mut commands,
+            &star_mesh,
+            &mut material_pool,
+            &mut materials,
+            &theme,
+            &field,
+            &mut rng.field,
+        );
+    }
+    for entity in stars.iter().skip(wanted) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Keeps the simulation's field shape in step with the settings, and with the window when it's fitted to it.
+fn apply_field_shape(
+    settings: Res<StarfieldSettings>,
+    windows: Res<Windows>,
+    mut field: ResMut<Field>,
+    mut created: EventReader<WindowCreated>,
+    mut resized: EventReader<WindowResized>,
+) {
+    let window_changed = created.iter().count() + resized.iter().count() > 0;
+    if !settings.is_changed() && !window_changed {
+        return;
+    }
+
+    let mut shape = settings.field;
+    let mut aspect_ratio = 1.0;
+    if settings.fit_to_window {
+        if let Some(window) = windows.get_primary() {
+            if window.width() > 0.0 && window.height() > 0.0 {
+                shape.extent = window.width().max(window.height()) / 2.0 * FIT_MARGIN;
+                aspect_ratio = window.width() / window.height();
+            }
+        }
+    }
+    field.set_shape(shape);
+    field.set_aspect_ratio(aspect_ratio);
+}
+
+/// Hands the motion mode setting to the simulation, which moves and recycles every star by it.
+fn apply_motion_mode(settings: Res<StarfieldSettings>, mut field: ResMut<Field>) {
+    if settings.is_changed() {
+        field.set_motion_mode(settings.motion);
+    }
+}
+
+/// Hands the spawn distribution setting to the simulation, which places every new and recycled star by it.
+fn apply_spawn_distribution(settings: Res<StarfieldSettings>, mut field: ResMut<Field>) {
+    if settings.is_changed() {
+        field.set_spawn_distribution(settings.spawn_distribution);
+    }
+}
+
+/// Puts everything drawn with a star material on the field's render layer.
+#[allow(clippy::type_complexity)]
+fn assign_render_layer(
+    mut commands: Commands,
+    layer: Res<StarfieldLayer>,
+    query: Query<Entity, (With<Handle<StarMaterial>>, Without<RenderLayers>)>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(layer.0);
+    }
+}
+
+/// Moves the stars one physics step based on their current velocity and the global speed.
+fn move_stars(speed: Res<SimulationSpeed>, mut query: Query<(&mut Star, &mut Transform)>) {
+    let speed = speed.current * speed.pulse * speed.audio;
+
+    for (mut star, mut transform) in query.iter_mut() {
+        star.displacement = star.velocity * PHYSICS_STEP as f32 * speed;
+        transform.translation += star.displacement;
+    }
+}
+
+/// How quickly stars shake off an attractor's pull once they're past it, higher is quicker.
+const DEFLECTION_DECAY: f32 = 0.5;
+
+/// Calculates velocity based on the speed of the star as well as the current acceleration.
+fn calculate_velocity(
+    settings: Res<StarfieldSettings>,
+    speed: Res<SimulationSpeed>,
+    field: Res<Field>,
+    attractors: Query<(&Attractor, &Transform), Without<Star>>,
+    mut query: Query<(&mut Star, &Transform)>,
+) {
+    let attractors: Vec<_> = attractors
+        .iter()
+        .map(|(attractor, transform)| (transform.translation.truncate(), *attractor))
+        .collect();
+    // Pulls build up over simulated time, so they stop building up while the field is stopped.
+    let step = PHYSICS_STEP as f32 * speed.current * speed.pulse * speed.audio;
+    let decay = (-DEFLECTION_DECAY * step).exp();
+
+    for (mut star, transform) in query.iter_mut() {
+        // We're dealing with 2D so we want to disregard the z dimension which is only used for draw order.
+        let xy_coords = transform.translation.truncate();
+        // Far stars drift along while near ones streak past.
+        let base_speed = star.base_speed * settings.depth_cues.speed(star.depth);
+        let velocity = field.velocity(xy_coords, base_speed, PHYSICS_STEP as f32);
+
+        let pull = attractor::pull(xy_coords, &attractors) * step;
+        star.deflection = star.deflection * decay + pull.extend(0.0);
+        star.velocity = velocity.extend(0.0) + star.deflection;
+    }
+}
+
+/// Adds the cursor's pull or push to each star's velocity, on top of how it would move anyway.
+fn push_stars(
+    pointer: Res<PointerForce>,
+    speed: Res<SimulationSpeed>,
+    mut query: Query<(&mut Star, &Transform)>,
+) {
+    if !pointer.active() {
+        return;
+    }
+    // Like an attractor's pull, it builds up into the deflection and wears off once it's let go.
+    let step = PHYSICS_STEP as f32 * speed.current * speed.pulse * speed.audio;
+
+    for (mut star, transform) in query.iter_mut() {
+        let push = (pointer.force(transform.translation.truncate()) * step).extend(0.0);
+        star.deflection += push;
+        star.velocity += push;
+    }
+}
+
+/// Puts stars back where the simulation has them before stepping it, undoing [`interpolate_stars`].
+fn catch_up_stars(mut query: Query<(&mut Star, &mut Transform)>) {
+    for (mut star, mut transform) in query.iter_mut() {
+        transform.translation += star.lag;
+        star.lag = Vec3::ZERO;
+    }
+}
+
+/// Draws stars part of the way between their last two physics steps, by however far the frame is between them.
+/// Without this stars would visibly stutter whenever the frame rate and physics rate don't line up.
+fn interpolate_stars(
+    fixed_timesteps: Res<FixedTimesteps>,
+    mut query: Query<(&mut Star, &mut Transform)>,
+) {
+    let overstep = fixed_timesteps
+        .get(PHYSICS_TIMESTEP)
+        .map_or(1.0, |timestep| timestep.overstep_percentage() as f32)
+        .clamp(0.0, 1.0);
+
+    for (mut star, mut transform) in query.iter_mut() {
+        let lag = star.displacement * (1.0 - overstep);
+        transform.translation += star.lag - lag;
+        star.lag = lag;
+    }
+}
+
+/// Reseeds the simulation and draws every star again from the new seed, as if the field had just started.
+fn reseed_field(
+    seed: u64,
+    field: &mut Field,
+    rng: &mut StarfieldRng,
+    stars: &mut Query<(&mut Star, &mut Transform)>,
+) {
+    info!("Generating the field from seed {seed}");
+    rng.reseed(seed);
+    field.reseed(seed);
+
+    for (mut star, mut transform) in stars.iter_mut() {
+        *star = Star::new(field, &mut rng.field);
+        let translation = field
+            .spawn_position(&mut rng.field)
+            .extend(depth_to_z(star.depth));
+        star.place(&mut transform, translation);
+        transform.scale = Vec3::ONE;
+    }
+}
+
+/// Takes stars outside the space extent, or swallowed by an attractor, and places them back inside.
+fn reset_stars(
+    field: Res<Field>,
+    mut rng: ResMut<StarfieldRng>,
+    attractors: Query<(&Attractor, &Transform), Without<Star>>,
+    mut query: Query<(&mut Star, &mut Transform)>,
+) {
+    let attractors: Vec<_> = attractors
+        .iter()
+        .map(|(attractor, transform)| (transform.translation.truncate(), *attractor))
+        .collect();
+
+    query
+        .iter_mut()
+        .filter(|(_, transform)| {
+            let position = transform.translation.truncate();
+            field.outside_extent(position) || attractor::swallowed(position, &attractors)
+        })
+        .for_each(|(mut star, mut transform)| star.respawn(&mut transform, &field, &mut rng.field));
+}
@@ -0,0 +1,151 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A ready-made animated loading screen, tying the field to how far along loading is.
+//!
+//! Insert a [`LoadingProgress`] and keep it updated. The field starts out slow, sparse and dim, picks up as
+//! progress climbs, and punches a final warp at 100%. Removing the resource hands the field back as it was.
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::parallax::ParallaxSystem;
+use crate::shake::CameraShake;
+use crate::speed::SimulationSpeed;
+use crate::Star;
+
+/// Speed multiplier at 0% and 100%.
+const SPEED_RANGE: (f32, f32) = (0.2, 1.0);
+
+/// Share of stars shown at 0% and 100%.
+const DENSITY_RANGE: (f32, f32) = (0.15, 1.0);
+
+/// Star brightness at 0% and 100%.
+const BRIGHTNESS_RANGE: (f32, f32) = (0.3, 1.0);
+
+/// Speed multiplier at the start of the final warp, easing back to normal over its duration.
+const FINAL_WARP_SPEED: f32 = 12.0;
+
+/// Seconds the final warp lasts.
+const FINAL_WARP_DURATION: f32 = 1.0;
+
+/// Camera shake trauma the final warp adds.
+const FINAL_WARP_TRAUMA: f32 = 0.6;
+
+pub struct StarfieldLoadingScreenPlugin;
+impl Plugin for StarfieldLoadingScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadingScreen>()
+            .add_system(advance_demo)
+            .add_system(follow_progress.after(advance_demo))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                thin_out_stars
+                    .after(TransformSystem::TransformPropagate)
+                    .before(ParallaxSystem),
+            );
+    }
+}
+
+/// How far along loading is, from 0.0 to 1.0.
+pub struct LoadingProgress(pub f32);
+
+/// Fills up the progress by itself over this many seconds, to show what the loading screen looks like.
+pub struct LoadingDemo(pub f32);
+
+#[derive(Default)]
+struct LoadingScreen {
+    /// Whether the field is currently being driven by a progress value.
+    showing: bool,
+    /// Seconds left on the final warp.
+    warp_left: f32,
+    finished: bool,
+    brightness: f32,
+}
+
+fn lerp((from, to): (f32, f32), t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn advance_demo(
+    time: Res<Time>,
+    demo: Option<Res<LoadingDemo>>,
+    progress: Option<ResMut<LoadingProgress>>,
+) {
+    if let (Some(demo), Some(mut progress)) = (demo, progress) {
+        if progress.0 < 1.0 {
+            progress.0 = (progress.0 + time.delta_seconds() / demo.0.max(0.001)).min(1.0);
+        }
+    }
+}
+
+fn follow_progress(
+    time: Res<Time>,
+    progress: Option<Res<LoadingProgress>>,
+    mut screen: ResMut<LoadingScreen>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut shake: ResMut<CameraShake>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+) {
+    let progress = match progress {
+        Some(progress) => progress.0.clamp(0.0, 1.0),
+        None => {
+            if screen.showing {
+                *screen = LoadingScreen::default();
+                speed.loading = 1.0;
+                pool.set_brightness(1.0, &mut materials);
+            }
+            return;
+        }
+    };
+    screen.showing = true;
+
+    // Only fires once per load, even if progress wobbles at the end.
+    if progress >= 1.0 && !screen.finished {
+        screen.finished = true;
+        screen.warp_left = FINAL_WARP_DURATION;
+        shake.add_trauma(FINAL_WARP_TRAUMA);
+    } else if progress < 1.0 {
+        screen.finished = false;
+    }
+
+    screen.warp_left = (screen.warp_left - time.delta_seconds()).max(0.0);
+    let warp = screen.warp_left / FINAL_WARP_DURATION;
+    speed.loading = lerp(SPEED_RANGE, progress) * lerp((1.0, FINAL_WARP_SPEED), warp);
+
+    let brightness = lerp(BRIGHTNESS_RANGE, progress);
+    if brightness != screen.brightness {
+        screen.brightness = brightness;
+        pool.set_brightness(brightness, &mut materials);
+    }
+}
+
+/// Hides part of the field, along with the glows and anything else attached to those stars.
+fn thin_out_stars(
+    progress: Option<Res<LoadingProgress>>,
+    stars: Query<(Entity, Option<&Children>), With<Star>>,
+    mut transforms: Query<&mut GlobalTransform>,
+) {
+    let density = match progress {
+        Some(progress) => lerp(DENSITY_RANGE, progress.0.clamp(0.0, 1.0)),
+        None => return,
+    };
+
+    for (entity, children) in stars.iter() {
+        // The entity index is a cheap stand in for a random number that doesn't change from frame to frame.
+        let threshold = (entity.id() % 97) as f32 / 97.0;
+        if threshold < density {
+            continue;
+        }
+
+        let hidden = std::iter::once(entity).chain(children.into_iter().flatten().copied());
+        for hidden in hidden {
+            if let Ok(mut global_transform) = transforms.get_mut(hidden) {
+                let mut transform = global_transform.compute_transform();
+                transform.scale = Vec3::ZERO;
+                *global_transform = transform.into();
+            }
+        }
+    }
+}
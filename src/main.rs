@@ -1,154 +1,300 @@
 // Copyright Quentin Wright 2022, All Rights Reserved.
 
-use std::ops::RangeInclusive;
-
+use bevy::app::ScheduleRunnerPlugin;
 use bevy::prelude::*;
-use bevy::sprite::MaterialMesh2dBundle;
-use rand::distributions::uniform::{SampleRange, SampleUniform};
-use rand::Rng;
+use bevy::render::settings::WgpuSettings;
+use bevy::window::{PresentMode, WindowMode, WindowSettings};
+use bevy::winit::WinitPlugin;
+use clap::Parser;
 
 #[cfg(debug_assertions)]
 use bevy_inspector_egui::WorldInspectorPlugin;
 
-const ACCELERATION_MULTIPLIER: f32 = 1.0;
-const SPACE_EXTENT: f32 = 1000.0;
-const MOVE_SPEED_RANGE: RangeInclusive<f32> = 10.0..=80.0;
-const NUM_STARS: u32 = 1300;
+#[cfg(feature = "audio-reactive")]
+use starfield_bevy::audio_reactive::AudioReactivePlugin;
+use starfield_bevy::bench::{BenchPlugin, DEFAULT_FRAMES, DEFAULT_STAR_COUNTS};
+use starfield_bevy::bindings::BindingsPlugin;
+use starfield_bevy::boost::BoostPlugin;
+use starfield_bevy::capture::CapturePlugin;
+use starfield_bevy::constellation::ConstellationPlugin;
+use starfield_bevy::diagnostics::StarfieldDiagnosticsPlugin;
+use starfield_bevy::dodge::DodgePlugin;
+use starfield_bevy::event_log::EventLogPlugin;
+use starfield_bevy::export::ExportPlugin;
+use starfield_bevy::flight::FlightPlugin;
+use starfield_bevy::heatmap::HeatmapPlugin;
+use starfield_bevy::history::HistoryPlugin;
+use starfield_bevy::ipc::IpcPlugin;
+use starfield_bevy::layout::LayoutPlugin;
+use starfield_bevy::loading_screen::{LoadingDemo, LoadingProgress};
+use starfield_bevy::menu::MenuPlugin;
+use starfield_bevy::palette::PalettePlugin;
+use starfield_bevy::pause_menu::PauseMenuPlugin;
+use starfield_bevy::persist::{self, Overrides, PersistPlugin};
+use starfield_bevy::post::PostPlugin;
+use starfield_bevy::randomize::RandomizePlugin;
+use starfield_bevy::record::{RecordPlugin, FRAME_RATE};
+use starfield_bevy::scene::ScenePlugin;
+use starfield_bevy::screensaver::ScreensaverPlugin;
+use starfield_bevy::settings::StarfieldSettings;
+use starfield_bevy::settings_panel::SettingsPanelPlugin;
+use starfield_bevy::share::{self, SharePlugin};
+use starfield_bevy::spectator::SpectatorPlugin;
+use starfield_bevy::star_editor::StarEditorPlugin;
+use starfield_bevy::steer::SteerPlugin;
+use starfield_bevy::sync::SyncPlugin;
+use starfield_bevy::theme::Theme;
+use starfield_bevy::theme_editor::ThemeEditorPlugin;
+use starfield_bevy::volume::VolumePlugin;
+use starfield_bevy::wallpaper::WallpaperPlugin;
+use starfield_bevy::{daily, StarfieldPlugin};
+#[cfg(not(target_arch = "wasm32"))]
+use starfield_bevy::{sim::Field, terminal};
+
+use cli::{Args, Mode};
+
+mod cli;
+
+/// The canvas on the hosting page the field is drawn into on the web.
+#[cfg(target_arch = "wasm32")]
+const CANVAS: &str = "#starfield";
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugin(DebugPlugin)
-        .insert_resource(ClearColor(Color::BLACK))
-        .add_startup_system(setup)
-        .add_system_to_stage(CoreStage::PreUpdate, reset_stars)
-        .add_system_to_stage(CoreStage::Update, calculate_velocity)
-        .add_system_to_stage(CoreStage::PostUpdate, move_stars)
-        .run();
-}
+    let args = Args::parse();
 
-pub struct DebugPlugin;
-impl Plugin for DebugPlugin {
-    #[cfg(debug_assertions)]
-    fn build(&self, app: &mut App) {
-        app.add_plugin(WorldInspectorPlugin::new())
-            .register_type::<Star>();
+    // The terminal and benchmarks are run from a console, which is where their problems should go.
+    let windowed = !args.terminal && !args.bench;
+    // Browsers report panics to the console themselves, and there's nowhere to write a crash log.
+    #[cfg(not(target_arch = "wasm32"))]
+    if windowed {
+        starfield_bevy::crash::install_hook();
     }
+    let report = |problem: &str| {
+        if windowed {
+            starfield_bevy::crash::show_problem(problem);
+        } else {
+            eprintln!("{problem}");
+        }
+    };
 
-    #[cfg(not(debug_assertions))]
-    fn build(&self, _: &mut App) {}
-}
+    // There's no config directory to keep settings in on the web.
+    let persist = !args.no_persist && !cfg!(target_arch = "wasm32");
+    let loaded = if persist { persist::load() } else { Ok(None) };
+    let saved: (StarfieldSettings, Theme) = loaded
+        .unwrap_or_else(|problem| {
+            report(&problem);
+            None
+        })
+        .unwrap_or_default();
 
-#[derive(Reflect, Component)]
-#[reflect(Component)]
-struct Star {
-    velocity: Vec3,
-    base_speed: f32,
-}
+    // A share code wins over whatever was saved last time.
+    let (mut settings, mut theme) = match args.code.as_deref().map(share::decode) {
+        Some(Ok((mut shared, theme))) => {
+            share::keep_local(&mut shared, &saved.0);
+            (shared, theme)
+        }
+        Some(Err(error)) => {
+            report(&format!("Couldn't use that share code, {error}"));
+            saved.clone()
+        }
+        None => saved.clone(),
+    };
+    if args.daily {
+        settings.daily = true;
+    }
+    if settings.daily {
+        daily::apply_today(&mut settings, &mut theme);
+    }
 
-impl Default for Star {
-    fn default() -> Self {
-        let base_speed = rand_in_range(MOVE_SPEED_RANGE);
-        let velocity = Vec3::default();
+    // Anything given on the command line wins over saved and shared settings.
+    let overrides = Overrides {
+        star_count: args.stars,
+        seed: args.seed,
+        speed: args.speed,
+    };
+    overrides.apply(&mut settings);
 
-        Self {
-            velocity,
-            base_speed,
-        }
+    #[cfg(not(target_arch = "wasm32"))]
+    if args.terminal {
+        let mut field = Field::default();
+        field.set_depth_distribution(settings.depth_distribution);
+        field.set_shape(settings.field);
+        field.set_motion_mode(settings.motion);
+        field.set_spawn_distribution(settings.spawn_distribution);
+        terminal::run(field, settings.seed);
+        return;
     }
-}
 
-#[derive(Component)]
-struct StarTrail;
-
-/// Sets up the starfield.
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    // Camera.
-    commands.spawn_bundle(Camera2dBundle::default());
-
-    for _ in 0..=NUM_STARS {
-        // Random (x, y) position.
-        let x = rand_in_range(space_extent());
-        let y = rand_in_range(space_extent());
-        let z = rand_in_range(half_space_extent());
-        let transform = Transform::from_translation(Vec3::new(x, y, z));
-
-        // Spawn the star.
-        commands
-            .spawn_bundle(MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::new(1.0).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::WHITE)),
-                transform,
+    if let Some((width, height)) = args.wallpaper {
+        settings.wallpaper.width = width;
+        settings.wallpaper.height = height;
+    }
+
+    // Wallpapers, recordings and screensavers run without anyone there to press start.
+    let autostart =
+        args.autostart || args.wallpaper.is_some() || args.record.is_some() || args.screensaver;
+
+    let mode = if args.fullscreen || args.screensaver {
+        WindowMode::BorderlessFullscreen
+    } else {
+        WindowMode::Windowed
+    };
+
+    let window = WindowDescriptor {
+        mode,
+        cursor_visible: !args.screensaver,
+        // On the web the page decides how big the field is, and fitting the field to it keeps it filled.
+        #[cfg(target_arch = "wasm32")]
+        canvas: Some(CANVAS.to_string()),
+        #[cfg(target_arch = "wasm32")]
+        fit_canvas_to_parent: true,
+        ..default()
+    };
+
+    // Benchmarks start from the default field so runs can be compared, and never wait for vsync.
+    if args.bench {
+        let star_counts = match args.stars {
+            Some(stars) => vec![stars],
+            None => DEFAULT_STAR_COUNTS.to_vec(),
+        };
+        let settings = StarfieldSettings {
+            seed: args.seed.unwrap_or_default(),
+            star_count: star_counts[0],
+            ..default()
+        };
+
+        let mut app = App::new();
+        if args.headless {
+            // Without a window or a GPU, nothing is drawn and frames follow one another as fast as they can.
+            app.insert_resource(WgpuSettings {
+                backends: None,
+                ..default()
+            })
+            .insert_resource(WindowSettings {
+                add_primary_window: false,
+                exit_on_all_closed: false,
                 ..default()
             })
-            .insert(Star::default());
+            .add_plugins_with(DefaultPlugins, |group| group.disable::<WinitPlugin>())
+            .add_plugin(ScheduleRunnerPlugin);
+        } else {
+            app.insert_resource(WindowDescriptor {
+                present_mode: PresentMode::AutoNoVsync,
+                ..window
+            })
+            .add_plugins(DefaultPlugins);
+        }
+
+        app.insert_resource(settings)
+            .add_plugin(StarfieldPlugin {
+                autostart: true,
+                ..default()
+            })
+            .add_plugin(BenchPlugin {
+                star_counts,
+                frames: args.frames.unwrap_or(DEFAULT_FRAMES),
+                json: args.json,
+            })
+            .run();
+        return;
     }
-}
 
-/// Moves the stars based on their current velocity.
-fn move_stars(time: Res<Time>, mut query: Query<(&Star, &mut Transform)>) {
-    for (star, mut transform) in query.iter_mut() {
-        transform.translation += star.velocity * time.delta_seconds();
+    // The 3D field has none of the flat field's menus, editors or effects to go with it.
+    if args.mode == Mode::Volume {
+        let mut app = App::new();
+        app.insert_resource(window)
+            .insert_resource(settings)
+            .insert_resource(theme)
+            .add_plugins(DefaultPlugins)
+            .add_plugin(VolumePlugin);
+        if persist {
+            app.add_plugin(PersistPlugin { overrides, saved });
+        }
+        app.run();
+        return;
     }
-}
 
-/// Calculates velocity based on the speed of the star as well as the current acceleration.
-fn calculate_velocity(time: Res<Time>, mut query: Query<(&mut Star, &Transform)>) {
-    for (mut star, transform) in query.iter_mut() {
-        // We're dealing with 2D so we want to disregard the z dimension which will be used for parallax.
-        let xy_coords = Vec3::new(transform.translation.x, transform.translation.y, 0.0);
+    let mut app = App::new();
+    app.insert_resource(window)
+        .insert_resource(settings)
+        .insert_resource(theme)
+        .add_plugins(DefaultPlugins)
+        .add_plugin(DebugPlugin)
+        .add_plugin(StarfieldPlugin {
+            autostart,
+            ..default()
+        })
+        .add_plugin(PauseMenuPlugin)
+        .add_plugin(BindingsPlugin)
+        .add_plugin(PalettePlugin)
+        .add_plugin(HistoryPlugin)
+        .add_plugin(ThemeEditorPlugin)
+        .add_plugin(SettingsPanelPlugin)
+        .add_plugin(StarEditorPlugin)
+        .add_plugin(PostPlugin)
+        .add_plugin(CapturePlugin)
+        .add_plugin(WallpaperPlugin {
+            export_and_exit: args.wallpaper.is_some(),
+        })
+        .add_plugin(SharePlugin)
+        .add_plugin(LayoutPlugin { path: args.layout })
+        .add_plugin(ExportPlugin)
+        .add_plugin(SpectatorPlugin)
+        .add_plugin(FlightPlugin)
+        .add_plugin(DodgePlugin)
+        .add_plugin(SteerPlugin)
+        .add_plugin(RandomizePlugin)
+        .add_plugin(IpcPlugin)
+        .add_plugin(SyncPlugin)
+        .add_plugin(BoostPlugin)
+        .add_plugin(EventLogPlugin)
+        .add_plugin(HeatmapPlugin)
+        .add_plugin(ConstellationPlugin)
+        .add_plugin(ScenePlugin { load: args.load });
 
-        // We're always moving away from the origin, so we don't have to calculate direction.
-        let movement_direction = xy_coords.normalize();
+    if !autostart {
+        app.add_plugin(MenuPlugin);
+    }
 
-        // Acceleration scaled with distance.
-        // We only multiply delta once even though a = s*(dt^2) this is because we'll multiply velocity later.
-        let acceleration = xy_coords.length() * ACCELERATION_MULTIPLIER * time.delta_seconds();
-        let velocity = movement_direction * acceleration * star.base_speed;
+    if let Some(seconds) = args.loading_demo {
+        app.insert_resource(LoadingProgress(0.0))
+            .insert_resource(LoadingDemo(seconds));
+    }
 
-        star.velocity = velocity;
+    if persist {
+        app.add_plugin(PersistPlugin { overrides, saved });
     }
-}
 
-/// Takes stars outside the space extent and places them back inside.
-fn reset_stars(mut query: Query<(&mut Star, &mut Transform)>) {
-    // Checks if a location is outside of the space extent.
-    let outside_extent = |t: Vec3| !space_extent().contains(&t.x) || !space_extent().contains(&t.y);
-
-    query
-        .iter_mut()
-        .filter(|(_, transform)| outside_extent(transform.translation))
-        .for_each(|(mut star, mut transform)| {
-            let x = rand_in_range(half_space_extent());
-            let y = rand_in_range(half_space_extent());
-            let z = rand_in_range(half_space_extent());
-
-            transform.translation = Vec3::new(x, y, z);
-            star.base_speed = rand_in_range(MOVE_SPEED_RANGE);
-        });
-}
+    if args.diagnostics {
+        app.add_plugin(StarfieldDiagnosticsPlugin);
+    }
 
-/// Generates a random value within a range.
-fn rand_in_range<T, R>(range: R) -> T
-where
-    T: SampleUniform,
-    R: SampleRange<T>,
-{
-    // This function is really just a short way of doing this.
-    rand::thread_rng().gen_range(range)
-}
+    if args.screensaver {
+        app.add_plugin(ScreensaverPlugin);
+    }
 
-/// Turns the SPACE_EXTENT constant into a range.
-fn space_extent() -> RangeInclusive<f32> {
-    -SPACE_EXTENT..=SPACE_EXTENT
+    if let Some(directory) = args.record {
+        // Ten seconds unless told otherwise.
+        let frames = args.frames.unwrap_or(FRAME_RATE * 10);
+        app.add_plugin(RecordPlugin { directory, frames });
+    }
+
+    #[cfg(feature = "audio-reactive")]
+    app.add_plugin(AudioReactivePlugin);
+
+    app.run();
 }
 
-/// Turns the SPACE_EXTENT constant into a range, half the size of the entire extent.
-fn half_space_extent() -> RangeInclusive<f32> {
-    let half_extent = SPACE_EXTENT / 2.0;
-    -half_extent..=half_extent
+pub struct DebugPlugin;
+impl Plugin for DebugPlugin {
+    #[cfg(debug_assertions)]
+    fn build(&self, app: &mut App) {
+        app.add_plugin(WorldInspectorPlugin::new()).add_system(
+            starfield_bevy::inspector::inspect_resources
+                .with_run_criteria(starfield_bevy::state::ui_shown),
+        );
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn build(&self, _: &mut App) {}
 }
@@ -0,0 +1,296 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+use bevy::asset::load_internal_asset;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::MeshVertexBufferLayout;
+use bevy::render::render_resource::{
+    AsBindGroup, BlendComponent, BlendFactor, BlendOperation, BlendState, RenderPipelineDescriptor,
+    ShaderRef, SpecializedMeshPipelineError,
+};
+use bevy::sprite::{Material2d, Material2dKey, Material2dPlugin};
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::StarfieldSettings;
+
+const STAR_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x5d1c_34a9_7e02_b6f1);
+
+/// Levels alpha is split into whatever the color buckets are. Fading, shading and twinkling all move alpha,
+/// so too few shows them stepping, while every level multiplies how many materials the pool can hold.
+const ALPHA_LEVELS: u32 = 16;
+
+pub struct MaterialsPlugin;
+impl Plugin for MaterialsPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            STAR_SHADER_HANDLE,
+            "shaders/star.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.register_type::<BlendMode>()
+            .add_plugin(Material2dPlugin::<StarMaterial>::default())
+            .add_startup_system_to_stage(StartupStage::PreStartup, create_material_pool)
+            .add_system(apply_blend_mode);
+    }
+}
+
+/// How star colors are combined with whatever has already been drawn behind them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum BlendMode {
+    /// Regular alpha blending, nearer stars occlude the ones behind them.
+    #[default]
+    Alpha,
+    /// Colors are added together, so overlapping stars brighten each other.
+    Additive,
+}
+
+impl BlendMode {
+    pub(crate) fn blend_state(self) -> BlendState {
+        match self {
+            BlendMode::Alpha => BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            },
+        }
+    }
+}
+
+/// A flat colored material used for stars and their trails.
+#[derive(AsBindGroup, Clone, TypeUuid)]
+#[uuid = "8f3b2c61-4d0e-4a7b-9c55-2e6f0a1d7b34"]
+#[bind_group_data(StarMaterialKey)]
+pub struct StarMaterial {
+    #[uniform(0)]
+    pub color: Color,
+    pub blend_mode: BlendMode,
+}
+
+impl StarMaterial {
+    pub fn new(color: Color, blend_mode: BlendMode) -> Self {
+        Self { color, blend_mode }
+    }
+}
+
+/// The parts of a [`StarMaterial`] that need a different render pipeline.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct StarMaterialKey {
+    blend_mode: BlendMode,
+}
+
+impl From<&StarMaterial> for StarMaterialKey {
+    fn from(material: &StarMaterial) -> Self {
+        Self {
+            blend_mode: material.blend_mode,
+        }
+    }
+}
+
+impl Material2d for StarMaterial {
+    fn fragment_shader() -> ShaderRef {
+        STAR_SHADER_HANDLE.typed().into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _: &MeshVertexBufferLayout,
+        key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            let blend = Some(key.bind_group_data.blend_mode.blend_state());
+            fragment
+                .targets
+                .iter_mut()
+                .flatten()
+                .for_each(|target| target.blend = blend);
+        }
+
+        Ok(())
+    }
+}
+
+/// Shares star materials between stars of similar color.
+///
+/// Colors are quantized into a fixed number of buckets per channel and every star in a bucket
+/// uses the same handle, so we end up with a handful of materials instead of one per star.
+/// Alpha always gets [`ALPHA_LEVELS`] levels, however few buckets there are for color.
+pub struct MaterialPool {
+    levels: u32,
+    blend_mode: BlendMode,
+    /// Multiplies the alpha of every pooled material, 1.0 being fully bright.
+    brightness: f32,
+    /// A color every pooled material is pulled towards, and how far.
+    shift: (Color, f32),
+    handles: HashMap<[u8; 4], Handle<StarMaterial>>,
+}
+
+impl MaterialPool {
+    pub fn new(buckets: u32, blend_mode: BlendMode) -> Self {
+        // We need at least two levels per channel (off and on) and can't go beyond what fits in a u8.
+        let levels = buckets.clamp(2, 256);
+
+        Self {
+            levels,
+            blend_mode,
+            brightness: 1.0,
+            shift: (Color::WHITE, 0.0),
+            handles: HashMap::default(),
+        }
+    }
+
+    /// Returns the shared material for a color, creating it the first time its bucket is used.
+    pub fn get(
+        &mut self,
+        color: Color,
+        materials: &mut Assets<StarMaterial>,
+    ) -> Handle<StarMaterial> {
+        let key = self.bucket(color);
+        let material = StarMaterial::new(self.shown_color(key), self.blend_mode);
+
+        self.handles
+            .entry(key)
+            .or_insert_with(|| materials.add(material))
+            .clone()
+    }
+
+    /// The highest bucket index of each channel, red, green, blue and alpha.
+    fn maxima(&self) -> [f32; 4] {
+        let max = (self.levels - 1) as f32;
+        [max, max, max, (ALPHA_LEVELS - 1) as f32]
+    }
+
+    /// Quantizes each channel of a color into its bucket index.
+    fn bucket(&self, color: Color) -> [u8; 4] {
+        let maxima = self.maxima();
+        let channels = color.as_rgba_f32();
+        [0, 1, 2, 3].map(|index| (channels[index].clamp(0.0, 1.0) * maxima[index]).round() as u8)
+    }
+
+    /// Turns a bucket index back into the representative color for that bucket.
+    fn bucket_color(&self, key: [u8; 4]) -> Color {
+        let maxima = self.maxima();
+        let [r, g, b, a] = [0, 1, 2, 3].map(|index| key[index] as f32 / maxima[index]);
+        Color::rgba(r, g, b, a)
+    }
+
+    /// The color a bucket is actually drawn with, shifted and faded by the pool's brightness.
+    fn shown_color(&self, key: [u8; 4]) -> Color {
+        let color = Vec4::from(self.bucket_color(key).as_rgba_f32());
+        let (shift, amount) = self.shift;
+        let [r, g, b, _] = color
+            .lerp(Vec4::from(shift.as_rgba_f32()), amount)
+            .to_array();
+        Color::rgba(r, g, b, color.w * self.brightness)
+    }
+
+    /// Fades every pooled material in or out, without changing which bucket stars are in.
+    pub fn set_brightness(&mut self, brightness: f32, materials: &mut Assets<StarMaterial>) {
+        self.brightness = brightness.clamp(0.0, 1.0);
+        self.refresh(materials);
+    }
+
+    /// Pulls every pooled material's color towards another, without changing which bucket stars are in.
+    pub fn set_shift(&mut self, color: Color, amount: f32, materials: &mut Assets<StarMaterial>) {
+        self.shift = (color, amount.clamp(0.0, 1.0));
+        self.refresh(materials);
+    }
+
+    fn refresh(&self, materials: &mut Assets<StarMaterial>) {
+        for (key, handle) in self.handles.iter() {
+            if let Some(material) = materials.get_mut(handle) {
+                material.color = self.shown_color(*key);
+            }
+        }
+    }
+}
+
+/// Creates the material pool before any stars are spawned.
+fn create_material_pool(mut commands: Commands, settings: Res<StarfieldSettings>) {
+    commands.insert_resource(MaterialPool::new(
+        settings.color_buckets,
+        settings.blend_mode,
+    ));
+}
+
+/// Switches every pooled material over when the blend mode setting changes.
+fn apply_blend_mode(
+    settings: Res<StarfieldSettings>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+) {
+    if !settings.is_changed() || pool.blend_mode == settings.blend_mode {
+        return;
+    }
+
+    pool.blend_mode = settings.blend_mode;
+    for handle in pool.handles.values() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.blend_mode = settings.blend_mode;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_round_trip() {
+        let pool = MaterialPool::new(8, BlendMode::Alpha);
+        for key in [[0, 0, 0, 0], [7, 3, 5, 4], [7, 7, 7, 7]] {
+            assert_eq!(pool.bucket(pool.bucket_color(key)), key);
+        }
+    }
+
+    #[test]
+    fn similar_colors_share_a_bucket() {
+        let pool = MaterialPool::new(4, BlendMode::Alpha);
+        assert_eq!(
+            pool.bucket(Color::rgb(0.5, 0.5, 0.5)),
+            pool.bucket(Color::rgb(0.55, 0.6, 0.52))
+        );
+        assert_ne!(
+            pool.bucket(Color::rgb(0.0, 0.5, 0.5)),
+            pool.bucket(Color::rgb(1.0, 0.5, 0.5))
+        );
+    }
+
+    #[test]
+    fn bucket_counts_are_clamped() {
+        assert_eq!(MaterialPool::new(0, BlendMode::Alpha).levels, 2);
+        assert_eq!(MaterialPool::new(1000, BlendMode::Alpha).levels, 256);
+    }
+
+    #[test]
+    fn alpha_keeps_its_own_levels() {
+        let pool = MaterialPool::new(2, BlendMode::Alpha);
+        let faint = pool.bucket(Color::rgba(1.0, 1.0, 1.0, 0.3));
+        let fainter = pool.bucket(Color::rgba(1.0, 1.0, 1.0, 0.2));
+        assert_eq!(faint[..3], fainter[..3]);
+        assert_ne!(faint[3], fainter[3]);
+    }
+
+    #[test]
+    fn pool_size_is_bounded() {
+        let pool = MaterialPool::new(8, BlendMode::Alpha);
+        let mut keys = std::collections::HashSet::new();
+        for step in 0..=1000 {
+            let alpha = step as f32 / 1000.0;
+            keys.insert(pool.bucket(Color::rgba(1.0, 0.8, 0.6, alpha)));
+        }
+        assert_eq!(keys.len(), ALPHA_LEVELS as usize);
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! The main menu shown over the drifting field at startup.
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext, EguiPlugin};
+
+use crate::gpu::Renderer;
+use crate::materials::BlendMode;
+use crate::settings::StarfieldSettings;
+use crate::sim::{DepthDistribution, MotionMode};
+use crate::state::AppState;
+use crate::theme::Theme;
+
+pub struct MenuPlugin;
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        // The inspector brings its own egui in debug builds.
+        if !app.world.contains_resource::<EguiContext>() {
+            app.add_plugin(EguiPlugin);
+        }
+
+        app.add_system_set(SystemSet::on_update(AppState::Menu).with_system(menu_ui));
+    }
+}
+
+fn menu_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut state: ResMut<State<AppState>>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // Edit copies so the settings only count as changed when something actually changed.
+    let mut edited_settings = settings.clone();
+    let mut edited_theme = theme.clone();
+
+    egui::Window::new("Starfield")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.vertical_centered_justified(|ui| {
+                if ui.button("Start").clicked() {
+                    let _ = state.set(AppState::Running);
+                }
+
+                preset_picker(ui, &mut edited_theme);
+
+                ui.collapsing("Settings", |ui| settings_ui(ui, &mut edited_settings));
+
+                if ui.button("Quit").clicked() {
+                    exit.send(AppExit);
+                }
+            });
+        });
+
+    if edited_settings != *settings {
+        *settings = edited_settings;
+    }
+    if edited_theme != *theme {
+        *theme = edited_theme;
+    }
+}
+
+/// A drop down of the built-in theme presets.
+pub fn preset_picker(ui: &mut egui::Ui, theme: &mut Theme) {
+    let current = Theme::PRESETS
+        .iter()
+        .find(|(_, preset)| preset == theme)
+        .map_or("Custom", |(name, _)| name);
+
+    egui::ComboBox::from_label("Theme")
+        .selected_text(current)
+        .show_ui(ui, |ui| {
+            for (name, preset) in Theme::PRESETS {
+                if ui.selectable_label(*theme == preset, name).clicked() {
+                    *theme = preset;
+                }
+            }
+        });
+}
+
+/// The settings most people will want to change, everything else is in the config file.
+pub fn settings_ui(ui: &mut egui::Ui, settings: &mut StarfieldSettings) {
+    let (min, max) = crate::speed::SPEED_LIMITS;
+    ui.add(egui::Slider::new(&mut settings.speed.multiplier, min..=max).text("Speed"));
+    let most_stars = match settings.renderer {
+        Renderer::Entities => 100_000,
+        Renderer::Gpu => 2_000_000,
+    };
+    ui.add(
+        egui::Slider::new(&mut settings.star_count, 100..=most_stars)
+            .logarithmic(true)
+            .text("Stars"),
+    );
+    ui.horizontal(|ui| {
+        ui.label("Renderer");
+        ui.radio_value(&mut settings.renderer, Renderer::Entities, "Entities");
+        ui.radio_value(&mut settings.renderer, Renderer::Gpu, "GPU");
+    });
+    ui.label(format!("Seed {}", settings.seed));
+
+    ui.horizontal(|ui| {
+        ui.label("Blending");
+        ui.radio_value(&mut settings.blend_mode, BlendMode::Alpha, "Normal");
+        ui.radio_value(&mut settings.blend_mode, BlendMode::Additive, "Additive");
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Motion");
+        let motion = &mut settings.motion;
+        ui.radio_value(motion, MotionMode::FlyThrough, "Fly through");
+        ui.radio_value(motion, MotionMode::Drift, "Drift");
+        ui.radio_value(motion, MotionMode::Orbit, "Orbit");
+        ui.radio_value(motion, MotionMode::Rain, "Rain");
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Depth");
+        let depth = &mut settings.depth_distribution;
+        ui.radio_value(depth, DepthDistribution::Uniform, "Even");
+        ui.radio_value(depth, DepthDistribution::FrontLoaded, "Near");
+        ui.radio_value(depth, DepthDistribution::Exponential, "Far");
+    });
+
+    ui.checkbox(&mut settings.spawn_distribution.enabled, "Star clusters");
+    ui.checkbox(
+        &mut settings.interaction.enabled,
+        "Push stars with the mouse",
+    );
+    ui.checkbox(&mut settings.spectral.enabled, "Spectral colors");
+    ui.checkbox(&mut settings.twinkle.enabled, "Twinkling");
+    ui.checkbox(&mut settings.crt.enabled, "CRT effect");
+    ui.checkbox(&mut settings.pixel_art.enabled, "Pixel art");
+    ui.checkbox(&mut settings.parallax.enabled, "Mouse parallax");
+    ui.checkbox(&mut settings.steer.enabled, "Steer with the mouse");
+    ui.checkbox(&mut settings.shake.enabled, "Camera shake");
+    ui.checkbox(&mut settings.merge.enabled, "Merging stars");
+    ui.checkbox(&mut settings.trail.enabled, "Warp trails");
+    if settings.trail.enabled {
+        ui.add(
+            egui::Slider::new(&mut settings.trail.max_length, 50.0..=1500.0).text("Trail length"),
+        );
+    }
+    ui.checkbox(&mut settings.tunnel.enabled, "Wormhole tunnel");
+    ui.checkbox(&mut settings.asteroids.enabled, "Asteroids");
+    ui.checkbox(&mut settings.shooting_stars.enabled, "Shooting stars");
+    ui.checkbox(&mut settings.attractors.enabled, "Black holes");
+    ui.checkbox(&mut settings.nebula.enabled, "Nebula");
+    ui.checkbox(&mut settings.constellations.enabled, "Constellations");
+    #[cfg(feature = "audio-reactive")]
+    ui.checkbox(&mut settings.audio_reactive.enabled, "React to music");
+    ui.checkbox(&mut settings.fit_to_window, "Fit field to window");
+    ui.checkbox(&mut settings.daily, "Seed of the day");
+}
@@ -0,0 +1,188 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Optional physics where stars that pass close to each other merge into one heavier, slower star.
+//! Stars that grow heavy enough eventually go supernova.
+
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::grid::SpatialGrid;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::settings::StarfieldSettings;
+use crate::shake::CameraShake;
+use crate::sim::{Field, StarfieldRng};
+use crate::theme::Theme;
+use crate::Star;
+
+/// Stars only merge if they're also about as far away, not just overlapping on screen.
+const MERGE_DEPTH_TOLERANCE: f32 = 0.05;
+
+/// Seconds a supernova takes to flare up and fade away.
+const SUPERNOVA_DURATION: f32 = 0.8;
+
+/// How much bigger a supernova gets than the star it came from.
+const SUPERNOVA_SCALE: f32 = 8.0;
+
+/// Camera shake trauma a supernova adds.
+const SUPERNOVA_TRAUMA: f32 = 0.4;
+
+pub struct MergePlugin;
+impl Plugin for MergePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MergeSettings>()
+            .register_type::<Supernova>()
+            .add_system(merge_stars)
+            .add_system(explode_supernovae);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MergeSettings {
+    pub enabled: bool,
+    /// How close two stars have to pass to merge.
+    pub radius: f32,
+    /// Mass at which a star goes supernova, every star starts out with a mass of 1.
+    pub supernova_mass: f32,
+}
+
+impl Default for MergeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 2.0,
+            supernova_mass: 12.0,
+        }
+    }
+}
+
+/// A star in the middle of exploding.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct Supernova {
+    age: f32,
+}
+
+/// Finds pairs of stars close enough to merge and folds the lighter one into the heavier one.
+#[allow(clippy::type_complexity)]
+fn merge_stars(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    mut shake: ResMut<CameraShake>,
+    field: Res<Field>,
+    mut rng: ResMut<StarfieldRng>,
+    mut query: Query<(Entity, &mut Star, &mut Transform), Without<Supernova>>,
+) {
+    let merge = settings.merge;
+    if !merge.enabled {
+        return;
+    }
+
+    let mut grid = SpatialGrid::new(merge.radius);
+    for (entity, star, transform) in query.iter() {
+        grid.insert(
+            transform.translation.truncate(),
+            (entity, star.depth, star.mass),
+        );
+    }
+
+    // Every star takes part in at most one merge per frame.
+    let mut merged = HashSet::default();
+    let mut pairs = Vec::new();
+    for (entity, star, transform) in query.iter() {
+        if merged.contains(&entity) {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let partner = grid
+            .nearby(position)
+            .find(|(other_position, (other, depth, _))| {
+                *other != entity
+                    && !merged.contains(other)
+                    && other_position.distance(position) <= merge.radius
+                    && (depth - star.depth).abs() <= MERGE_DEPTH_TOLERANCE
+            });
+
+        if let Some((_, (other, _, other_mass))) = partner {
+            merged.insert(entity);
+            merged.insert(*other);
+
+            // The heavier star survives and swallows the other one.
+            if star.mass >= *other_mass {
+                pairs.push((entity, *other));
+            } else {
+                pairs.push((*other, entity));
+            }
+        }
+    }
+
+    for (survivor, absorbed) in pairs {
+        let (mass, base_speed) = match query.get_mut(absorbed) {
+            Ok((_, mut star, mut transform)) => {
+                let taken = (star.mass, star.base_speed);
+                star.respawn(&mut transform, &field, &mut rng.effects);
+                taken
+            }
+            Err(_) => continue,
+        };
+
+        if let Ok((_, mut star, mut transform)) = query.get_mut(survivor) {
+            // A fake conservation of momentum, so merged stars end up slower than the faster of the two.
+            let total_mass = star.mass + mass;
+            star.base_speed = (star.base_speed * star.mass + base_speed * mass) / total_mass;
+            star.mass = total_mass;
+
+            // Heavier stars are bigger and so look brighter.
+            transform.scale = Vec3::splat(star.mass.sqrt());
+
+            if star.mass >= merge.supernova_mass {
+                commands.entity(survivor).insert(Supernova { age: 0.0 });
+                shake.add_trauma(SUPERNOVA_TRAUMA);
+            }
+        }
+    }
+}
+
+/// Flares supernovae up and fades them out, then sends the star off to start over.
+#[allow(clippy::too_many_arguments)]
+fn explode_supernovae(
+    mut commands: Commands,
+    time: Res<Time>,
+    theme: Res<Theme>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    field: Res<Field>,
+    mut rng: ResMut<StarfieldRng>,
+    mut query: Query<(
+        Entity,
+        &mut Supernova,
+        &mut Star,
+        &mut Transform,
+        &mut Handle<StarMaterial>,
+    )>,
+) {
+    for (entity, mut supernova, mut star, mut transform, mut material) in query.iter_mut() {
+        supernova.age += time.delta_seconds();
+        let progress = (supernova.age / SUPERNOVA_DURATION).min(1.0);
+
+        if progress >= 1.0 {
+            star.respawn(&mut transform, &field, &mut rng.effects);
+            *material = pool.get(theme.star_color(star.tint), &mut materials);
+            commands.entity(entity).remove::<Supernova>();
+            continue;
+        }
+
+        // Grows fast at first and slows down, while fading out.
+        let growth = 1.0 - (1.0 - progress).powi(3);
+        let scale = star.mass.sqrt() * (1.0 + growth * (SUPERNOVA_SCALE - 1.0));
+        transform.scale = Vec3::splat(scale);
+
+        let color = theme.star_color(star.tint);
+        let faded = pool.get(*color.clone().set_a(1.0 - progress), &mut materials);
+        if *material != faded {
+            *material = faded;
+        }
+    }
+}
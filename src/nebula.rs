@@ -0,0 +1,159 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Faint, slowly drifting clouds of gas behind the stars, so the sky isn't just an empty void.
+//!
+//! The clouds are noise drawn by a shader on one quad covering the field, just behind the farthest stars.
+
+use bevy::asset::load_internal_asset;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::render_resource::{AsBindGroup, ShaderRef, ShaderType};
+use bevy::sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::StarfieldSettings;
+use crate::sim::Field;
+use crate::speed::SimulationSpeed;
+use crate::theme::Theme;
+use crate::StarfieldLayer;
+
+const NEBULA_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x8c4f_1e72_d93a_5b06);
+
+/// Behind even the farthest stars, but still inside the 2D camera's visible range.
+const NEBULA_Z: f32 = 0.0;
+
+/// How far past the field the quad reaches, so camera shake never shows its edges.
+const NEBULA_MARGIN: f32 = 1.2;
+
+pub struct NebulaPlugin;
+impl Plugin for NebulaPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            NEBULA_SHADER_HANDLE,
+            "shaders/nebula.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.register_type::<NebulaSettings>()
+            .add_plugin(Material2dPlugin::<NebulaMaterial>::default())
+            .add_startup_system(spawn_nebula)
+            .add_system(update_nebula);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NebulaSettings {
+    pub enabled: bool,
+    /// Color of the densest parts of the clouds.
+    pub inner: Color,
+    /// Color of the thin edges of the clouds.
+    pub outer: Color,
+    /// How strongly the clouds show, 0.0 to 1.0.
+    pub intensity: f32,
+    /// Roughly how big a cloud is, in pixels.
+    pub scale: f32,
+    /// How fast the clouds drift across the sky, in pixels per second at normal speed.
+    pub drift: Vec2,
+}
+
+impl Default for NebulaSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            inner: Color::rgb(0.85, 0.35, 0.6),
+            outer: Color::rgb(0.15, 0.2, 0.55),
+            intensity: 0.35,
+            scale: 700.0,
+            drift: Vec2::new(6.0, 2.0),
+        }
+    }
+}
+
+#[derive(AsBindGroup, Clone, TypeUuid)]
+#[uuid = "3e9d57a1-c086-4b2f-a7d4-16f0e8b95c21"]
+struct NebulaMaterial {
+    #[uniform(0)]
+    uniform: NebulaUniform,
+}
+
+#[derive(Clone, Default, ShaderType)]
+struct NebulaUniform {
+    inner: Color,
+    outer: Color,
+    /// How far the clouds have drifted, in pixels.
+    offset: Vec2,
+    scale: f32,
+    intensity: f32,
+}
+
+impl Material2d for NebulaMaterial {
+    fn fragment_shader() -> ShaderRef {
+        NEBULA_SHADER_HANDLE.typed().into()
+    }
+}
+
+#[derive(Component)]
+struct Nebula {
+    material: Handle<NebulaMaterial>,
+}
+
+fn spawn_nebula(
+    mut commands: Commands,
+    layer: Option<Res<StarfieldLayer>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<NebulaMaterial>>,
+) {
+    let material = materials.add(NebulaMaterial {
+        uniform: NebulaUniform::default(),
+    });
+
+    let mut nebula = commands.spawn_bundle(MaterialMesh2dBundle {
+        mesh: meshes.add(shape::Quad::new(Vec2::ONE).into()).into(),
+        material: material.clone(),
+        transform: Transform::from_xyz(0.0, 0.0, NEBULA_Z),
+        visibility: Visibility { is_visible: false },
+        ..default()
+    });
+    nebula.insert(Nebula { material });
+
+    if let Some(layer) = layer {
+        nebula.insert(layer.0);
+    }
+}
+
+/// Drifts the clouds along, and keeps the quad covering the field and the shader in step with the settings.
+fn update_nebula(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+    speed: Res<SimulationSpeed>,
+    field: Res<Field>,
+    mut materials: ResMut<Assets<NebulaMaterial>>,
+    mut query: Query<(&Nebula, &mut Transform, &mut Visibility)>,
+) {
+    let nebula_settings = settings.nebula;
+
+    for (nebula, mut transform, mut visibility) in query.iter_mut() {
+        if visibility.is_visible != nebula_settings.enabled {
+            visibility.is_visible = nebula_settings.enabled;
+        }
+        if !nebula_settings.enabled {
+            continue;
+        }
+
+        let size = field.extents() * 2.0 * NEBULA_MARGIN;
+        transform.scale = size.extend(1.0);
+
+        if let Some(material) = materials.get_mut(&nebula.material) {
+            let uniform = &mut material.uniform;
+            uniform.inner = theme.tint_nebula(nebula_settings.inner);
+            uniform.outer = theme.tint_nebula(nebula_settings.outer);
+            uniform.offset += nebula_settings.drift * time.delta_seconds() * speed.current;
+            uniform.scale = nebula_settings.scale.max(1.0);
+            uniform.intensity = nebula_settings.intensity.clamp(0.0, 1.0);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A Ctrl+P command palette listing every action with fuzzy search, so nobody has to memorize key bindings.
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext, EguiPlugin};
+
+use crate::actions::Action;
+use crate::bindings::Bindings;
+use crate::state;
+
+pub struct PalettePlugin;
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        // The inspector brings its own egui in debug builds.
+        if !app.world.contains_resource::<EguiContext>() {
+            app.add_plugin(EguiPlugin);
+        }
+
+        app.init_resource::<CommandPalette>()
+            .add_system_to_stage(CoreStage::PreUpdate, toggle_palette)
+            .add_system(palette_ui.with_run_criteria(state::ui_shown));
+    }
+}
+
+#[derive(Default)]
+pub struct CommandPalette {
+    pub open: bool,
+    query: String,
+    selected: usize,
+}
+
+/// Opens the palette with Ctrl+P and closes it again with Ctrl+P or Escape.
+fn toggle_palette(keys: Res<Input<KeyCode>>, mut palette: ResMut<CommandPalette>) {
+    let control = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let toggle = control && keys.just_pressed(KeyCode::P);
+    let close = palette.open && keys.just_pressed(KeyCode::Escape);
+
+    if toggle || close {
+        *palette = CommandPalette {
+            open: !palette.open,
+            ..default()
+        };
+    }
+}
+
+fn palette_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut palette: ResMut<CommandPalette>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    mut actions: EventWriter<Action>,
+) {
+    if !palette.open {
+        return;
+    }
+
+    let matches = search(&palette.query);
+    let last = matches.len().saturating_sub(1);
+    if keys.just_pressed(KeyCode::Down) {
+        palette.selected = (palette.selected + 1).min(last);
+    }
+    if keys.just_pressed(KeyCode::Up) {
+        palette.selected = palette.selected.saturating_sub(1);
+    }
+    palette.selected = palette.selected.min(last);
+
+    let mut chosen = None;
+    if keys.just_pressed(KeyCode::Return) {
+        chosen = matches.get(palette.selected).copied();
+    }
+
+    egui::Window::new("Command palette")
+        .title_bar(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 80.0])
+        .show(egui_context.ctx_mut(), |ui| {
+            let search_box = ui.text_edit_singleline(&mut palette.query);
+            search_box.request_focus();
+            if search_box.changed() {
+                palette.selected = 0;
+            }
+
+            for (index, action) in matches.iter().enumerate() {
+                let label = match bindings.key(*action) {
+                    Some(key) => format!("{}  ({key:?})", action.name()),
+                    None => action.name().to_string(),
+                };
+
+                if ui
+                    .selectable_label(index == palette.selected, label)
+                    .clicked()
+                {
+                    chosen = Some(*action);
+                }
+            }
+
+            if matches.is_empty() {
+                ui.label("No matching commands");
+            }
+        });
+
+    if let Some(action) = chosen {
+        actions.send(action);
+        *palette = CommandPalette::default();
+    }
+}
+
+/// Every action matching the query, best matches first.
+fn search(query: &str) -> Vec<Action> {
+    let mut scored: Vec<_> = Action::ALL
+        .into_iter()
+        .filter_map(|action| fuzzy_score(query, action.name()).map(|score| (score, action)))
+        .collect();
+
+    // Stable, so equally good matches keep their usual order.
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, action)| action).collect()
+}
+
+/// Scores how well a query matches some text, or `None` if the query's letters don't all appear in order.
+/// Letters that follow each other or start a word count for more.
+fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match = None;
+
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|c| *c == wanted)?;
+
+        score += 1;
+        if found > 0 && previous_match == Some(found - 1) {
+            score += 3;
+        }
+        if found == 0 || text[found - 1] == ' ' {
+            score += 2;
+        }
+
+        previous_match = Some(found);
+        position = found + 1;
+    }
+
+    Some(score)
+}
@@ -0,0 +1,136 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Shifts near stars more than far ones as the mouse moves, like looking through a window into space.
+//! Only what's drawn moves, the stars keep flying in the same direction.
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::depth::z_to_depth;
+use crate::materials::StarMaterial;
+use crate::settings::StarfieldSettings;
+
+/// How quickly the view catches up with the mouse, higher is snappier.
+const PARALLAX_FOLLOW: f32 = 4.0;
+
+pub struct ParallaxPlugin;
+impl Plugin for ParallaxPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ParallaxSettings>()
+            .init_resource::<Parallax>()
+            .add_system(follow_mouse)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                refresh_global_transforms.before(TransformSystem::TransformPropagate),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_parallax
+                    .label(ParallaxSystem)
+                    .after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Runs after transforms are propagated, anything else touching global transforms should run after it.
+#[derive(SystemLabel, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ParallaxSystem;
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParallaxSettings {
+    pub enabled: bool,
+    /// How many pixels the nearest stars shift with the mouse at the edge of the window.
+    pub strength: f32,
+}
+
+impl Default for ParallaxSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 40.0,
+        }
+    }
+}
+
+/// Where the mouse is relative to the middle of the window, -1.0 to 1.0 on each axis, smoothed over time.
+#[derive(Default)]
+struct Parallax {
+    offset: Vec2,
+    /// The offset stars were shifted by last frame, which has to be undone even once there's none.
+    applied: Vec2,
+}
+
+fn follow_mouse(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    windows: Res<Windows>,
+    mut parallax: ResMut<Parallax>,
+) {
+    // Turning parallax off puts the stars straight back where they belong.
+    if !settings.parallax.enabled {
+        parallax.offset = Vec2::ZERO;
+        return;
+    }
+
+    let target = windows
+        .get_primary()
+        .and_then(|window| {
+            let size = Vec2::new(window.width(), window.height());
+            let cursor = window.cursor_position()?;
+            Some((cursor / size * 2.0 - 1.0).clamp(Vec2::NEG_ONE, Vec2::ONE))
+        })
+        .unwrap_or(Vec2::ZERO);
+
+    let follow = (PARALLAX_FOLLOW * time.delta_seconds()).min(1.0);
+    parallax.offset = parallax.offset.lerp(target, follow);
+}
+
+/// Makes sure everything drawn with a star material gets its global transform worked out afresh.
+///
+/// Propagation skips transforms that haven't changed, so stars that stay put, like pinned, imported and
+/// constellation stars, would otherwise keep the view's shift from last frame and have it added again.
+/// Without a shift this frame or last there's nothing to undo, so change detection is left alone.
+fn refresh_global_transforms(
+    settings: Res<StarfieldSettings>,
+    parallax: Res<Parallax>,
+    mut query: Query<&mut Transform, With<Handle<StarMaterial>>>,
+) {
+    let shifting = settings.parallax.enabled && parallax.offset != Vec2::ZERO;
+    if !shifting && parallax.applied == Vec2::ZERO {
+        return;
+    }
+
+    for mut transform in query.iter_mut() {
+        transform.set_changed();
+    }
+}
+
+/// Shifts everything drawn with a star material by an amount depending on how near it is.
+fn apply_parallax(
+    settings: Res<StarfieldSettings>,
+    mut parallax: ResMut<Parallax>,
+    mut query: Query<&mut GlobalTransform, With<Handle<StarMaterial>>>,
+) {
+    let offset = if settings.parallax.enabled {
+        parallax.offset
+    } else {
+        Vec2::ZERO
+    };
+    if parallax.applied != offset {
+        parallax.applied = offset;
+    }
+    if offset == Vec2::ZERO {
+        return;
+    }
+
+    let shift = offset * settings.parallax.strength;
+
+    for mut global_transform in query.iter_mut() {
+        let mut transform = global_transform.compute_transform();
+        let nearness = 1.0 - z_to_depth(transform.translation.z);
+        transform.translation += (shift * nearness).extend(0.0);
+        *global_transform = transform.into();
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! The menu shown over the field while it's paused, usable with a mouse, keyboard or gamepad.
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext, EguiPlugin};
+
+use crate::actions::Action;
+use crate::menu::settings_ui;
+use crate::palette::CommandPalette;
+use crate::settings::StarfieldSettings;
+use crate::state::AppState;
+use crate::theme::Theme;
+
+pub struct PauseMenuPlugin;
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        // The inspector brings its own egui in debug builds.
+        if !app.world.contains_resource::<EguiContext>() {
+            app.add_plugin(EguiPlugin);
+        }
+
+        app.init_resource::<PauseMenu>()
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(reset_pause_menu))
+            .add_system_set(
+                SystemSet::on_update(AppState::Paused)
+                    .with_system(navigate_pause_menu)
+                    .with_system(pause_menu_ui.after(navigate_pause_menu)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Resume,
+    Settings,
+    Theme,
+    Screenshot,
+    Quit,
+}
+
+impl Item {
+    const ALL: [Item; 5] = [
+        Item::Resume,
+        Item::Settings,
+        Item::Theme,
+        Item::Screenshot,
+        Item::Quit,
+    ];
+}
+
+#[derive(Default)]
+struct PauseMenu {
+    selected: usize,
+    settings_open: bool,
+    /// Set by the keyboard or a gamepad, and handled the same way as a click.
+    activated: bool,
+    /// Which way to step through theme presets, -1, 0 or 1.
+    step: i32,
+}
+
+fn reset_pause_menu(mut menu: ResMut<PauseMenu>) {
+    *menu = PauseMenu::default();
+}
+
+/// Moves the selection with the arrow keys or a d-pad, and picks items with Enter or the south button.
+fn navigate_pause_menu(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    palette: Res<CommandPalette>,
+    mut menu: ResMut<PauseMenu>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if palette.open {
+        return;
+    }
+
+    let pressed = |key: KeyCode, button: GamepadButtonType| {
+        keys.just_pressed(key)
+            || gamepads
+                .iter()
+                .any(|gamepad| buttons.just_pressed(GamepadButton::new(*gamepad, button)))
+    };
+
+    let last = Item::ALL.len() - 1;
+    if pressed(KeyCode::Up, GamepadButtonType::DPadUp) {
+        menu.selected = menu.selected.saturating_sub(1);
+    }
+    if pressed(KeyCode::Down, GamepadButtonType::DPadDown) {
+        menu.selected = (menu.selected + 1).min(last);
+    }
+
+    menu.step = 0;
+    if pressed(KeyCode::Left, GamepadButtonType::DPadLeft) {
+        menu.step = -1;
+    }
+    if pressed(KeyCode::Right, GamepadButtonType::DPadRight) {
+        menu.step = 1;
+    }
+
+    menu.activated = pressed(KeyCode::Return, GamepadButtonType::South);
+
+    // Start is bound to pausing, so it resumes through that instead.
+    if pressed(KeyCode::Escape, GamepadButtonType::East) {
+        let _ = state.set(AppState::Running);
+    }
+}
+
+fn pause_menu_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut menu: ResMut<PauseMenu>,
+    mut state: ResMut<State<AppState>>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+    mut actions: EventWriter<Action>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // Edit copies so the settings only count as changed when something actually changed.
+    let mut edited_settings = settings.clone();
+    let mut edited_theme = theme.clone();
+    let mut chosen = None;
+
+    egui::Window::new("Paused")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.vertical_centered_justified(|ui| {
+                for (index, item) in Item::ALL.into_iter().enumerate() {
+                    let label = match item {
+                        Item::Resume => "Resume".to_string(),
+                        Item::Settings => "Settings".to_string(),
+                        Item::Theme => format!("◀ {} ▶", preset_name(&edited_theme)),
+                        Item::Screenshot => "Screenshot".to_string(),
+                        Item::Quit => "Quit".to_string(),
+                    };
+
+                    let response = ui.selectable_label(menu.selected == index, label);
+                    if response.hovered() {
+                        menu.selected = index;
+                    }
+                    if response.clicked() || (menu.activated && menu.selected == index) {
+                        chosen = Some(item);
+                    }
+
+                    if item == Item::Settings && menu.settings_open {
+                        settings_ui(ui, &mut edited_settings);
+                    }
+                }
+            });
+        });
+
+    // Left and right flip through presets while the theme is selected.
+    if Item::ALL[menu.selected] == Item::Theme {
+        step_preset(&mut edited_theme, menu.step);
+    }
+
+    match chosen {
+        Some(Item::Resume) => {
+            let _ = state.set(AppState::Running);
+        }
+        Some(Item::Settings) => menu.settings_open = !menu.settings_open,
+        Some(Item::Theme) => step_preset(&mut edited_theme, 1),
+        Some(Item::Screenshot) => actions.send(Action::Screenshot),
+        Some(Item::Quit) => exit.send(AppExit),
+        None => {}
+    }
+
+    if edited_settings != *settings {
+        *settings = edited_settings;
+    }
+    if edited_theme != *theme {
+        *theme = edited_theme;
+    }
+}
+
+fn preset_name(theme: &Theme) -> &'static str {
+    Theme::PRESETS
+        .iter()
+        .find(|(_, preset)| preset == theme)
+        .map_or("Custom theme", |(name, _)| name)
+}
+
+/// Moves to the next or previous built-in preset, starting from the first if the theme isn't one.
+fn step_preset(theme: &mut Theme, step: i32) {
+    if step == 0 {
+        return;
+    }
+
+    let count = Theme::PRESETS.len() as i32;
+    let next = match Theme::PRESETS
+        .iter()
+        .position(|(_, preset)| preset == theme)
+    {
+        Some(current) => (current as i32 + step).rem_euclid(count),
+        None => 0,
+    };
+
+    *theme = Theme::PRESETS[next as usize].1.clone();
+}
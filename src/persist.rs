@@ -0,0 +1,444 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Remembers settings between runs by saving them to the user's config directory on exit.
+//!
+//! The saved file doubles as a config file, edits made to it while the field is running are picked up
+//! as soon as they're saved. Anything given on the command line still wins over the file after an edit,
+//! and is never saved: only what's changed while the field is running goes back into the file.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Mutex;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::settings::StarfieldSettings;
+use crate::speed::SPEED_LIMITS;
+use crate::theme::Theme;
+
+const FILE_NAME: &str = "settings.ron";
+
+#[derive(Default)]
+pub struct PersistPlugin {
+    /// Settings from the command line, put back over the file every time it's reloaded.
+    pub overrides: Overrides,
+    /// The settings and theme as they were saved, before anything from the command line was put over them.
+    pub saved: (StarfieldSettings, Theme),
+}
+
+impl Plugin for PersistPlugin {
+    fn build(&self, app: &mut App) {
+        let (settings, theme) = self.saved.clone();
+        app.insert_resource(self.overrides)
+            .insert_resource(Baseline {
+                saved: Persisted { settings, theme },
+                launched: None,
+            })
+            .add_startup_system(save_if_missing)
+            .add_startup_system(watch_settings_file.after(save_if_missing))
+            .add_system_to_stage(CoreStage::First, remember_launch)
+            .add_system(reload_on_change)
+            .add_system_to_stage(CoreStage::Last, save_on_exit);
+    }
+}
+
+/// Settings given on the command line, which win over saved and shared ones.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Overrides {
+    pub star_count: Option<u32>,
+    pub seed: Option<u64>,
+    pub speed: Option<f32>,
+}
+
+impl Overrides {
+    pub fn apply(&self, settings: &mut StarfieldSettings) {
+        if let Some(stars) = self.star_count {
+            settings.star_count = stars;
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = seed;
+        }
+        if let Some(speed) = self.speed {
+            let (min, max) = SPEED_LIMITS;
+            settings.speed.multiplier = speed.clamp(min, max);
+        }
+    }
+}
+
+/// Hears about changes to the settings file from the operating system.
+struct SettingsWatcher {
+    /// Changes are only reported for as long as this is kept around.
+    _watcher: RecommendedWatcher,
+    events: Mutex<Receiver<notify::Result<Event>>>,
+    path: PathBuf,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Persisted {
+    settings: StarfieldSettings,
+    theme: Theme,
+}
+
+/// What's in the settings file, and what the field started out with once everything else was put over it.
+struct Baseline {
+    saved: Persisted,
+    launched: Option<Persisted>,
+}
+
+impl Baseline {
+    /// The saved settings with whatever's been changed since launch put over them.
+    fn with_changes(&self, settings: &StarfieldSettings, theme: &Theme) -> Persisted {
+        let launched = self.launched.as_ref().unwrap_or(&self.saved);
+        Persisted {
+            settings: keep_changes(&self.saved.settings, &launched.settings, settings),
+            theme: keep_changes(&self.saved.theme, &launched.theme, theme),
+        }
+    }
+}
+
+/// Takes each value from `current` where it's different from `launched`, and from `saved` where it isn't.
+fn keep_changes<T: Clone + Serialize + DeserializeOwned>(
+    saved: &T,
+    launched: &T,
+    current: &T,
+) -> T {
+    let values = [saved, launched, current].map(serde_json::to_value);
+    match values {
+        [Ok(saved_value), Ok(launched), Ok(current)] => {
+            serde_json::from_value(merge_changes(saved_value, &launched, current))
+                .unwrap_or_else(|_| saved.clone())
+        }
+        _ => saved.clone(),
+    }
+}
+
+fn merge_changes(saved: Value, launched: &Value, current: Value) -> Value {
+    match (saved, launched, current) {
+        // Only fields both sides have are merged one by one, a different enum variant is a change of its own.
+        (Value::Object(mut saved), Value::Object(launched), Value::Object(current))
+            if saved.keys().eq(current.keys()) && launched.keys().eq(current.keys()) =>
+        {
+            for (key, current) in current {
+                if let (Some(saved_value), Some(launched)) =
+                    (saved.remove(&key), launched.get(&key))
+                {
+                    saved.insert(key, merge_changes(saved_value, launched, current));
+                }
+            }
+            Value::Object(saved)
+        }
+        (saved, launched, current) if current == *launched => saved,
+        (_, _, current) => current,
+    }
+}
+
+/// Loads the settings saved last time, if there are any, or says why the saved ones can't be used.
+pub fn load() -> Result<Option<(StarfieldSettings, Theme)>, String> {
+    let path = match config_path(FILE_NAME) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => return Ok(None),
+    };
+
+    match ron::from_str::<Persisted>(&contents) {
+        Ok(mut persisted) => {
+            // Everything but the layout of the stars is remembered, a new run should still be a new field.
+            persisted.settings.seed = rand::random();
+            Ok(Some((persisted.settings, persisted.theme)))
+        }
+        Err(error) => Err(format!(
+            "Ignoring saved settings in {}, {error}",
+            path.display()
+        )),
+    }
+}
+
+/// Notes what the field starts out with, so changes made later can be told apart from the command line.
+/// This waits for the first frame, since a scene given on the command line is loaded at startup too.
+fn remember_launch(
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+    mut baseline: ResMut<Baseline>,
+) {
+    if baseline.launched.is_some() {
+        return;
+    }
+    baseline.launched = Some(Persisted {
+        settings: settings.clone(),
+        theme: theme.clone(),
+    });
+}
+
+/// Writes out the saved settings when there's no file yet, so there's something to edit.
+fn save_if_missing(baseline: Res<Baseline>) {
+    if matches!(config_path(FILE_NAME), Some(path) if !path.exists()) {
+        save(&baseline.saved);
+    }
+}
+
+/// Starts listening for edits to the settings file.
+fn watch_settings_file(mut commands: Commands) {
+    let path = match config_path(FILE_NAME) {
+        Some(path) => path,
+        None => return,
+    };
+
+    // Plenty of editors save by replacing the file, so it's the folder it's in that gets watched.
+    let (sender, events) = mpsc::channel();
+    let watched = notify::recommended_watcher(sender).and_then(|mut watcher| {
+        let directory = path.parent().unwrap_or(&path);
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map(|_| watcher)
+    });
+
+    match watched {
+        Ok(watcher) => commands.insert_resource(SettingsWatcher {
+            _watcher: watcher,
+            events: Mutex::new(events),
+            path,
+        }),
+        Err(error) => warn!(
+            "Couldn't watch {} for changes, edits will be picked up next run, {error}",
+            path.display()
+        ),
+    }
+}
+
+/// Applies the settings file again whenever it's edited while the field is running.
+fn reload_on_change(
+    watcher: Option<Res<SettingsWatcher>>,
+    overrides: Res<Overrides>,
+    mut baseline: ResMut<Baseline>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+) {
+    let watcher = match watcher {
+        Some(watcher) => watcher,
+        None => return,
+    };
+
+    let edited = watcher
+        .events
+        .lock()
+        .map(|events| {
+            events
+                .try_iter()
+                .filter_map(Result::ok)
+                .filter(|event| event.kind.is_create() || event.kind.is_modify())
+                // Only the settings file's own folder is watched, so its name is enough to pick it out.
+                .any(|event| event.paths.iter().any(|path| path.ends_with(FILE_NAME)))
+        })
+        .unwrap_or(false);
+    if !edited {
+        return;
+    }
+
+    let contents = match fs::read_to_string(&watcher.path) {
+        Ok(contents) => contents,
+        Err(_) => return,
+    };
+
+    match ron::from_str::<Persisted>(&contents) {
+        Ok(persisted) => {
+            // Keep the current field instead of reshuffling it on every edit.
+            let mut reloaded = StarfieldSettings {
+                seed: settings.seed,
+                ..persisted.settings.clone()
+            };
+            overrides.apply(&mut reloaded);
+            // The file is what's saved now, and only changes made after this should be saved over it.
+            baseline.launched = Some(Persisted {
+                settings: reloaded.clone(),
+                theme: persisted.theme.clone(),
+            });
+            baseline.saved = persisted.clone();
+
+            let settings_changed = reloaded != *settings;
+            let theme_changed = persisted.theme != *theme;
+            if settings_changed {
+                *settings = reloaded;
+            }
+            if theme_changed {
+                *theme = persisted.theme;
+            }
+            // Saving the file ourselves is reported as an edit too, but leaves nothing to change.
+            if settings_changed || theme_changed {
+                info!("Reloaded settings from {}", watcher.path.display());
+            }
+        }
+        Err(error) => warn!(
+            "Ignoring edited settings in {}, {error}",
+            watcher.path.display()
+        ),
+    }
+}
+
+fn save_on_exit(
+    mut exit: EventReader<AppExit>,
+    baseline: Res<Baseline>,
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+) {
+    if exit.iter().count() == 0 {
+        return;
+    }
+
+    save(&baseline.with_changes(&settings, &theme));
+}
+
+fn save(persisted: &Persisted) {
+    let path = match config_path(FILE_NAME) {
+        Some(path) => path,
+        None => {
+            warn!("Couldn't find a config directory to save settings to");
+            return;
+        }
+    };
+
+    let saved = ron::ser::to_string_pretty(persisted, ron::ser::PrettyConfig::default())
+        .map_err(|error| error.to_string())
+        .and_then(|contents| {
+            if let Some(directory) = path.parent() {
+                fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+            }
+            fs::write(&path, contents).map_err(|error| error.to_string())
+        });
+
+    match saved {
+        Ok(()) => info!("Saved settings to {}", path.display()),
+        Err(error) => warn!("Couldn't save settings to {}, {error}", path.display()),
+    }
+}
+
+/// Where a config file lives, following each platform's convention for config files.
+pub fn config_path(file_name: &str) -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+
+    Some(base?.join("starfield").join(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_win_only_where_given() {
+        let saved = StarfieldSettings {
+            seed: 5,
+            star_count: 900,
+            ..default()
+        };
+
+        let mut settings = saved.clone();
+        Overrides::default().apply(&mut settings);
+        assert!(settings == saved);
+
+        Overrides {
+            star_count: Some(10),
+            ..default()
+        }
+        .apply(&mut settings);
+        assert_eq!(settings.star_count, 10);
+        assert_eq!(settings.seed, 5);
+        assert_eq!(settings.speed.multiplier, saved.speed.multiplier);
+    }
+
+    #[test]
+    fn overridden_speed_is_kept_in_limits() {
+        let (min, max) = SPEED_LIMITS;
+        let mut settings = StarfieldSettings::default();
+        for (speed, expected) in [(max * 10.0, max), (min - 1.0, min)] {
+            Overrides {
+                speed: Some(speed),
+                ..default()
+            }
+            .apply(&mut settings);
+            assert_eq!(settings.speed.multiplier, expected);
+        }
+    }
+
+    #[test]
+    fn command_line_values_are_not_saved() {
+        let saved = Persisted {
+            settings: StarfieldSettings {
+                seed: 1,
+                star_count: 900,
+                ..default()
+            },
+            theme: Theme::default(),
+        };
+        let overrides = Overrides {
+            star_count: Some(10),
+            seed: Some(2),
+            speed: Some(3.0),
+        };
+        let mut launched = saved.clone();
+        overrides.apply(&mut launched.settings);
+        let baseline = Baseline {
+            saved: saved.clone(),
+            launched: Some(launched.clone()),
+        };
+
+        let mut settings = launched.settings.clone();
+        settings.speed.transition += 1.0;
+        let mut theme = launched.theme.clone();
+        theme.glow = 0.75;
+        let persisted = baseline.with_changes(&settings, &theme);
+
+        assert_eq!(persisted.settings.star_count, 900);
+        assert_eq!(persisted.settings.seed, 1);
+        assert_eq!(
+            persisted.settings.speed.multiplier,
+            saved.settings.speed.multiplier
+        );
+        assert_eq!(
+            persisted.settings.speed.transition,
+            settings.speed.transition
+        );
+        assert_eq!(persisted.theme.glow, 0.75);
+    }
+
+    #[test]
+    fn changes_after_launch_are_saved_even_over_overrides() {
+        let saved = Persisted {
+            settings: StarfieldSettings::default(),
+            theme: Theme::default(),
+        };
+        let mut launched = saved.clone();
+        Overrides {
+            star_count: Some(10),
+            ..default()
+        }
+        .apply(&mut launched.settings);
+        let baseline = Baseline {
+            saved,
+            launched: Some(launched.clone()),
+        };
+
+        let settings = StarfieldSettings {
+            star_count: 2000,
+            ..launched.settings
+        };
+        let persisted = baseline.with_changes(&settings, &launched.theme);
+        assert_eq!(persisted.settings.star_count, 2000);
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+use bevy::asset::load_internal_asset;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::camera::RenderTarget;
+use bevy::render::render_resource::{
+    AsBindGroup, Extent3d, ShaderRef, ShaderType, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages,
+};
+use bevy::render::texture::{BevyDefault, ImageSampler};
+use bevy::render::view::RenderLayers;
+use bevy::sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::transform::TransformSystem;
+use bevy::window::WindowResized;
+use serde::{Deserialize, Serialize};
+
+use crate::parallax::ParallaxSystem;
+use crate::settings::StarfieldSettings;
+use crate::{MainCamera, Star};
+
+const CRT_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x2a7e_91c0_46d3_f85b);
+
+/// Render layer only the post processing camera can see, so the stars never end up in it.
+const POST_LAYER: u8 = RenderLayers::TOTAL_LAYERS as u8 - 1;
+
+pub struct PostPlugin;
+impl Plugin for PostPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            CRT_SHADER_HANDLE,
+            "shaders/crt.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.register_type::<CrtSettings>()
+            .register_type::<PixelArtSettings>()
+            .add_plugin(Material2dPlugin::<PostMaterial>::default())
+            .add_startup_system(setup_post)
+            .add_system_to_stage(CoreStage::PostUpdate, apply_post_settings)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                snap_to_pixels
+                    .after(TransformSystem::TransformPropagate)
+                    .after(ParallaxSystem),
+            );
+    }
+}
+
+/// Tuning for the CRT post effect.
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrtSettings {
+    pub enabled: bool,
+    /// How dark the gaps between scanlines get, 0.0 to 1.0.
+    pub scanlines: f32,
+    /// How strongly the image is bent around the edges of the tube.
+    pub curvature: f32,
+    /// How much light bleeds into neighboring pixels.
+    pub bloom: f32,
+    /// How far apart the red and blue channels are drawn, in pixels.
+    pub chroma: f32,
+}
+
+impl Default for CrtSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scanlines: 0.35,
+            curvature: 0.08,
+            bloom: 0.4,
+            chroma: 1.5,
+        }
+    }
+}
+
+/// Settings for rendering at a tiny internal resolution and scaling it up with hard pixel edges.
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PixelArtSettings {
+    pub enabled: bool,
+    /// Vertical resolution of the internal image, the width follows the window's aspect ratio.
+    pub height: u32,
+}
+
+impl Default for PixelArtSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            height: 180,
+        }
+    }
+}
+
+/// Draws the offscreen image the stars were rendered into onto the window, applying the CRT effect on the way.
+#[derive(AsBindGroup, Clone, TypeUuid)]
+#[uuid = "c4a8e1f7-3b26-4d9c-8e05-71f2b9d6a043"]
+struct PostMaterial {
+    #[uniform(0)]
+    uniform: PostUniform,
+    #[texture(1)]
+    #[sampler(2)]
+    source: Handle<Image>,
+}
+
+#[derive(Clone, Default, ShaderType)]
+struct PostUniform {
+    resolution: Vec2,
+    scanlines: f32,
+    curvature: f32,
+    bloom: f32,
+    chroma: f32,
+}
+
+impl Material2d for PostMaterial {
+    fn fragment_shader() -> ShaderRef {
+        CRT_SHADER_HANDLE.typed().into()
+    }
+}
+
+/// Everything needed to reroute the main camera through the post pass.
+struct PostTarget {
+    image: Handle<Image>,
+    material: Handle<PostMaterial>,
+    quad: Entity,
+    camera: Entity,
+}
+
+/// Creates the offscreen image, the quad showing it, and the camera looking at the quad.
+/// They all stay idle until a post effect is turned on.
+fn setup_post(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<PostMaterial>>,
+) {
+    let image = images.add(offscreen_image(1, 1));
+    let material = materials.add(PostMaterial {
+        uniform: PostUniform::default(),
+        source: image.clone(),
+    });
+
+    let quad = commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(shape::Quad::new(Vec2::ONE).into()).into(),
+            material: material.clone(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(RenderLayers::layer(POST_LAYER))
+        .id();
+
+    // Drawn after the main camera so the image is ready by the time we show it.
+    let camera = commands
+        .spawn_bundle(Camera2dBundle {
+            camera: Camera {
+                priority: 1,
+                is_active: false,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(RenderLayers::layer(POST_LAYER))
+        .id();
+
+    commands.insert_resource(PostTarget {
+        image,
+        material,
+        quad,
+        camera,
+    });
+}
+
+/// Turns the post pass on or off, and keeps the offscreen image sized to the window or the pixel art resolution.
+#[allow(clippy::too_many_arguments)]
+fn apply_post_settings(
+    settings: Res<StarfieldSettings>,
+    target: Res<PostTarget>,
+    windows: Res<Windows>,
+    mut resized: EventReader<WindowResized>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<PostMaterial>>,
+    mut main_camera: Query<(&mut Camera, &mut OrthographicProjection), With<MainCamera>>,
+    mut post_camera: Query<&mut Camera, Without<MainCamera>>,
+    mut quad: Query<(&mut Visibility, &Mesh2dHandle)>,
+    mut msaa: ResMut<Msaa>,
+) {
+    let window_resized = resized.iter().count() > 0;
+    if !settings.is_changed() && !window_resized {
+        return;
+    }
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let crt = settings.crt;
+    let pixel_art = settings.pixel_art;
+    let enabled = crt.enabled || pixel_art.enabled;
+
+    // In pixel art mode the image only has a handful of pixels, otherwise it matches the window.
+    let (width, height) = if pixel_art.enabled {
+        let height = pixel_art.height.max(1);
+        let width = (height as f32 * window.width() / window.height()).round() as u32;
+        (width.max(1), height)
+    } else {
+        (
+            window.physical_width().max(1),
+            window.physical_height().max(1),
+        )
+    };
+
+    if let Ok((mut camera, mut projection)) = main_camera.get_single_mut() {
+        if enabled {
+            camera.target = RenderTarget::Image(target.image.clone());
+            // Scale so we still see the same part of the world however many pixels the image has.
+            projection.scale = window.height() / height as f32;
+        } else {
+            camera.target = RenderTarget::default();
+            projection.scale = 1.0;
+        }
+    }
+
+    if let Ok(mut camera) = post_camera.get_mut(target.camera) {
+        camera.is_active = enabled;
+    }
+
+    if let Ok((mut visibility, mesh)) = quad.get_mut(target.quad) {
+        visibility.is_visible = enabled;
+        if let Some(mesh) = meshes.get_mut(&mesh.0) {
+            *mesh = shape::Quad::new(Vec2::new(window.width(), window.height())).into();
+        }
+    }
+
+    // Antialiasing would smear stars across neighboring pixels.
+    msaa.samples = if pixel_art.enabled { 1 } else { 4 };
+
+    if !enabled {
+        return;
+    }
+
+    if let Some(image) = images.get_mut(&target.image) {
+        if image.size() != Vec2::new(width as f32, height as f32) {
+            image.resize(Extent3d {
+                width,
+                height,
+                ..default()
+            });
+        }
+
+        image.sampler_descriptor = if pixel_art.enabled {
+            ImageSampler::nearest()
+        } else {
+            ImageSampler::linear()
+        };
+    }
+
+    // Touching the material also makes it rebind the image after a resize.
+    if let Some(material) = materials.get_mut(&target.material) {
+        // With the CRT effect off the shader just passes the image through.
+        material.uniform = if crt.enabled {
+            PostUniform {
+                resolution: Vec2::new(width as f32, height as f32),
+                scanlines: crt.scanlines,
+                curvature: crt.curvature,
+                bloom: crt.bloom,
+                chroma: crt.chroma,
+            }
+        } else {
+            PostUniform {
+                resolution: Vec2::new(width as f32, height as f32),
+                ..default()
+            }
+        };
+    }
+}
+
+/// Snaps stars to the middle of the pixel they're in and makes sure they cover at least one pixel.
+/// Only the global transform is touched so the simulation keeps its sub-pixel positions.
+fn snap_to_pixels(
+    settings: Res<StarfieldSettings>,
+    camera: Query<&OrthographicProjection, With<MainCamera>>,
+    mut query: Query<&mut GlobalTransform, With<Star>>,
+) {
+    if !settings.pixel_art.enabled {
+        return;
+    }
+
+    let pixel_size = match camera.get_single() {
+        Ok(projection) => projection.scale,
+        Err(_) => return,
+    };
+
+    for mut global_transform in query.iter_mut() {
+        let mut transform = global_transform.compute_transform();
+        let snapped = (transform.translation.truncate() / pixel_size).floor() + 0.5;
+        transform.translation = (snapped * pixel_size).extend(transform.translation.z);
+
+        // Stars are a unit circle, so their diameter is twice their scale.
+        let min_scale = pixel_size / 2.0;
+        transform.scale = transform.scale.max(Vec3::splat(min_scale));
+
+        *global_transform = transform.into();
+    }
+}
+
+/// An image the main camera can render into and the post material can sample from.
+fn offscreen_image(width: u32, height: u32) -> Image {
+    let size = Extent3d {
+        width,
+        height,
+        ..default()
+    };
+
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("starfield_offscreen_image"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::bevy_default(),
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..default()
+    };
+
+    // Fills the image with zeroes.
+    image.resize(size);
+    image
+}
@@ -0,0 +1,124 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Rerolls the seed, palette, speed and effects all at once, for quickly exploring what the field can look like.
+//!
+//! Every roll stays within bounds that still look like a starfield, and shows its share code so a good one
+//! can be kept.
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+use rand::Rng;
+
+use crate::actions::Action;
+use crate::materials::BlendMode;
+use crate::settings::StarfieldSettings;
+use crate::share;
+use crate::sim::{DepthDistribution, Field, StarfieldRng};
+use crate::speed::Easing;
+use crate::star_mesh::StarShape;
+use crate::state;
+use crate::theme::Theme;
+use crate::{reseed_field, Star};
+
+/// Slowest and fastest a roll sets the speed multiplier to.
+const SPEED_RANGE: std::ops::Range<f32> = 0.4..2.5;
+
+/// Seconds the share code stays on screen after a roll.
+const CODE_DURATION: f32 = 8.0;
+
+pub struct RandomizePlugin;
+impl Plugin for RandomizePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RolledCode>()
+            .add_system(randomize_everything)
+            .add_system(rolled_code_ui.with_run_criteria(state::ui_shown));
+    }
+}
+
+/// The share code of the last roll, while it's still being shown.
+#[derive(Default)]
+struct RolledCode {
+    code: String,
+    remaining: f32,
+}
+
+fn randomize_everything(
+    mut actions: EventReader<Action>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+    mut field: ResMut<Field>,
+    mut field_rng: ResMut<StarfieldRng>,
+    mut stars: Query<(&mut Star, &mut Transform)>,
+    mut rolled: ResMut<RolledCode>,
+) {
+    if !actions
+        .iter()
+        .any(|action| *action == Action::RandomizeEverything)
+    {
+        return;
+    }
+
+    // Rolls come from the thread's generator rather than the field's, so they don't follow the current seed.
+    let mut rng = rand::thread_rng();
+
+    settings.seed = rng.gen();
+    settings.daily = false;
+    *theme = Theme::random(&mut rng);
+    settings.speed.multiplier = rng.gen_range(SPEED_RANGE);
+    settings.speed.easing = [Easing::Cubic, Easing::Expo, Easing::Spring][rng.gen_range(0..3)];
+    settings.blend_mode = if rng.gen_bool(0.5) {
+        BlendMode::Alpha
+    } else {
+        BlendMode::Additive
+    };
+    settings.depth_distribution = [
+        DepthDistribution::Uniform,
+        DepthDistribution::FrontLoaded,
+        DepthDistribution::Exponential,
+    ][rng.gen_range(0..3)];
+    settings.star_mesh.shape = if rng.gen_bool(0.8) {
+        StarShape::Circle
+    } else {
+        StarShape::Quad
+    };
+
+    // Heavier effects come up less often, so most rolls are still recognisably the same starfield.
+    // Modes like the tunnel are left alone, a roll only changes how the field looks.
+    settings.crt.enabled = rng.gen_bool(0.2);
+    settings.pixel_art.enabled = rng.gen_bool(0.15);
+    settings.parallax.enabled = rng.gen_bool(0.5);
+    settings.merge.enabled = rng.gen_bool(0.3);
+    settings.asteroids.enabled = rng.gen_bool(0.5);
+
+    reseed_field(settings.seed, &mut field, &mut field_rng, &mut stars);
+
+    let code = share::encode(&settings, &theme);
+    println!("{code}");
+    *rolled = RolledCode {
+        code,
+        remaining: CODE_DURATION,
+    };
+}
+
+/// Shows the last roll's share code for a few seconds, with a button to copy it.
+fn rolled_code_ui(
+    time: Res<Time>,
+    mut egui_context: ResMut<EguiContext>,
+    mut rolled: ResMut<RolledCode>,
+) {
+    if rolled.remaining <= 0.0 {
+        return;
+    }
+    rolled.remaining -= time.delta_seconds();
+
+    egui::Area::new("rolled_code")
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -24.0])
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Share code {}", rolled.code));
+                if ui.button("Copy").clicked() {
+                    ui.output().copied_text = rolled.code.clone();
+                }
+            });
+        });
+}
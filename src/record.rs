@@ -0,0 +1,116 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Records the field as numbered PNG frames for assembling into a video, then quits.
+//!
+//! Time steps forward by exactly one frame per update, however long frames really take to render and save,
+//! so the recording plays back smoothly. Assemble it with something like
+//! `ffmpeg -framerate 60 -i frame-%05d.png -pix_fmt yuv420p starfield.mp4`.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::time::TimeSystem;
+// Bevy's clock, which on the web comes from the browser.
+use bevy::utils::Instant;
+
+use crate::capture::{CaptureRequest, CaptureSaved};
+
+/// Frames per second of the recording.
+pub const FRAME_RATE: u32 = 60;
+
+pub struct RecordPlugin {
+    /// Where the frames are saved, created if it doesn't exist yet.
+    pub directory: PathBuf,
+    /// How many frames to record before quitting.
+    pub frames: u32,
+}
+
+impl Plugin for RecordPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Recording {
+            directory: self.directory.clone(),
+            frames: self.frames,
+            requested: 0,
+            saved: 0,
+        })
+        .add_startup_system(create_directory)
+        .add_system_to_stage(CoreStage::First, fixed_time_step.after(TimeSystem))
+        .add_system(record_frame)
+        .add_system(exit_when_recorded);
+    }
+}
+
+struct Recording {
+    directory: PathBuf,
+    frames: u32,
+    requested: u32,
+    saved: u32,
+}
+
+fn create_directory(recording: Res<Recording>, mut exit: EventWriter<AppExit>) {
+    if let Err(error) = fs::create_dir_all(&recording.directory) {
+        error!(
+            "Couldn't create {} to record into, {error}",
+            recording.directory.display()
+        );
+        exit.send(AppExit);
+    }
+}
+
+/// Replaces the real time that passed with exactly one frame's worth.
+fn fixed_time_step(mut time: ResMut<Time>, mut fixed: Local<Option<(Time, Instant)>>) {
+    let (fixed_time, now) = fixed.get_or_insert_with(|| {
+        let now = time.last_update().unwrap_or_else(|| time.startup());
+        (time.clone(), now)
+    });
+    *now += Duration::from_secs_f64(1.0 / FRAME_RATE as f64);
+    fixed_time.update_with_instant(*now);
+    *time = fixed_time.clone();
+}
+
+/// Asks for a capture of every frame until there are enough.
+fn record_frame(
+    windows: Res<Windows>,
+    mut recording: ResMut<Recording>,
+    mut requests: EventWriter<CaptureRequest>,
+) {
+    if recording.requested >= recording.frames {
+        return;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    recording.requested += 1;
+    let name = format!("frame-{:05}.png", recording.requested);
+    requests.send(CaptureRequest {
+        size: UVec2::new(window.physical_width(), window.physical_height()),
+        path: recording.directory.join(name),
+        bloom: 0.0,
+    });
+}
+
+fn exit_when_recorded(
+    mut saved: EventReader<CaptureSaved>,
+    mut recording: ResMut<Recording>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let finished = saved.iter().count() as u32;
+    if finished == 0 {
+        return;
+    }
+
+    recording.saved += finished;
+    if recording.saved >= recording.frames {
+        info!(
+            "Recorded {} frames to {}",
+            recording.saved,
+            recording.directory.display()
+        );
+        exit.send(AppExit);
+    }
+}
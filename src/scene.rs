@@ -0,0 +1,269 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Saves the whole field, stars and settings, as a Bevy scene and loads it back when one is dropped on the window
+//! or passed with `--load`.
+//!
+//! Along with where the simulation has every star and how it's moving, a scene keeps where the random
+//! generator was up to and how fast the field was going, warp included, so a loaded field carries on exactly
+//! as the saved one would have.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::reflect::TypeRegistryArc;
+use bevy::sprite::Mesh2dHandle;
+
+use crate::actions::Action;
+use crate::clock;
+use crate::layout::ImportedStar;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::settings::StarfieldSettings;
+use crate::sim::{Field, RngState, StarfieldRng};
+use crate::speed::SimulationSpeed;
+use crate::star_editor::PinnedStar;
+use crate::star_mesh::StarMesh;
+use crate::theme::Theme;
+use crate::warp::WarpDrive;
+use crate::Star;
+
+const SCENE_EXTENSION: &str = ".scn.ron";
+
+#[derive(Default)]
+pub struct ScenePlugin {
+    /// A scene to replace the generated field with from the start.
+    pub load: Option<PathBuf>,
+}
+
+impl Plugin for ScenePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SceneSettings>()
+            .register_type::<RngState>()
+            .insert_resource(LoadOnStart(self.load.clone()))
+            .init_resource::<SaveRequested>()
+            .add_startup_system_to_stage(StartupStage::PostStartup, load_scene_on_start)
+            .add_system(request_save)
+            .add_system(save_scene.exclusive_system().at_end())
+            .add_system(load_dropped_scenes)
+            .add_system(apply_scene_settings)
+            .add_system(finish_loaded_stars);
+    }
+}
+
+/// Carries the settings and theme inside a scene, since scenes only hold entities.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+struct SceneSettings {
+    settings: StarfieldSettings,
+    theme: Theme,
+    /// Where the random generator was up to. Scenes saved before it was kept have the default, and start
+    /// over from the seed instead.
+    rng: RngState,
+    /// Scenes saved before the speed was kept ease into the settings' speed instead.
+    speed: SimulationSpeed,
+    warp: WarpDrive,
+}
+
+struct LoadOnStart(Option<PathBuf>);
+
+/// Set when a save was asked for, the save itself needs the whole world so it happens in an exclusive system.
+#[derive(Default)]
+struct SaveRequested(bool);
+
+fn request_save(mut actions: EventReader<Action>, mut requested: ResMut<SaveRequested>) {
+    for action in actions.iter() {
+        if *action == Action::SaveScene {
+            requested.0 = true;
+        }
+    }
+}
+
+/// Copies everything worth keeping into a fresh world and writes it out as a scene.
+fn save_scene(world: &mut World) {
+    if !std::mem::take(&mut world.resource_mut::<SaveRequested>().0) {
+        return;
+    }
+
+    let mut scene_world = World::new();
+
+    // Stars are drawn a little behind the simulation between physics steps, it's the simulated position
+    // that has to be kept for the field to carry on from where it was.
+    let mut stars = world.query::<(&Star, &Transform)>();
+    for (star, transform) in stars.iter(world) {
+        let mut simulated = *transform;
+        let mut star = star.clone();
+        star.place(&mut simulated, star.position(transform));
+        scene_world.spawn().insert(star).insert(simulated);
+    }
+
+    let mut pinned = world.query::<(&PinnedStar, &Transform)>();
+    for (star, transform) in pinned.iter(world) {
+        scene_world.spawn().insert(star.clone()).insert(*transform);
+    }
+
+    let mut imported = world.query::<(&ImportedStar, &Transform)>();
+    for (star, transform) in imported.iter(world) {
+        scene_world.spawn().insert(star.clone()).insert(*transform);
+    }
+
+    scene_world.spawn().insert(SceneSettings {
+        settings: world.resource::<StarfieldSettings>().clone(),
+        theme: world.resource::<Theme>().clone(),
+        rng: world.resource::<StarfieldRng>().state(),
+        speed: world.resource::<SimulationSpeed>().clone(),
+        warp: world.resource::<WarpDrive>().clone(),
+    });
+
+    let registry = world.resource::<TypeRegistryArc>();
+    let scene = DynamicScene::from_world(&scene_world, registry);
+
+    let timestamp = clock::unix_seconds();
+    let path = format!("starfield-{timestamp}{SCENE_EXTENSION}");
+
+    let saved = scene
+        .serialize_ron(registry)
+        .map_err(|error| error.to_string())
+        .and_then(|contents| fs::write(&path, contents).map_err(|error| error.to_string()));
+
+    match saved {
+        Ok(()) => info!("Saved the field to {path}"),
+        Err(error) => warn!("Couldn't save the field to {path}, {error}"),
+    }
+}
+
+type FieldStars<'w, 's> =
+    Query<'w, 's, Entity, Or<(With<Star>, With<PinnedStar>, With<ImportedStar>)>>;
+
+/// Replaces the generated field with the scene given on the command line, once it's been generated.
+fn load_scene_on_start(
+    mut commands: Commands,
+    load: Res<LoadOnStart>,
+    asset_server: Res<AssetServer>,
+    mut scene_spawner: ResMut<SceneSpawner>,
+    current: FieldStars,
+) {
+    let path = match &load.0 {
+        Some(path) => path,
+        None => return,
+    };
+
+    // The asset server looks in the assets folder for relative paths, but this one is relative to where we were run.
+    match fs::canonicalize(path) {
+        Ok(path) => load_scene(
+            &path,
+            &mut commands,
+            &asset_server,
+            &mut scene_spawner,
+            &current,
+        ),
+        Err(error) => warn!("Couldn't load the field from {}, {error}", path.display()),
+    }
+}
+
+/// Replaces the field with any scene dropped onto the window.
+fn load_dropped_scenes(
+    mut commands: Commands,
+    mut dropped: EventReader<FileDragAndDrop>,
+    asset_server: Res<AssetServer>,
+    mut scene_spawner: ResMut<SceneSpawner>,
+    current: FieldStars,
+) {
+    for event in dropped.iter() {
+        let path = match event {
+            FileDragAndDrop::DroppedFile { path_buf, .. } => path_buf,
+            _ => continue,
+        };
+
+        if !path.to_string_lossy().ends_with(SCENE_EXTENSION) {
+            continue;
+        }
+
+        load_scene(
+            path,
+            &mut commands,
+            &asset_server,
+            &mut scene_spawner,
+            &current,
+        );
+    }
+}
+
+fn load_scene(
+    path: &Path,
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    scene_spawner: &mut SceneSpawner,
+    current: &FieldStars,
+) {
+    for entity in current.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    scene_spawner.spawn_dynamic(asset_server.load(path));
+    info!("Loading the field from {}", path.display());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_scene_settings(
+    mut commands: Commands,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+    mut field: ResMut<Field>,
+    mut rng: ResMut<StarfieldRng>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut warp: ResMut<WarpDrive>,
+    loaded: Query<(Entity, &SceneSettings), Added<SceneSettings>>,
+) {
+    for (entity, scene_settings) in loaded.iter() {
+        *settings = scene_settings.settings.clone();
+        *theme = scene_settings.theme.clone();
+
+        field.reseed(settings.seed);
+        rng.reseed(settings.seed);
+        if scene_settings.rng != RngState::default() {
+            rng.restore(scene_settings.rng);
+        }
+        *speed = scene_settings.speed.clone();
+        *warp = scene_settings.warp.clone();
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Scenes only carry the star data, this gives loaded stars everything they need to be drawn.
+#[allow(clippy::type_complexity)]
+fn finish_loaded_stars(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    star_mesh: Res<StarMesh>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    loaded: Query<
+        (
+            Entity,
+            Option<&Star>,
+            Option<&PinnedStar>,
+            Option<&ImportedStar>,
+        ),
+        (
+            Or<(With<Star>, With<PinnedStar>, With<ImportedStar>)>,
+            Without<Mesh2dHandle>,
+        ),
+    >,
+) {
+    for (entity, star, pinned, imported) in loaded.iter() {
+        let color = match (star, pinned, imported) {
+            (Some(star), _, _) => theme.star_color(star.tint),
+            (_, Some(pinned), _) => pinned.color,
+            (_, _, Some(imported)) => imported.color,
+            _ => continue,
+        };
+
+        commands
+            .entity(entity)
+            .insert(Mesh2dHandle(star_mesh.0.clone()))
+            .insert(pool.get(color, &mut materials))
+            .insert(GlobalTransform::default())
+            .insert(Visibility::default())
+            .insert(ComputedVisibility::default());
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Runs the field as a screensaver, fullscreen on every monitor until someone touches the keyboard or mouse.
+//!
+//! The primary window covers the primary monitor, and every other monitor gets a window and camera of its
+//! own showing the same field.
+
+use bevy::app::AppExit;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::mouse::{MouseButtonInput, MouseMotion, MouseWheel};
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::window::{
+    CreateWindow, MonitorSelection, WindowCreated, WindowId, WindowMode, WindowPosition,
+};
+use bevy::winit::WinitWindows;
+
+/// Seconds after starting that input is ignored, so the key press or click that started it doesn't end it.
+const GRACE_PERIOD: f64 = 1.0;
+
+/// How far the mouse has to move, in pixels, before it counts. Keeps a bumped desk from ending it.
+const MOVE_THRESHOLD: f32 = 20.0;
+
+pub struct ScreensaverPlugin;
+impl Plugin for ScreensaverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(open_other_monitors)
+            .add_system(fullscreen_new_windows)
+            .add_system(exit_on_input);
+    }
+}
+
+/// Opens a window on every monitor but the primary one, once the primary window is up.
+fn open_other_monitors(
+    mut commands: Commands,
+    winit_windows: NonSend<WinitWindows>,
+    mut create: EventWriter<CreateWindow>,
+    mut opened: Local<bool>,
+) {
+    if *opened {
+        return;
+    }
+    let primary = match winit_windows.get_window(WindowId::primary()) {
+        Some(window) => window,
+        None => return,
+    };
+    *opened = true;
+
+    let primary_monitor = primary.primary_monitor();
+    for (number, monitor) in primary.available_monitors().enumerate() {
+        if Some(&monitor) == primary_monitor.as_ref() {
+            continue;
+        }
+
+        // Fullscreen windows are always created on the primary monitor, so it starts out windowed on its
+        // own monitor and goes fullscreen once it's there.
+        let id = WindowId::new();
+        create.send(CreateWindow {
+            id,
+            descriptor: WindowDescriptor {
+                title: "Starfield".to_string(),
+                position: WindowPosition::Centered(MonitorSelection::Number(number)),
+                decorations: false,
+                cursor_visible: false,
+                ..default()
+            },
+        });
+
+        commands.spawn_bundle(Camera2dBundle {
+            camera: Camera {
+                target: RenderTarget::Window(id),
+                ..default()
+            },
+            ..default()
+        });
+    }
+}
+
+fn fullscreen_new_windows(mut created: EventReader<WindowCreated>, mut windows: ResMut<Windows>) {
+    for event in created.iter() {
+        if event.id == WindowId::primary() {
+            continue;
+        }
+        if let Some(window) = windows.get_mut(event.id) {
+            window.set_mode(WindowMode::BorderlessFullscreen);
+        }
+    }
+}
+
+/// Quits on any key, click, scroll, or mouse movement.
+fn exit_on_input(
+    time: Res<Time>,
+    mut keys: EventReader<KeyboardInput>,
+    mut buttons: EventReader<MouseButtonInput>,
+    mut wheel: EventReader<MouseWheel>,
+    mut motion: EventReader<MouseMotion>,
+    mut moved: Local<f32>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let pressed = keys.iter().count() + buttons.iter().count() + wheel.iter().count() > 0;
+    let distance: f32 = motion.iter().map(|motion| motion.delta.length()).sum();
+
+    if time.seconds_since_startup() < GRACE_PERIOD {
+        return;
+    }
+
+    *moved += distance;
+    if pressed || *moved > MOVE_THRESHOLD {
+        exit.send(AppExit);
+    }
+}
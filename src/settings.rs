@@ -0,0 +1,173 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+use bevy::reflect::Reflect;
+use serde::{Deserialize, Serialize};
+
+use crate::asteroid::AsteroidSettings;
+use crate::attractor::AttractorSettings;
+#[cfg(feature = "audio-reactive")]
+use crate::audio_reactive::AudioReactiveSettings;
+use crate::bpm::BpmSettings;
+use crate::constellation::ConstellationSettings;
+use crate::depth::DepthCues;
+use crate::dodge::DodgeSettings;
+use crate::event_log::EventLogSettings;
+use crate::fade::FadeSettings;
+use crate::gpu::Renderer;
+use crate::interaction::InteractionSettings;
+use crate::ipc::IpcSettings;
+use crate::jump::JumpSettings;
+use crate::materials::BlendMode;
+use crate::merge::MergeSettings;
+use crate::nebula::NebulaSettings;
+use crate::parallax::ParallaxSettings;
+use crate::post::{CrtSettings, PixelArtSettings};
+use crate::shake::ShakeSettings;
+use crate::shooting_star::ShootingStarSettings;
+use crate::sim::{DepthDistribution, FieldShape, MotionMode};
+use crate::spawn_distribution::SpawnDistribution;
+use crate::spectral::SpectralSettings;
+use crate::speed::SpeedSettings;
+use crate::star_mesh::StarMeshSettings;
+use crate::steer::SteerSettings;
+use crate::sync::SyncSettings;
+use crate::trail::TrailSettings;
+use crate::tunnel::TunnelSettings;
+use crate::twinkle::TwinkleSettings;
+use crate::wallpaper::WallpaperSettings;
+use crate::warp::WarpSettings;
+
+/// Runtime tunables for the starfield.
+#[derive(Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StarfieldSettings {
+    /// Seed for the random generator, the same seed always produces the same field.
+    pub seed: u64,
+    /// Takes the seed and palette from today's date, so everyone sees the same field each day.
+    pub daily: bool,
+    /// How many stars are in the field.
+    pub star_count: u32,
+    /// Whether stars are entities or live entirely on the GPU, which handles far more of them.
+    pub renderer: Renderer,
+    /// The size of the field and how stars move through it.
+    pub field: FieldShape,
+    /// Sizes the field to the window instead of the field's extent, so it always fills the view.
+    pub fit_to_window: bool,
+    /// How stars move through the field.
+    pub motion: MotionMode,
+    /// How star depths are spread between the nearest and farthest planes.
+    pub depth_distribution: DepthDistribution,
+    /// Clusters, voids and a galactic band in where stars are placed.
+    pub spawn_distribution: SpawnDistribution,
+    /// How much slower, smaller and dimmer far stars are than near ones.
+    pub depth_cues: DepthCues,
+    /// Coloring stars by spectral class, and how common each class is.
+    pub spectral: SpectralSettings,
+    /// Stars flickering as if seen through an atmosphere.
+    pub twinkle: TwinkleSettings,
+    /// Number of quantization levels per color channel used when pooling star materials, alpha has its own.
+    pub color_buckets: u32,
+    /// How overlapping stars and trails are blended together.
+    pub blend_mode: BlendMode,
+    /// The optional CRT post effect.
+    pub crt: CrtSettings,
+    /// Renders at a tiny internal resolution for a retro game look.
+    pub pixel_art: PixelArtSettings,
+    /// Size and look of exported wallpapers.
+    pub wallpaper: WallpaperSettings,
+    /// Stars merging when they pass close to each other.
+    pub merge: MergeSettings,
+    /// How stars ease in when they spawn.
+    pub fade: FadeSettings,
+    /// Warp streaks behind moving stars.
+    pub trail: TrailSettings,
+    /// How fast the field moves and how it eases between speeds.
+    pub speed: SpeedSettings,
+    /// Shifting near stars with the mouse for a sense of depth.
+    pub parallax: ParallaxSettings,
+    /// Camera shake from boosts, supernovae, and the like.
+    pub shake: ShakeSettings,
+    /// Pulsing in time with a tempo.
+    pub bpm: BpmSettings,
+    /// Moving with the music the microphone hears.
+    #[cfg(feature = "audio-reactive")]
+    pub audio_reactive: AudioReactiveSettings,
+    /// Logging notable events to a file.
+    pub event_log: EventLogSettings,
+    /// The shape and detail of the mesh stars are drawn with.
+    pub star_mesh: StarMeshSettings,
+    /// Automatic hyperspace jumps and how loud they are.
+    pub jump: JumpSettings,
+    /// How the warp drive ramps up and what it looks like.
+    pub warp: WarpSettings,
+    /// Taking actions from other programs over a local port.
+    pub ipc: IpcSettings,
+    /// The wormhole tunnel view.
+    pub tunnel: TunnelSettings,
+    /// Asteroids tumbling past now and then.
+    pub asteroids: AsteroidSettings,
+    /// Shooting stars streaking across now and then.
+    pub shooting_stars: ShootingStarSettings,
+    /// Massive objects drifting through now and then, bending the paths of stars.
+    pub attractors: AttractorSettings,
+    /// Clouds of gas drifting behind the stars.
+    pub nebula: NebulaSettings,
+    /// Named constellations pinned over the field.
+    pub constellations: ConstellationSettings,
+    /// The dodge mini-game, which is off until started.
+    pub dodge: DodgeSettings,
+    /// Steering where the field flies towards.
+    pub steer: SteerSettings,
+    /// Pulling and pushing stars around with the mouse.
+    pub interaction: InteractionSettings,
+    /// Showing one field across several machines.
+    pub sync: SyncSettings,
+}
+
+impl Default for StarfieldSettings {
+    fn default() -> Self {
+        Self {
+            seed: rand::random(),
+            daily: false,
+            star_count: 1300,
+            renderer: Renderer::default(),
+            field: FieldShape::default(),
+            fit_to_window: true,
+            motion: MotionMode::default(),
+            depth_distribution: DepthDistribution::default(),
+            spawn_distribution: SpawnDistribution::default(),
+            depth_cues: DepthCues::default(),
+            spectral: SpectralSettings::default(),
+            twinkle: TwinkleSettings::default(),
+            color_buckets: 8,
+            blend_mode: BlendMode::default(),
+            crt: CrtSettings::default(),
+            pixel_art: PixelArtSettings::default(),
+            wallpaper: WallpaperSettings::default(),
+            merge: MergeSettings::default(),
+            fade: FadeSettings::default(),
+            trail: TrailSettings::default(),
+            speed: SpeedSettings::default(),
+            parallax: ParallaxSettings::default(),
+            shake: ShakeSettings::default(),
+            bpm: BpmSettings::default(),
+            #[cfg(feature = "audio-reactive")]
+            audio_reactive: AudioReactiveSettings::default(),
+            event_log: EventLogSettings::default(),
+            star_mesh: StarMeshSettings::default(),
+            jump: JumpSettings::default(),
+            warp: WarpSettings::default(),
+            ipc: IpcSettings::default(),
+            tunnel: TunnelSettings::default(),
+            asteroids: AsteroidSettings::default(),
+            shooting_stars: ShootingStarSettings::default(),
+            attractors: AttractorSettings::default(),
+            nebula: NebulaSettings::default(),
+            constellations: ConstellationSettings::default(),
+            dodge: DodgeSettings::default(),
+            steer: SteerSettings::default(),
+            interaction: InteractionSettings::default(),
+            sync: SyncSettings::default(),
+        }
+    }
+}
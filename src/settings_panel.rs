@@ -0,0 +1,76 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A settings window over the running field, for tuning it live without pausing.
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext, EguiPlugin};
+
+use crate::actions::Action;
+use crate::menu::{preset_picker, settings_ui};
+use crate::settings::StarfieldSettings;
+use crate::state;
+use crate::theme::Theme;
+
+pub struct SettingsPanelPlugin;
+impl Plugin for SettingsPanelPlugin {
+    fn build(&self, app: &mut App) {
+        // The inspector brings its own egui in debug builds.
+        if !app.world.contains_resource::<EguiContext>() {
+            app.add_plugin(EguiPlugin);
+        }
+
+        app.init_resource::<SettingsPanel>()
+            .add_system(toggle_settings_panel)
+            .add_system(
+                settings_panel_ui
+                    .after(toggle_settings_panel)
+                    .with_run_criteria(state::ui_shown),
+            );
+    }
+}
+
+#[derive(Default)]
+struct SettingsPanel {
+    open: bool,
+}
+
+fn toggle_settings_panel(mut actions: EventReader<Action>, mut panel: ResMut<SettingsPanel>) {
+    for action in actions.iter() {
+        if *action == Action::ToggleSettingsPanel {
+            panel.open = !panel.open;
+        }
+    }
+}
+
+fn settings_panel_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut panel: ResMut<SettingsPanel>,
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+) {
+    if !panel.open {
+        return;
+    }
+
+    // Edit copies so the settings only count as changed when something actually changed.
+    let mut edited_settings = settings.clone();
+    let mut edited_theme = theme.clone();
+    let mut open = panel.open;
+
+    egui::Window::new("Settings")
+        .open(&mut open)
+        .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            preset_picker(ui, &mut edited_theme);
+            settings_ui(ui, &mut edited_settings);
+        });
+    panel.open = open;
+
+    if edited_settings != *settings {
+        *settings = edited_settings;
+    }
+    if edited_theme != *theme {
+        *theme = edited_theme;
+    }
+}
@@ -0,0 +1,60 @@
+struct PostMaterial {
+    resolution: vec2<f32>,
+    scanlines: f32,
+    curvature: f32,
+    bloom: f32,
+    chroma: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> material: PostMaterial;
+@group(1) @binding(1)
+var source: texture_2d<f32>;
+@group(1) @binding(2)
+var source_sampler: sampler;
+
+struct FragmentInput {
+    #import bevy_sprite::mesh2d_vertex_output
+};
+
+// Bends the uv outwards from the center like the glass of a CRT.
+fn barrel(uv: vec2<f32>, amount: f32) -> vec2<f32> {
+    let centered = uv * 2.0 - 1.0;
+    let offset = centered.yx * centered.yx * amount;
+    return (centered + centered * offset) * 0.5 + 0.5;
+}
+
+// Samples each channel slightly apart, like a badly converged electron gun.
+fn sample_chroma(uv: vec2<f32>) -> vec3<f32> {
+    let offset = vec2<f32>(material.chroma / material.resolution.x, 0.0);
+    let r = textureSample(source, source_sampler, uv + offset).r;
+    let g = textureSample(source, source_sampler, uv).g;
+    let b = textureSample(source, source_sampler, uv - offset).b;
+    return vec3<f32>(r, g, b);
+}
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    let uv = barrel(in.uv, material.curvature);
+    var color = sample_chroma(uv);
+
+    // Cheap bloom, just a cross of nearby samples added back on top.
+    let texel = 2.0 / material.resolution;
+    var blur = vec3<f32>(0.0);
+    blur += textureSample(source, source_sampler, uv + vec2<f32>(texel.x, 0.0)).rgb;
+    blur += textureSample(source, source_sampler, uv - vec2<f32>(texel.x, 0.0)).rgb;
+    blur += textureSample(source, source_sampler, uv + vec2<f32>(0.0, texel.y)).rgb;
+    blur += textureSample(source, source_sampler, uv - vec2<f32>(0.0, texel.y)).rgb;
+    color += blur * 0.25 * material.bloom;
+
+    // Darken every other line of the source image.
+    let line = sin(uv.y * material.resolution.y * 3.14159265);
+    color *= 1.0 - material.scanlines * (0.5 - 0.5 * line);
+
+    // Anything bent off the edge of the tube is black. This comes last so the samples above stay in uniform control flow.
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
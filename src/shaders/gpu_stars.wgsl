@@ -0,0 +1,95 @@
+// Draws every star in the storage buffer as one instanced quad, trimmed to a disc.
+
+#import bevy_sprite::mesh2d_view_bindings
+
+// Matches `GpuStarParams`.
+struct Params {
+    star_color: vec4<f32>,
+    star_tint: vec4<f32>,
+    vanishing_point: vec2<f32>,
+    extents: vec2<f32>,
+    speed_range: vec2<f32>,
+    delta: f32,
+    step: f32,
+    acceleration: f32,
+    depth_speed: f32,
+    depth_size: f32,
+    depth_brightness: f32,
+    fade_in: f32,
+    drift_speed: f32,
+    orbit_speed: f32,
+    rain_speed: f32,
+    count: u32,
+    seed: u32,
+    frame: u32,
+    reset: u32,
+    motion: u32,
+};
+
+// Matches `STAR_STRIDE`.
+struct Star {
+    position: vec2<f32>,
+    base_speed: f32,
+    depth: f32,
+    tint: f32,
+    age: f32,
+    padding: vec2<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(1) @binding(1)
+var<storage, read> stars: array<Star>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Where in the star's disc this is, -1.0 to 1.0 on both axes.
+    @location(0) offset: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+// Mirrors `FadeSettings::fade_in`.
+fn fade_in(age: f32) -> f32 {
+    if (params.fade_in <= 0.0) {
+        return 1.0;
+    }
+    return smoothstep(0.0, 1.0, min(age / params.fade_in, 1.0));
+}
+
+@vertex
+fn vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let star = stars[instance_index];
+    let fade = fade_in(star.age);
+
+    // Stars have a radius of one pixel at the nearest depth, the same as entity stars.
+    let radius = (1.0 - params.depth_size * star.depth) * fade;
+    let color = mix(params.star_color, params.star_tint, star.tint);
+
+    var out: VertexOutput;
+    // Inside the 2D camera's visible range, level with the farthest entity stars.
+    out.clip_position = view.view_proj * vec4<f32>(star.position + corner * radius, 1.0, 1.0);
+    out.offset = corner;
+    out.color = vec4<f32>(color.rgb, color.a * (1.0 - params.depth_brightness * star.depth) * fade);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    if (dot(in.offset, in.offset) > 1.0) {
+        discard;
+    }
+    return in.color;
+}
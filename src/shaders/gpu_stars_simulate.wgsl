@@ -0,0 +1,144 @@
+// Moves every star in the storage buffer one frame along, recycling the ones that leave the field.
+
+// Matches `GpuStarParams`.
+struct Params {
+    star_color: vec4<f32>,
+    star_tint: vec4<f32>,
+    vanishing_point: vec2<f32>,
+    extents: vec2<f32>,
+    speed_range: vec2<f32>,
+    // Simulated seconds since the last frame.
+    delta: f32,
+    // Seconds the acceleration is scaled by, the same fixed step entity stars use.
+    step: f32,
+    acceleration: f32,
+    depth_speed: f32,
+    depth_size: f32,
+    depth_brightness: f32,
+    fade_in: f32,
+    drift_speed: f32,
+    orbit_speed: f32,
+    rain_speed: f32,
+    count: u32,
+    seed: u32,
+    frame: u32,
+    reset: u32,
+    motion: u32,
+};
+
+// Matches `STAR_STRIDE`.
+struct Star {
+    position: vec2<f32>,
+    base_speed: f32,
+    depth: f32,
+    tint: f32,
+    age: f32,
+    padding: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(0) @binding(1)
+var<storage, read_write> stars: array<Star>;
+
+// PCG hash, cheap and good enough to scatter stars.
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state) / 4294967295.0;
+}
+
+// Anywhere from -1.0 to 1.0 on both axes.
+fn random_signed(state: ptr<function, u32>) -> vec2<f32> {
+    let x = random(state);
+    let y = random(state);
+    return vec2<f32>(x, y) * 2.0 - 1.0;
+}
+
+fn spawn_position(state: ptr<function, u32>) -> vec2<f32> {
+    return random_signed(state) * params.extents;
+}
+
+// Mirrors `sim::respawn_position`.
+fn respawn_position(state: ptr<function, u32>) -> vec2<f32> {
+    switch params.motion {
+        case 1u: {
+            return vec2<f32>(-params.extents.x, (random(state) * 2.0 - 1.0) * params.extents.y);
+        }
+        case 2u: {
+            return spawn_position(state);
+        }
+        case 3u: {
+            return vec2<f32>((random(state) * 2.0 - 1.0) * params.extents.x, params.extents.y);
+        }
+        default: {
+            let spread = random(state);
+            return params.vanishing_point + random_signed(state) * params.extents / 2.0 * spread;
+        }
+    }
+}
+
+// Mirrors `sim::velocity`.
+fn velocity(position: vec2<f32>, base_speed: f32) -> vec2<f32> {
+    let from_vanishing_point = position - params.vanishing_point;
+    switch params.motion {
+        case 1u: {
+            return vec2<f32>(1.0, 0.0) * base_speed * params.drift_speed;
+        }
+        case 2u: {
+            return vec2<f32>(-from_vanishing_point.y, from_vanishing_point.x) * params.orbit_speed;
+        }
+        case 3u: {
+            return vec2<f32>(0.0, -1.0) * base_speed * params.rain_speed;
+        }
+        default: {
+            // The direction scaled by the distance is just the offset itself.
+            return from_vanishing_point * params.acceleration * params.step * base_speed;
+        }
+    }
+}
+
+fn new_star(state: ptr<function, u32>) -> Star {
+    var star: Star;
+    star.base_speed = mix(params.speed_range.x, params.speed_range.y, random(state));
+    star.depth = random(state);
+    star.tint = random(state);
+    star.age = 0.0;
+    return star;
+}
+
+@compute @workgroup_size(64)
+fn simulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= params.count) {
+        return;
+    }
+
+    // Seeded by the field's seed so a fresh field is the same every time, and by the frame so recycled
+    // stars don't all land in the same spots.
+    var state = hash(index ^ hash(params.seed + params.frame * 2654435769u));
+
+    var star = stars[index];
+    if (params.reset != 0u) {
+        var state = hash(index ^ hash(params.seed));
+        star = new_star(&state);
+        star.position = spawn_position(&state);
+    }
+
+    let base_speed = star.base_speed * (1.0 - params.depth_speed * star.depth);
+    star.position = star.position + velocity(star.position, base_speed) * params.delta;
+    star.age = star.age + params.delta;
+
+    if (any(abs(star.position) > params.extents)) {
+        star = new_star(&state);
+        star.position = respawn_position(&state);
+    }
+
+    stars[index] = star;
+}
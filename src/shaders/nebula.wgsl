@@ -0,0 +1,57 @@
+struct NebulaMaterial {
+    inner: vec4<f32>,
+    outer: vec4<f32>,
+    offset: vec2<f32>,
+    scale: f32,
+    intensity: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> material: NebulaMaterial;
+
+struct FragmentInput {
+    #import bevy_sprite::mesh2d_vertex_output
+};
+
+fn hash(cell: vec2<f32>) -> f32 {
+    return fract(sin(dot(cell, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+// Smoothly interpolated random values at each whole coordinate.
+fn value_noise(position: vec2<f32>) -> f32 {
+    let cell = floor(position);
+    let local = fract(position);
+    let blend = local * local * (3.0 - 2.0 * local);
+
+    let a = hash(cell);
+    let b = hash(cell + vec2<f32>(1.0, 0.0));
+    let c = hash(cell + vec2<f32>(0.0, 1.0));
+    let d = hash(cell + vec2<f32>(1.0, 1.0));
+    return mix(mix(a, b, blend.x), mix(c, d, blend.x), blend.y);
+}
+
+// Several octaves of noise on top of each other, each finer and fainter than the last, for wispy clouds.
+fn fbm(position: vec2<f32>) -> f32 {
+    var value = 0.0;
+    var amplitude = 0.5;
+    var p = position;
+    for (var octave = 0; octave < 5; octave = octave + 1) {
+        value = value + amplitude * value_noise(p);
+        p = p * 2.03 + vec2<f32>(17.0, 9.0);
+        amplitude = amplitude * 0.5;
+    }
+    return value;
+}
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    let position = (in.world_position.xy + material.offset) / material.scale;
+
+    // Warping the lookup by more noise swirls the clouds instead of leaving them blobby.
+    let swirl = vec2<f32>(fbm(position + vec2<f32>(3.1, 7.4)), fbm(position + vec2<f32>(8.3, 2.8)));
+    let cloud = fbm(position + swirl * 1.5);
+
+    let density = smoothstep(0.35, 0.85, cloud) * material.intensity;
+    let color = mix(material.outer, material.inner, smoothstep(0.5, 0.9, cloud));
+    return vec4<f32>(color.rgb, color.a * density);
+}
@@ -0,0 +1,93 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Trauma based camera shake. Anything can add trauma, and the shake falls off as it wears away.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::StarfieldSettings;
+use crate::MainCamera;
+
+pub struct ShakePlugin;
+impl Plugin for ShakePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ShakeSettings>()
+            .init_resource::<CameraShake>()
+            .init_resource::<CameraHome>()
+            .add_system_to_stage(CoreStage::PostUpdate, shake_camera);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShakeSettings {
+    /// Turns all camera shake off, for anyone sensitive to motion.
+    pub enabled: bool,
+    /// How far the camera moves at full trauma, in pixels.
+    pub amplitude: f32,
+    /// How fast the camera jitters back and forth, 1.0 being normal.
+    pub frequency: f32,
+    /// How much trauma wears off per second.
+    pub decay: f32,
+}
+
+impl Default for ShakeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            amplitude: 14.0,
+            frequency: 1.0,
+            decay: 1.5,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CameraShake {
+    trauma: f32,
+}
+
+impl CameraShake {
+    /// Makes the camera shake harder, trauma is capped at 1.0.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+}
+
+/// Where the camera sits when it isn't shaking.
+#[derive(Default)]
+pub struct CameraHome(pub Vec2);
+
+/// Jitters the camera while there's trauma left.
+fn shake_camera(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    home: Res<CameraHome>,
+    mut shake: ResMut<CameraShake>,
+    mut camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    let mut transform = match camera.get_single_mut() {
+        Ok(transform) => transform,
+        Err(_) => return,
+    };
+
+    let shake_settings = settings.shake;
+    shake.trauma = (shake.trauma - shake_settings.decay * time.delta_seconds()).max(0.0);
+
+    if !shake_settings.enabled {
+        shake.trauma = 0.0;
+    }
+
+    // Squaring makes the falloff feel natural, strong shakes die down quickly and small ones linger.
+    let strength = shake.trauma * shake.trauma * shake_settings.amplitude;
+
+    // A few out of sync waves look random enough without touching the seeded generator.
+    let t = time.seconds_since_startup() as f32 * shake_settings.frequency;
+    let offset = Vec2::new(
+        (t * 47.0).sin() + (t * 83.0).sin() * 0.5,
+        (t * 59.0).cos() + (t * 71.0).cos() * 0.5,
+    ) / 1.5;
+
+    transform.translation.x = home.0.x + offset.x * strength;
+    transform.translation.y = home.0.y + offset.y * strength;
+}
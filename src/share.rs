@@ -0,0 +1,183 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Packs the settings, theme, and seed into a short string people can paste to each other.
+//!
+//! Only the field itself is shared. Settings about the machine it runs on, like ports to listen on and
+//! files to write, are left out of codes and reset when reading one, so a pasted code can't change them.
+
+use std::fmt;
+use std::io::{Read, Write};
+
+use bevy::prelude::*;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::settings::StarfieldSettings;
+use crate::theme::Theme;
+
+/// Bumped whenever the code format changes in a way old codes can't be read.
+const CODE_VERSION: u8 = 1;
+
+pub struct SharePlugin;
+impl Plugin for SharePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(log_share_code)
+            .add_system(print_share_code);
+    }
+}
+
+/// Everything needed to recreate a starfield exactly.
+#[derive(Serialize, Deserialize)]
+struct SharedField {
+    settings: StarfieldSettings,
+    theme: Theme,
+}
+
+#[derive(Debug)]
+pub enum ShareCodeError {
+    /// Not valid base64, probably cut off when it was copied.
+    Encoding,
+    /// Made by a version of the starfield that works differently.
+    Version(u8),
+    /// The contents didn't make sense.
+    Contents,
+}
+
+impl fmt::Display for ShareCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShareCodeError::Encoding => write!(f, "the code isn't complete or has typos in it"),
+            ShareCodeError::Version(version) => {
+                write!(
+                    f,
+                    "the code is for version {version}, expected {CODE_VERSION}"
+                )
+            }
+            ShareCodeError::Contents => write!(f, "the code doesn't contain valid settings"),
+        }
+    }
+}
+
+/// Turns settings and a theme into a share code.
+pub fn encode(settings: &StarfieldSettings, theme: &Theme) -> String {
+    let mut field = SharedField {
+        settings: settings.clone(),
+        theme: theme.clone(),
+    };
+    keep_local(&mut field.settings, &StarfieldSettings::default());
+
+    // Writing into a Vec can't fail, so neither can any of this.
+    let json = serde_json::to_vec(&field).unwrap_or_default();
+    let mut encoder = DeflateEncoder::new(vec![CODE_VERSION], Compression::best());
+    let _ = encoder.write_all(&json);
+    let bytes = encoder.finish().unwrap_or_default();
+
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Reads settings and a theme back out of a share code, with this machine's settings left at their defaults.
+pub fn decode(code: &str) -> Result<(StarfieldSettings, Theme), ShareCodeError> {
+    let bytes = base64::decode_config(code.trim(), base64::URL_SAFE_NO_PAD)
+        .map_err(|_| ShareCodeError::Encoding)?;
+
+    let (version, compressed) = bytes.split_first().ok_or(ShareCodeError::Encoding)?;
+    if *version != CODE_VERSION {
+        return Err(ShareCodeError::Version(*version));
+    }
+
+    let mut json = Vec::new();
+    DeflateDecoder::new(compressed)
+        .read_to_end(&mut json)
+        .map_err(|_| ShareCodeError::Encoding)?;
+
+    let mut field: SharedField =
+        serde_json::from_slice(&json).map_err(|_| ShareCodeError::Contents)?;
+    keep_local(&mut field.settings, &StarfieldSettings::default());
+    Ok((field.settings, field.theme))
+}
+
+/// Puts back the settings that are about this machine rather than the field, from `local`.
+pub fn keep_local(shared: &mut StarfieldSettings, local: &StarfieldSettings) {
+    shared.sync = local.sync.clone();
+    shared.ipc = local.ipc;
+    shared.event_log = local.event_log;
+    shared.wallpaper = local.wallpaper;
+}
+
+fn log_share_code(settings: Res<StarfieldSettings>, theme: Res<Theme>) {
+    info!(
+        "Seed {}, share code: {}",
+        settings.seed,
+        encode(&settings, &theme)
+    );
+}
+
+/// Prints the share code for the field as it is right now.
+fn print_share_code(
+    mut actions: EventReader<Action>,
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+) {
+    for action in actions.iter() {
+        if *action == Action::PrintShareCode {
+            println!("{}", encode(&settings, &theme));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::SyncRole;
+
+    #[test]
+    fn codes_round_trip() {
+        let settings = StarfieldSettings {
+            seed: 42,
+            star_count: 777,
+            ..default()
+        };
+        let theme = Theme {
+            glow: 0.25,
+            ..default()
+        };
+
+        let (decoded_settings, decoded_theme) = decode(&encode(&settings, &theme)).unwrap();
+        assert!(decoded_settings == settings);
+        assert!(decoded_theme == theme);
+    }
+
+    #[test]
+    fn codes_leave_out_local_settings() {
+        let mut settings = StarfieldSettings::default();
+        settings.ipc.enabled = true;
+        settings.event_log.enabled = true;
+        settings.sync.role = SyncRole::Follower;
+        settings.wallpaper.width = 640;
+
+        let (decoded, _) = decode(&encode(&settings, &Theme::default())).unwrap();
+        let defaults = StarfieldSettings::default();
+        assert!(decoded.ipc == defaults.ipc);
+        assert!(decoded.event_log == defaults.event_log);
+        assert!(decoded.sync == defaults.sync);
+        assert!(decoded.wallpaper == defaults.wallpaper);
+    }
+
+    #[test]
+    fn rejects_broken_codes() {
+        assert!(matches!(
+            decode("not a code!"),
+            Err(ShareCodeError::Encoding)
+        ));
+        assert!(matches!(decode(""), Err(ShareCodeError::Encoding)));
+
+        let other_version = base64::encode_config([CODE_VERSION + 1], base64::URL_SAFE_NO_PAD);
+        assert!(matches!(
+            decode(&other_version),
+            Err(ShareCodeError::Version(version)) if version == CODE_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Now and then a shooting star streaks straight across the screen, trailing a long tail.
+//!
+//! Shooting stars aren't stars, they fly in a straight line at their own speed and leave the field's
+//! radial motion alone.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::settings::StarfieldSettings;
+use crate::sim::{Field, StarfieldRng};
+use crate::speed::SimulationSpeed;
+use crate::star_mesh::StarMesh;
+use crate::theme::Theme;
+use crate::trail::TrailMesh;
+
+/// Radius of a shooting star's head, in pixels.
+const HEAD_SIZE: f32 = 2.5;
+
+/// How wide the tail is where it meets the head, in pixels.
+const TAIL_WIDTH: f32 = 4.0;
+
+/// Opacity of the tail where it meets the head.
+const TAIL_OPACITY: f32 = 0.6;
+
+/// In front of every star, inside the 2D camera's visible range.
+const SHOOTING_STAR_Z: f32 = 600.0;
+
+pub struct ShootingStarPlugin;
+impl Plugin for ShootingStarPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ShootingStarSettings>()
+            .add_system(spawn_shooting_stars)
+            .add_system_to_stage(CoreStage::PostUpdate, move_shooting_stars);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShootingStarSettings {
+    pub enabled: bool,
+    /// On average how many shooting stars go by a minute at normal speed.
+    pub frequency: f32,
+    /// How fast they fly, in pixels per second.
+    pub speed: f32,
+    /// How long their tails are, in pixels.
+    pub tail_length: f32,
+}
+
+impl Default for ShootingStarSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            frequency: 2.0,
+            speed: 1800.0,
+            tail_length: 300.0,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ShootingStar {
+    /// Which way it's flying, always of length 1.
+    direction: Vec2,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_shooting_stars(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    speed: Res<SimulationSpeed>,
+    theme: Res<Theme>,
+    star_mesh: Res<StarMesh>,
+    trail_mesh: Res<TrailMesh>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    field: Res<Field>,
+    mut rng: ResMut<StarfieldRng>,
+) {
+    let shooting_stars = settings.shooting_stars;
+    if !shooting_stars.enabled {
+        return;
+    }
+
+    let chance = shooting_stars.frequency / 60.0 * time.delta_seconds() * speed.current;
+    let rng = &mut rng.effects;
+    if rng.gen_range(0.0..1.0) >= chance {
+        return;
+    }
+
+    // Starts just outside the field on one side and crosses somewhere near the middle to the other.
+    let extent = field.extent();
+    let angle = rng.gen_range(0.0..TAU);
+    let direction = Vec2::new(angle.cos(), angle.sin());
+    let across = direction.perp() * rng.gen_range(-0.5..=0.5) * extent;
+    let start = across - direction * extent * 1.5;
+
+    let color = theme.star_color(0.0);
+    let head = pool.get(color, &mut materials);
+    let tail = pool.get(*color.clone().set_a(TAIL_OPACITY), &mut materials);
+    let length = shooting_stars.tail_length;
+
+    commands
+        .spawn_bundle(SpatialBundle::from_transform(
+            Transform::from_translation(start.extend(SHOOTING_STAR_Z))
+                .with_rotation(Quat::from_rotation_z(angle)),
+        ))
+        .insert(ShootingStar { direction })
+        .with_children(|parent| {
+            parent.spawn_bundle(MaterialMesh2dBundle {
+                mesh: star_mesh.0.clone().into(),
+                material: head,
+                transform: Transform::from_scale(Vec3::new(HEAD_SIZE, HEAD_SIZE, 1.0)),
+                ..default()
+            });
+
+            // The trail mesh is widest at +x, so it sits behind the head and tapers away from it.
+            parent.spawn_bundle(MaterialMesh2dBundle {
+                mesh: trail_mesh.0.clone().into(),
+                material: tail,
+                transform: Transform::from_xyz(-length / 2.0, 0.0, -0.01)
+                    .with_scale(Vec3::new(length, TAIL_WIDTH, 1.0)),
+                ..default()
+            });
+        });
+}
+
+/// Flies shooting stars across the screen and clears them away once they've left the field.
+fn move_shooting_stars(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    speed: Res<SimulationSpeed>,
+    field: Res<Field>,
+    mut query: Query<(Entity, &ShootingStar, &mut Transform)>,
+) {
+    let delta = time.delta_seconds() * speed.current;
+    let distance = settings.shooting_stars.speed * delta;
+    let limit = field.extent() * 1.5 + settings.shooting_stars.tail_length;
+
+    for (entity, shooting_star, mut transform) in query.iter_mut() {
+        transform.translation += (shooting_star.direction * distance).extend(0.0);
+
+        // Only once it's on its way out, it starts out this far away on the other side.
+        let position = transform.translation.truncate();
+        if position.dot(shooting_star.direction) > limit {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,399 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! The starfield simulation itself.
+//! Nothing in here knows about Bevy, so front-ends other than the windowed one can drive it too. Each one
+//! keeps its own [`Field`] and [`StarfieldRng`], so any number of fields can run side by side.
+
+use std::ops::RangeInclusive;
+
+use glam::Vec2;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::spawn_distribution::{Layout, SpawnDistribution};
+
+/// The size of the field and how stars move through it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FieldShape {
+    /// How far the field reaches from the middle in x and y before stars are recycled.
+    pub extent: f32,
+    /// Slowest and fastest base speed a star can be given.
+    pub speed_range: (f32, f32),
+    /// How strongly stars speed up as they get further from the middle.
+    pub acceleration: f32,
+}
+
+impl Default for FieldShape {
+    fn default() -> Self {
+        Self {
+            extent: 1000.0,
+            speed_range: (10.0, 80.0),
+            acceleration: 1.0,
+        }
+    }
+}
+
+/// Simulation depth of a star, 0.0 being right in front of the camera and 1.0 the far plane.
+pub const DEPTH_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+
+/// How star depths are spread between the nearest and farthest planes.
+/// This is what mostly decides how strong parallax feels, so it's separate from how stars are placed in x and y.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepthDistribution {
+    /// Every depth is as likely as any other.
+    #[default]
+    Uniform,
+    /// Most stars sit near the front, so parallax feels strong.
+    FrontLoaded,
+    /// Stars thicken out exponentially towards the far plane, for a deep, calm field.
+    Exponential,
+}
+
+impl DepthDistribution {
+    /// Maps a uniform random number from 0.0 to 1.0 onto a depth with this distribution.
+    fn shape(self, uniform: f32) -> f32 {
+        match self {
+            DepthDistribution::Uniform => uniform,
+            DepthDistribution::FrontLoaded => uniform * uniform,
+            DepthDistribution::Exponential => {
+                // The inverse of an exponential density, 3.0 being how sharply it rises towards the back.
+                const STEEPNESS: f32 = 3.0;
+                (1.0 + uniform * (STEEPNESS.exp() - 1.0)).ln() / STEEPNESS
+            }
+        }
+    }
+}
+
+/// How stars move through the field.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MotionMode {
+    /// Flying through the field, stars stream out from the vanishing point and speed up as they pass.
+    #[default]
+    FlyThrough,
+    /// Stars slowly drift sideways past the camera.
+    Drift,
+    /// The whole sky turns around the vanishing point, like a time-lapse of the night sky.
+    Orbit,
+    /// Stars fall straight down like rain.
+    Rain,
+}
+
+impl MotionMode {
+    pub const ALL: [MotionMode; 4] = [
+        MotionMode::FlyThrough,
+        MotionMode::Drift,
+        MotionMode::Orbit,
+        MotionMode::Rain,
+    ];
+
+    /// The mode after this one, wrapping back around to the first.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|mode| *mode == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// How much faster than their base speed stars drift sideways.
+pub const DRIFT_SPEED: f32 = 1.5;
+
+/// How fast the sky turns in orbit mode, in radians per second.
+pub const ORBIT_SPEED: f32 = 0.05;
+
+/// How much faster than their base speed stars fall in rain mode.
+pub const RAIN_SPEED: f32 = 5.0;
+
+/// Which of the seed's streams effects draw from, the field has the first one.
+const EFFECTS_STREAM: u64 = 1;
+
+/// The generators all randomness in the simulation comes from, so the same seed always gives the same field.
+///
+/// Placing and recycling stars draws from a stream of its own, which only the field's setup and its fixed
+/// physics steps touch. Everything that comes and goes with the frame rate, like shooting stars and
+/// twinkles, draws from the other, so effects can never change where stars end up.
+pub struct StarfieldRng {
+    pub field: ChaCha12Rng,
+    pub effects: ChaCha12Rng,
+}
+
+impl StarfieldRng {
+    pub fn new(seed: u64) -> Self {
+        let mut effects = ChaCha12Rng::seed_from_u64(seed);
+        effects.set_stream(EFFECTS_STREAM);
+
+        Self {
+            field: ChaCha12Rng::seed_from_u64(seed),
+            effects,
+        }
+    }
+
+    /// Restarts both streams from a seed.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::new(seed);
+    }
+
+    /// Exactly where both streams are up to, so they can carry on from there later.
+    pub fn state(&self) -> RngState {
+        RngState {
+            field: StreamState::of(&self.field),
+            effects: StreamState::of(&self.effects),
+        }
+    }
+
+    /// Puts both streams back where they were when `state` was taken.
+    pub fn restore(&mut self, state: RngState) {
+        self.field = state.field.generator();
+        self.effects = state.effects.generator();
+    }
+}
+
+/// Seeded from the operating system, until there's a seed to start from.
+impl Default for StarfieldRng {
+    fn default() -> Self {
+        Self {
+            field: ChaCha12Rng::from_entropy(),
+            effects: ChaCha12Rng::from_entropy(),
+        }
+    }
+}
+
+/// Exactly where the random generators are up to, see [`StarfieldRng::state`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RngState {
+    field: StreamState,
+    effects: StreamState,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct StreamState {
+    seed: [u8; 32],
+    stream: u64,
+    /// How many words into the stream the generator is, split in two since it doesn't fit in a `u64`.
+    word_pos: (u64, u64),
+}
+
+impl StreamState {
+    fn of(rng: &ChaCha12Rng) -> Self {
+        let word_pos = rng.get_word_pos();
+        Self {
+            seed: rng.get_seed(),
+            stream: rng.get_stream(),
+            word_pos: ((word_pos >> 64) as u64, word_pos as u64),
+        }
+    }
+
+    fn generator(self) -> ChaCha12Rng {
+        let mut rng = ChaCha12Rng::from_seed(self.seed);
+        rng.set_stream(self.stream);
+        rng.set_word_pos((self.word_pos.0 as u128) << 64 | self.word_pos.1 as u128);
+        rng
+    }
+}
+
+/// The field stars move through, and how they're placed in it.
+///
+/// Settings reach the simulation through here, and every star is moved and recycled by it.
+#[derive(Clone, Debug)]
+pub struct Field {
+    shape: FieldShape,
+    /// Width over height of the field, 1.0 being square.
+    aspect_ratio: f32,
+    /// Where stars fly out from, the middle of the field unless it's been steered somewhere else.
+    vanishing_point: Vec2,
+    /// The distribution new and recycled stars take their depth from.
+    depth_distribution: DepthDistribution,
+    motion: MotionMode,
+    layout: Layout,
+}
+
+impl Default for Field {
+    fn default() -> Self {
+        Self {
+            shape: FieldShape::default(),
+            aspect_ratio: 1.0,
+            vanishing_point: Vec2::ZERO,
+            depth_distribution: DepthDistribution::default(),
+            motion: MotionMode::default(),
+            layout: Layout::new(SpawnDistribution::default(), 0),
+        }
+    }
+}
+
+impl Field {
+    /// Lays out where clusters and voids lie afresh for a seed, so the same seed always gives the same field.
+    pub fn reseed(&mut self, seed: u64) {
+        self.layout.reseed(seed);
+    }
+
+    /// Changes the shape of the field. Stars already outside a smaller extent are recycled on their next update.
+    pub fn set_shape(&mut self, shape: FieldShape) {
+        self.shape = shape;
+    }
+
+    /// Stretches the field to a different width over height, keeping its extent along the longer side.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.aspect_ratio = aspect_ratio.max(f32::EPSILON);
+    }
+
+    /// Moves the point stars fly out from, which changes the direction of every star from its next update.
+    pub fn set_vanishing_point(&mut self, point: Vec2) {
+        self.vanishing_point = point;
+    }
+
+    /// Changes the distribution depths are drawn from, for every star spawned from now on.
+    pub fn set_depth_distribution(&mut self, distribution: DepthDistribution) {
+        self.depth_distribution = distribution;
+    }
+
+    /// Changes how stars move, which takes effect from their next update.
+    pub fn set_motion_mode(&mut self, mode: MotionMode) {
+        self.motion = mode;
+    }
+
+    /// Changes how stars are spread over the field, for every star placed from now on.
+    pub fn set_spawn_distribution(&mut self, distribution: SpawnDistribution) {
+        self.layout.set_distribution(distribution);
+    }
+
+    /// How far the field reaches from the middle along its longer side.
+    pub fn extent(&self) -> f32 {
+        self.shape.extent
+    }
+
+    /// How far the field reaches from the middle in x and in y.
+    pub fn extents(&self) -> Vec2 {
+        let extent = self.extent();
+        if self.aspect_ratio >= 1.0 {
+            Vec2::new(extent, extent / self.aspect_ratio)
+        } else {
+            Vec2::new(extent * self.aspect_ratio, extent)
+        }
+    }
+
+    pub fn vanishing_point(&self) -> Vec2 {
+        self.vanishing_point
+    }
+
+    /// Calculates velocity based on the speed of the star and how stars are moving.
+    pub fn velocity(&self, position: Vec2, base_speed: f32, delta_seconds: f32) -> Vec2 {
+        match self.motion {
+            MotionMode::FlyThrough => {
+                self.fly_through_velocity(position, base_speed, delta_seconds)
+            }
+            MotionMode::Drift => Vec2::X * base_speed * DRIFT_SPEED,
+            MotionMode::Orbit => (position - self.vanishing_point).perp() * ORBIT_SPEED,
+            MotionMode::Rain => Vec2::NEG_Y * base_speed * RAIN_SPEED,
+        }
+    }
+
+    /// Calculates velocity based on the speed of the star as well as the current acceleration.
+    fn fly_through_velocity(&self, position: Vec2, base_speed: f32, delta_seconds: f32) -> Vec2 {
+        // We're always moving away from the vanishing point, so we don't have to calculate direction.
+        let from_vanishing_point = position - self.vanishing_point;
+        let movement_direction = from_vanishing_point.normalize_or_zero();
+
+        // Acceleration scaled with distance.
+        // We only multiply delta once even though a = s*(dt^2) this is because we'll multiply velocity later.
+        let acceleration = from_vanishing_point.length() * self.shape.acceleration * delta_seconds;
+        movement_direction * acceleration * base_speed
+    }
+
+    /// Checks if a location is outside of the space extent.
+    pub fn outside_extent(&self, position: Vec2) -> bool {
+        position.abs().cmpgt(self.extents()).any()
+    }
+
+    /// How far a position is from the middle towards the edge of the space extent, 0.0 in the middle and 1.0 at the edge.
+    pub fn extent_progress(&self, position: Vec2) -> f32 {
+        (position.abs() / self.extents()).max_element()
+    }
+
+    /// A random position anywhere in the space extent, used when the field is first filled.
+    pub fn spawn_position(&self, rng: &mut impl Rng) -> Vec2 {
+        self.layout.place(self.extents(), rng)
+    }
+
+    /// Where a star that left the space extent comes back in, depending on how stars are moving.
+    pub fn respawn_position(&self, rng: &mut impl Rng) -> Vec2 {
+        let extents = self.extents();
+        match self.motion {
+            // Positions cluster towards the vanishing point and thin out to half the space extent, so recycled
+            // stars emerge from the distance instead of appearing halfway across the field.
+            MotionMode::FlyThrough => {
+                let spread = rng.gen_range(0.0..=1.0_f32);
+                self.vanishing_point + self.layout.place(extents / 2.0, rng) * spread
+            }
+            // Stars wrap around to the edge they'd have come in from, level with wherever they'd have been placed.
+            MotionMode::Drift => Vec2::new(-extents.x, self.layout.place(extents, rng).y),
+            MotionMode::Rain => Vec2::new(self.layout.place(extents, rng).x, extents.y),
+            // Only the corners turn out of the field, anywhere else is as good a place to fade back in.
+            MotionMode::Orbit => self.spawn_position(rng),
+        }
+    }
+
+    /// A random base speed for a new or recycled star.
+    pub fn random_base_speed(&self, rng: &mut impl Rng) -> f32 {
+        let (slowest, fastest) = self.shape.speed_range;
+        if fastest > slowest {
+            rng.gen_range(slowest..=fastest)
+        } else {
+            slowest
+        }
+    }
+
+    /// A random depth for a new or recycled star.
+    pub fn random_depth(&self, rng: &mut impl Rng) -> f32 {
+        let (near, far) = (*DEPTH_RANGE.start(), *DEPTH_RANGE.end());
+        near + (far - near) * self.depth_distribution.shape(rng.gen_range(0.0..=1.0))
+    }
+}
+
+/// A random position no further than `extents` from the middle in x and y.
+pub(crate) fn random_within(extents: Vec2, rng: &mut impl Rng) -> Vec2 {
+    Vec2::new(
+        rng.gen_range(-extents.x..=extents.x),
+        rng.gen_range(-extents.y..=extents.y),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restored_rng_carries_on_where_it_was() {
+        let mut rng = StarfieldRng::new(7);
+        rng.field.gen::<u64>();
+        rng.effects.gen::<u32>();
+        let state = rng.state();
+        let expected: (u64, u64) = (rng.field.gen(), rng.effects.gen());
+
+        rng.reseed(99);
+        rng.restore(state);
+        assert_eq!((rng.field.gen(), rng.effects.gen()), expected);
+    }
+
+    #[test]
+    fn streams_differ() {
+        let mut rng = StarfieldRng::new(7);
+        assert_ne!(rng.field.gen::<u64>(), rng.effects.gen::<u64>());
+    }
+
+    #[test]
+    fn depth_shapes_stay_in_range() {
+        for distribution in [
+            DepthDistribution::Uniform,
+            DepthDistribution::FrontLoaded,
+            DepthDistribution::Exponential,
+        ] {
+            assert_eq!(distribution.shape(0.0), 0.0);
+            assert!((distribution.shape(1.0) - 1.0).abs() < 1e-5);
+            assert!(distribution.shape(0.25) < distribution.shape(0.75));
+        }
+
+        // Front loading pulls depths forward, exponential pushes them back.
+        assert!(DepthDistribution::FrontLoaded.shape(0.5) < 0.5);
+        assert!(DepthDistribution::Exponential.shape(0.5) > 0.5);
+    }
+}
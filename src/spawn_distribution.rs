@@ -0,0 +1,284 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Where in the field stars are placed, so it has clumps, voids and a faint galactic band instead of an
+//! evenly sprinkled look.
+//!
+//! Some stars gather around a handful of cluster centers, falling off like a Gaussian. The rest are spread
+//! over the whole field, but thinned out where a noise field runs low, which leaves voids, and thickened
+//! along a band running through the middle. Like [`crate::sim`], nothing in here knows about Bevy.
+
+use glam::Vec2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::sim;
+
+/// How many spots are tried for a star before the last one is taken anyway, so placing never stalls.
+const PLACEMENT_ATTEMPTS: usize = 8;
+
+/// How far out cluster centers can be, as a fraction of the way to the field's edge.
+const CLUSTER_REACH: f32 = 0.8;
+
+/// Layers of noise summed for the voids, each half the size of the one before.
+const NOISE_OCTAVES: u32 = 3;
+
+/// How stars are spread over the field as they're placed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpawnDistribution {
+    /// Spreads stars out evenly when off.
+    pub enabled: bool,
+    /// How many clusters stars gather in.
+    pub clusters: u32,
+    /// Fraction of stars placed in a cluster rather than across the field, 0.0 to 1.0.
+    pub cluster_share: f32,
+    /// How far a cluster spreads out from its center, as a fraction of the field's extent.
+    pub cluster_size: f32,
+    /// How empty the voids are, 0.0 leaves none and 1.0 clears them out entirely.
+    pub voids: f32,
+    /// Roughly how big the voids are, as a fraction of the field's extent.
+    pub void_size: f32,
+    /// Extra stars along the galactic band, 1.0 makes it twice as dense as the rest. 0.0 disables it.
+    pub band: f32,
+    /// Which way the band runs, in degrees counterclockwise from horizontal.
+    pub band_angle: f32,
+    /// How wide the band is, as a fraction of the field's extent.
+    pub band_width: f32,
+}
+
+impl Default for SpawnDistribution {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            clusters: 5,
+            cluster_share: 0.3,
+            cluster_size: 0.08,
+            voids: 0.7,
+            void_size: 0.35,
+            band: 2.0,
+            band_angle: 25.0,
+            band_width: 0.25,
+        }
+    }
+}
+
+/// Where the clusters and voids are for the current seed.
+#[derive(Clone, Debug)]
+pub struct Layout {
+    distribution: SpawnDistribution,
+    seed: u64,
+    /// Each cluster's center, as a fraction of the way to the field's edge in x and y.
+    centers: Vec<Vec2>,
+    noise_seed: u32,
+}
+
+impl Layout {
+    pub fn new(distribution: SpawnDistribution, seed: u64) -> Self {
+        // A generator of its own, so the layout doesn't depend on how many stars were placed before it.
+        let mut rng = StdRng::seed_from_u64(seed);
+        let centers = (0..distribution.clusters)
+            .map(|_| {
+                Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)) * CLUSTER_REACH
+            })
+            .collect();
+
+        Self {
+            distribution,
+            seed,
+            centers,
+            noise_seed: rng.gen(),
+        }
+    }
+
+    /// Lays out the clusters and voids afresh for a seed, so the same seed always gives the same layout.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Self::new(self.distribution, seed);
+    }
+
+    /// Changes how stars are spread over the field, for every star placed from now on.
+    pub fn set_distribution(&mut self, distribution: SpawnDistribution) {
+        if distribution != self.distribution {
+            *self = Self::new(distribution, self.seed);
+        }
+    }
+
+    /// A position no further than `extents` from the middle in x and y, spread out the way the distribution says.
+    pub fn place(&self, extents: Vec2, rng: &mut impl Rng) -> Vec2 {
+        if !self.distribution.enabled {
+            return sim::random_within(extents, rng);
+        }
+
+        let distribution = self.distribution;
+        let scale = extents.max_element();
+
+        let clustered = rng.gen_range(0.0..1.0_f32) < distribution.cluster_share;
+        if clustered && !self.centers.is_empty() {
+            let center = self.centers[rng.gen_range(0..self.centers.len())] * extents;
+            let position = center + gaussian(rng) * distribution.cluster_size * scale;
+            // Anything in the tail past the edge is placed across the field instead.
+            if !position.abs().cmpgt(extents).any() {
+                return position;
+            }
+        }
+
+        let mut position = sim::random_within(extents, rng);
+        for _ in 1..PLACEMENT_ATTEMPTS {
+            if rng.gen_range(0.0..=1.0) <= self.density(position / scale) {
+                break;
+            }
+            position = sim::random_within(extents, rng);
+        }
+        position
+    }
+
+    /// How likely a star is to be kept where it landed, from 0.0 to 1.0.
+    /// `position` is in fractions of the field's extent, so the layout stretches with the field.
+    fn density(&self, position: Vec2) -> f32 {
+        let distribution = self.distribution;
+
+        let noise = fractal_noise(self.noise_seed, position / distribution.void_size.max(0.01));
+        // Sharpened, so there are clear voids instead of a gentle ripple.
+        let noise = smoothstep(0.3, 0.7, noise);
+        let voids = 1.0 - distribution.voids.clamp(0.0, 1.0) * (1.0 - noise);
+
+        let (sin, cos) = distribution.band_angle.to_radians().sin_cos();
+        let across = position.dot(Vec2::new(-sin, cos)) / distribution.band_width.max(0.01);
+        let band = distribution.band.max(0.0);
+        let band = (1.0 + band * (-across * across).exp()) / (1.0 + band);
+
+        voids * band
+    }
+}
+
+/// A random offset with a standard normal spread, using the Box-Muller transform.
+fn gaussian(rng: &mut impl Rng) -> Vec2 {
+    let radius = (-2.0 * rng.gen_range(f32::EPSILON..=1.0_f32).ln()).sqrt();
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    Vec2::new(angle.cos(), angle.sin()) * radius
+}
+
+fn smoothstep(from: f32, to: f32, value: f32) -> f32 {
+    let t = ((value - from) / (to - from)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// PCG hash, cheap and plenty random enough for noise.
+fn hash(value: u32) -> u32 {
+    let state = value.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277_803_737);
+    (word >> 22) ^ word
+}
+
+/// A random value from 0.0 to 1.0 for a corner of the noise grid.
+fn lattice(seed: u32, x: i32, y: i32) -> f32 {
+    hash(seed ^ hash(x as u32 ^ hash(y as u32))) as f32 / u32::MAX as f32
+}
+
+/// Smoothly blends between random values at the corners of a unit grid, from 0.0 to 1.0.
+fn value_noise(seed: u32, position: Vec2) -> f32 {
+    let cell = position.floor();
+    let (x, y) = (cell.x as i32, cell.y as i32);
+    let along = position - cell;
+    let blend = along * along * (Vec2::splat(3.0) - 2.0 * along);
+
+    let lerp = |from: f32, to: f32, t: f32| from + (to - from) * t;
+    let bottom = lerp(lattice(seed, x, y), lattice(seed, x + 1, y), blend.x);
+    let top = lerp(
+        lattice(seed, x, y + 1),
+        lattice(seed, x + 1, y + 1),
+        blend.x,
+    );
+    lerp(bottom, top, blend.y)
+}
+
+/// Several layers of value noise at finer and finer sizes, from 0.0 to 1.0.
+fn fractal_noise(seed: u32, position: Vec2) -> f32 {
+    let (mut total, mut weight, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, 1.0);
+    for octave in 0..NOISE_OCTAVES {
+        total += value_noise(seed.wrapping_add(octave), position * frequency) * amplitude;
+        weight += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    total / weight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> SpawnDistribution {
+        SpawnDistribution {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stars_stay_inside_the_field() {
+        let layout = Layout::new(enabled(), 3);
+        let extents = Vec2::new(800.0, 450.0);
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..10_000 {
+            let position = layout.place(extents, &mut rng);
+            assert!(!position.abs().cmpgt(extents).any(), "{position}");
+        }
+    }
+
+    #[test]
+    fn same_seed_same_layout() {
+        let place = |seed| {
+            let layout = Layout::new(enabled(), seed);
+            let mut rng = StdRng::seed_from_u64(9);
+            (0..100)
+                .map(|_| layout.place(Vec2::splat(500.0), &mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(place(4), place(4));
+        assert_ne!(place(4), place(5));
+    }
+
+    #[test]
+    fn clustered_stars_gather_around_centers() {
+        let distribution = SpawnDistribution {
+            cluster_share: 1.0,
+            cluster_size: 0.01,
+            ..enabled()
+        };
+        let layout = Layout::new(distribution, 3);
+        let extents = Vec2::splat(1000.0);
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..1000 {
+            let position = layout.place(extents, &mut rng);
+            let nearest = layout
+                .centers
+                .iter()
+                .map(|center| (*center * extents).distance(position))
+                .fold(f32::MAX, f32::min);
+            // Six standard deviations, which a Gaussian all but never strays past.
+            assert!(nearest < 60.0, "{position} is {nearest} from every cluster");
+        }
+    }
+
+    #[test]
+    fn band_is_denser_than_the_rest() {
+        let distribution = SpawnDistribution {
+            voids: 0.0,
+            band_angle: 0.0,
+            ..enabled()
+        };
+        let layout = Layout::new(distribution, 3);
+        assert!(layout.density(Vec2::new(0.5, 0.0)) > layout.density(Vec2::new(0.5, 0.9)));
+        assert!((layout.density(Vec2::ZERO) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn noise_stays_in_range() {
+        for step in 0..1000 {
+            let position = Vec2::new(step as f32 * 0.37, step as f32 * -0.21);
+            let noise = fractal_noise(11, position);
+            assert!((0.0..=1.0).contains(&noise), "{noise}");
+        }
+    }
+}
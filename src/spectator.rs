@@ -0,0 +1,260 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A free flying camera through the star volume, treating each star's depth as a real distance.
+//!
+//! The field keeps being simulated in 2D, only what's drawn is projected from wherever the spectator is.
+//! Warp flies forward, the other directions have their own actions and the mouse looks around.
+
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::bindings::ActionInput;
+use crate::depth::{depth_to_z, z_to_depth};
+use crate::materials::StarMaterial;
+use crate::parallax::ParallaxSystem;
+use crate::state::{self, AppState};
+
+/// How far behind the nearest stars the spectator starts, which is also the focal length of the projection.
+/// Stars at the nearest depth look the same size from here as they do in the flat view.
+const HOME_DISTANCE: f32 = 1000.0;
+
+/// How deep the star volume is, from the nearest to the farthest depth.
+const DEPTH_SCALE: f32 = 1000.0;
+
+/// Anything closer to the spectator than this isn't drawn.
+const NEAR_PLANE: f32 = 10.0;
+
+/// Pixels per second the spectator flies at.
+const FLY_SPEED: f32 = 400.0;
+
+/// Radians turned per pixel the mouse moves.
+const LOOK_SENSITIVITY: f32 = 0.003;
+
+/// How quickly the spectator flies back to the default viewpoint, higher is snappier.
+const RETURN_FOLLOW: f32 = 3.0;
+
+/// Seconds it takes to blend between the flat view and the spectator's.
+const BLEND_DURATION: f32 = 0.5;
+
+pub struct SpectatorPlugin;
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Spectator>()
+            .add_system(toggle_spectator)
+            .add_system(
+                fly.with_run_criteria(state::running)
+                    .after(toggle_spectator),
+            )
+            .add_system(grab_cursor)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_spectator_view
+                    .label(SpectatorSystem)
+                    .after(TransformSystem::TransformPropagate)
+                    .before(ParallaxSystem),
+            );
+    }
+}
+
+/// Projects stars from the spectator's point of view after transforms are propagated.
+#[derive(SystemLabel, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpectatorSystem;
+
+/// Where the spectator is and which way it's looking, relative to the default viewpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pose {
+    pub offset: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Pose {
+    fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+}
+
+#[derive(Default)]
+pub struct Spectator {
+    active: bool,
+    /// Flying back to the default viewpoint, and leaving spectator mode once there if it's no longer active.
+    returning: bool,
+    /// Being moved along a recorded path instead of by the user.
+    on_rail: bool,
+    pose: Pose,
+    /// How much of the spectator's view is shown, 0.0 being the flat view.
+    blend: f32,
+}
+
+impl Spectator {
+    /// Whether the spectator's view is being shown at all.
+    pub fn in_view(&self) -> bool {
+        self.blend > 0.0
+    }
+
+    /// Whether the spectator is out and about, flown by the user or on a rail.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Whether movement is going to the spectator instead of the field.
+    pub fn flying(&self) -> bool {
+        self.active && !self.returning && !self.on_rail
+    }
+
+    pub fn on_rail(&self) -> bool {
+        self.on_rail
+    }
+
+    pub fn pose(&self) -> Pose {
+        self.pose
+    }
+
+    /// Puts the spectator somewhere along a rail, entering spectator mode if needed.
+    pub fn follow_rail(&mut self, pose: Pose) {
+        self.active = true;
+        self.returning = false;
+        self.on_rail = true;
+        self.pose = pose;
+    }
+
+    /// Takes the spectator off its rail and flies it home.
+    pub fn leave_rail(&mut self) {
+        self.active = false;
+        self.returning = true;
+        self.on_rail = false;
+    }
+}
+
+fn toggle_spectator(mut actions: EventReader<Action>, mut spectator: ResMut<Spectator>) {
+    for action in actions.iter() {
+        match action {
+            Action::ToggleSpectator => {
+                spectator.active = !spectator.active;
+                spectator.returning = !spectator.active;
+                spectator.on_rail = false;
+            }
+            Action::ReturnToDefaultView => spectator.returning = true,
+            _ => {}
+        }
+    }
+}
+
+fn fly(
+    time: Res<Time>,
+    input: Res<ActionInput>,
+    mut mouse: EventReader<MouseMotion>,
+    mut spectator: ResMut<Spectator>,
+) {
+    let delta = time.delta_seconds();
+    let look: Vec2 = mouse.iter().map(|motion| &motion.delta).sum();
+
+    if spectator.flying() {
+        // Looking right turns towards +x, and looking down towards -y.
+        spectator.pose.yaw += look.x * LOOK_SENSITIVITY;
+        spectator.pose.pitch = (spectator.pose.pitch + look.y * LOOK_SENSITIVITY)
+            .clamp(-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2);
+
+        let axis = |positive: Action, negative: Action| {
+            input.pressed(positive) as i32 as f32 - input.pressed(negative) as i32 as f32
+        };
+        let direction = Vec3::new(
+            axis(Action::FlyRight, Action::FlyLeft),
+            0.0,
+            axis(Action::Warp, Action::FlyBack),
+        );
+
+        let movement =
+            spectator.pose.rotation() * direction.normalize_or_zero() * FLY_SPEED * delta;
+        spectator.pose.offset += movement;
+    }
+
+    if spectator.returning {
+        let follow = (RETURN_FOLLOW * delta).min(1.0);
+        let pose = &mut spectator.pose;
+        pose.offset = pose.offset.lerp(Vec3::ZERO, follow);
+        pose.yaw -= pose.yaw * follow;
+        pose.pitch -= pose.pitch * follow;
+
+        let home = pose.offset.length() < 1.0 && pose.yaw.abs() < 0.001 && pose.pitch.abs() < 0.001;
+        if home {
+            *spectator = Spectator {
+                active: spectator.active,
+                blend: spectator.blend,
+                ..default()
+            };
+        }
+    }
+
+    // Only blend back to the flat view once the spectator has made it home.
+    let target = if spectator.active || spectator.returning {
+        1.0
+    } else {
+        0.0
+    };
+    let step = delta / BLEND_DURATION;
+    spectator.blend = if target > spectator.blend {
+        (spectator.blend + step).min(target)
+    } else {
+        (spectator.blend - step).max(target)
+    };
+}
+
+/// Hides and locks the cursor while it's being used to look around.
+fn grab_cursor(
+    spectator: Res<Spectator>,
+    state: Res<State<AppState>>,
+    mut windows: ResMut<Windows>,
+    mut grabbed: Local<bool>,
+) {
+    let grab = spectator.flying() && *state.current() == AppState::Running;
+    if grab == *grabbed {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_cursor_lock_mode(grab);
+        window.set_cursor_visibility(!grab);
+        *grabbed = grab;
+    }
+}
+
+/// Projects everything drawn with a star material as seen from the spectator.
+fn apply_spectator_view(
+    spectator: Res<Spectator>,
+    mut query: Query<&mut GlobalTransform, With<Handle<StarMaterial>>>,
+) {
+    if !spectator.in_view() {
+        return;
+    }
+
+    let eye = Vec3::new(0.0, 0.0, -HOME_DISTANCE) + spectator.pose.offset;
+    let inverse_rotation = spectator.pose.rotation().inverse();
+
+    for mut global_transform in query.iter_mut() {
+        let mut transform = global_transform.compute_transform();
+        let depth = z_to_depth(transform.translation.z);
+        let point = transform.translation.truncate().extend(depth * DEPTH_SCALE);
+        let view = inverse_rotation * (point - eye);
+
+        // Behind the spectator, or so close it would cover the screen.
+        if view.z < NEAR_PLANE {
+            transform.scale = Vec3::ZERO;
+            *global_transform = transform.into();
+            continue;
+        }
+
+        let perspective = HOME_DISTANCE / view.z;
+        let projected = view.truncate() * perspective;
+        let blend = spectator.blend;
+
+        let flat = transform.translation.truncate();
+        let nearness = depth_to_z((view.z - HOME_DISTANCE) / DEPTH_SCALE);
+        transform.translation = flat.lerp(projected, blend).extend(nearness);
+        transform.scale *= 1.0 + (perspective - 1.0) * blend;
+        *global_transform = transform.into();
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Colors and sizes stars by spectral class, from hot blue O stars down to cool red M dwarfs.
+//!
+//! While it's on, a star's class decides its color instead of the theme's tint range.
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::StarfieldSettings;
+use crate::sim::StarfieldRng;
+use crate::Star;
+
+pub struct SpectralPlugin;
+impl Plugin for SpectralPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SpectralSettings>()
+            .register_type::<SpectralClass>()
+            .add_system(reassign_spectral_classes)
+            .add_system(assign_spectral_classes.after(reassign_spectral_classes));
+    }
+}
+
+/// How common each class is, relative to the others.
+///
+/// The defaults lean towards what can be seen with the naked eye rather than what's actually out there,
+/// otherwise faint red dwarfs would make up three in every four stars.
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpectralSettings {
+    pub enabled: bool,
+    pub o: f32,
+    pub b: f32,
+    pub a: f32,
+    pub f: f32,
+    pub g: f32,
+    pub k: f32,
+    pub m: f32,
+}
+
+impl Default for SpectralSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            o: 0.5,
+            b: 10.0,
+            a: 20.0,
+            f: 15.0,
+            g: 15.0,
+            k: 25.0,
+            m: 15.0,
+        }
+    }
+}
+
+impl SpectralSettings {
+    fn weight(&self, class: SpectralClass) -> f32 {
+        let weight = match class {
+            SpectralClass::O => self.o,
+            SpectralClass::B => self.b,
+            SpectralClass::A => self.a,
+            SpectralClass::F => self.f,
+            SpectralClass::G => self.g,
+            SpectralClass::K => self.k,
+            SpectralClass::M => self.m,
+        };
+        weight.max(0.0)
+    }
+
+    /// Picks a class at random, weighted by how common each one is.
+    fn sample(&self, rng: &mut impl Rng) -> SpectralClass {
+        let total: f32 = SpectralClass::ALL
+            .iter()
+            .map(|class| self.weight(*class))
+            .sum();
+        if total <= 0.0 {
+            return SpectralClass::G;
+        }
+
+        let mut pick = rng.gen_range(0.0..total);
+        for class in SpectralClass::ALL {
+            pick -= self.weight(class);
+            if pick < 0.0 {
+                return class;
+            }
+        }
+        SpectralClass::M
+    }
+}
+
+/// A star's spectral class, hottest first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Component)]
+#[reflect_value(Component, PartialEq)]
+pub enum SpectralClass {
+    O,
+    B,
+    A,
+    F,
+    #[default]
+    G,
+    K,
+    M,
+}
+
+impl SpectralClass {
+    pub const ALL: [SpectralClass; 7] = [
+        SpectralClass::O,
+        SpectralClass::B,
+        SpectralClass::A,
+        SpectralClass::F,
+        SpectralClass::G,
+        SpectralClass::K,
+        SpectralClass::M,
+    ];
+
+    /// Roughly the color of a blackbody at the class's temperature.
+    pub fn color(self) -> Color {
+        match self {
+            SpectralClass::O => Color::rgb(0.61, 0.69, 1.0),
+            SpectralClass::B => Color::rgb(0.67, 0.75, 1.0),
+            SpectralClass::A => Color::rgb(0.79, 0.84, 1.0),
+            SpectralClass::F => Color::rgb(0.97, 0.96, 1.0),
+            SpectralClass::G => Color::rgb(1.0, 0.96, 0.92),
+            SpectralClass::K => Color::rgb(1.0, 0.82, 0.63),
+            SpectralClass::M => Color::rgb(1.0, 0.73, 0.44),
+        }
+    }
+
+    /// How big stars of the class are drawn compared to a sun-like G star.
+    pub fn size(self) -> f32 {
+        match self {
+            SpectralClass::O => 1.8,
+            SpectralClass::B => 1.5,
+            SpectralClass::A => 1.25,
+            SpectralClass::F => 1.1,
+            SpectralClass::G => 1.0,
+            SpectralClass::K => 0.9,
+            SpectralClass::M => 0.75,
+        }
+    }
+}
+
+/// Gives every star without a class one.
+fn assign_spectral_classes(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    mut rng: ResMut<StarfieldRng>,
+    query: Query<Entity, (With<Star>, Without<SpectralClass>)>,
+) {
+    for entity in query.iter() {
+        commands
+            .entity(entity)
+            .insert(settings.spectral.sample(&mut rng.effects));
+    }
+}
+
+/// Draws every star's class again when the weights change.
+fn reassign_spectral_classes(
+    settings: Res<StarfieldSettings>,
+    mut rng: ResMut<StarfieldRng>,
+    mut query: Query<&mut SpectralClass>,
+    mut applied: Local<Option<SpectralSettings>>,
+) {
+    // Only the weights count, turning classes on and off keeps the ones stars already have.
+    let spectral = SpectralSettings {
+        enabled: false,
+        ..settings.spectral
+    };
+    let previous = applied.replace(spectral);
+    if previous.is_none() || previous == Some(spectral) {
+        return;
+    }
+
+    for mut class in query.iter_mut() {
+        *class = spectral.sample(&mut rng.effects);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn samples_follow_the_weights() {
+        let settings = SpectralSettings {
+            o: 0.0,
+            b: 0.0,
+            a: 1.0,
+            f: 0.0,
+            g: 0.0,
+            k: 3.0,
+            m: -5.0,
+            ..default()
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let samples: Vec<_> = (0..4000).map(|_| settings.sample(&mut rng)).collect();
+
+        assert!(samples
+            .iter()
+            .all(|class| matches!(class, SpectralClass::A | SpectralClass::K)));
+        let k = samples
+            .iter()
+            .filter(|class| **class == SpectralClass::K)
+            .count();
+        assert!((2800..3200).contains(&k), "{k} of 4000 were K");
+    }
+
+    #[test]
+    fn no_weights_gives_sun_like_stars() {
+        let settings = SpectralSettings {
+            o: 0.0,
+            b: 0.0,
+            a: 0.0,
+            f: 0.0,
+            g: 0.0,
+            k: 0.0,
+            m: 0.0,
+            ..default()
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(settings.sample(&mut rng), SpectralClass::G);
+    }
+}
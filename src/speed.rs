@@ -0,0 +1,194 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! The global speed the field moves at, eased towards its target so changes never happen in a single step.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::StarfieldSettings;
+use crate::state::AppState;
+
+/// How much faster or slower each speed up or slow down action makes the field.
+pub const SPEED_STEP: f32 = 1.5;
+
+/// The slowest and fastest the speed multiplier can be set to.
+pub const SPEED_LIMITS: (f32, f32) = (0.1, 10.0);
+
+/// Speed multipliers the slow motion and fast forward actions jump straight to.
+pub const SLOW_MOTION: f32 = 0.1;
+pub const FAST_FORWARD: f32 = 4.0;
+
+pub struct SpeedPlugin;
+impl Plugin for SpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SpeedSettings>()
+            .register_type::<Easing>()
+            .register_type::<SimulationSpeed>()
+            .init_resource::<SimulationSpeed>()
+            .add_system_to_stage(CoreStage::PreUpdate, ease_speed);
+    }
+}
+
+/// Curves the speed can follow on its way to a new target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum Easing {
+    /// Speeds up and slows down gently at both ends.
+    #[default]
+    Cubic,
+    /// Most of the change happens right away, then settles in slowly.
+    Expo,
+    /// Overshoots the target a little and bounces back, like it's on a spring.
+    Spring,
+}
+
+impl Easing {
+    /// Maps how far along a transition is to how far the speed should have moved, both 0.0 to 1.0.
+    pub fn ease(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        if t >= 1.0 {
+            return 1.0;
+        }
+
+        match self {
+            Easing::Cubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::Expo => 1.0 - 2f32.powf(-10.0 * t),
+            Easing::Spring => 1.0 - (-6.0 * t).exp() * (1.5 * TAU * t).cos(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeedSettings {
+    /// How fast the field moves compared to normal.
+    pub multiplier: f32,
+    pub easing: Easing,
+    /// Seconds a speed change takes to complete.
+    pub transition: f32,
+}
+
+impl Default for SpeedSettings {
+    fn default() -> Self {
+        Self {
+            multiplier: 1.0,
+            easing: Easing::default(),
+            transition: 0.6,
+        }
+    }
+}
+
+/// The speed multiplier the field is actually moving at right now.
+#[derive(Clone, Reflect)]
+pub struct SimulationSpeed {
+    pub current: f32,
+    /// Extra multiplier on top of the settings while warping or boosting.
+    pub boost: f32,
+    /// Extra multiplier on top of the settings during a hyperspace jump.
+    pub jump: f32,
+    /// Extra multiplier on top of the settings while the warp drive is engaged.
+    pub warp: f32,
+    /// Extra multiplier on top of the settings while recovering from a crash in the dodge game.
+    pub penalty: f32,
+    /// Extra multiplier on top of the settings while a loading screen is showing.
+    pub loading: f32,
+    /// Multiplier applied on top of `current` without easing, for effects that follow a rhythm.
+    pub pulse: f32,
+    /// Multiplier applied on top of `current` without easing, following the music the field is reacting to.
+    pub audio: f32,
+    from: f32,
+    target: f32,
+    elapsed: f32,
+}
+
+impl Default for SimulationSpeed {
+    fn default() -> Self {
+        Self {
+            current: 1.0,
+            boost: 1.0,
+            jump: 1.0,
+            warp: 1.0,
+            penalty: 1.0,
+            loading: 1.0,
+            pulse: 1.0,
+            audio: 1.0,
+            from: 1.0,
+            target: 1.0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+/// Moves the current speed along the easing curve whenever the target changes.
+fn ease_speed(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    state: Res<State<AppState>>,
+    mut speed: ResMut<SimulationSpeed>,
+) {
+    // The field keeps drifting behind the menu, and comes to a stop when paused or taking photos.
+    let target = match state.current() {
+        AppState::Menu | AppState::Running => {
+            settings.speed.multiplier
+                * speed.boost
+                * speed.jump
+                * speed.warp
+                * speed.penalty
+                * speed.loading
+        }
+        AppState::Loading | AppState::Paused | AppState::PhotoMode => 0.0,
+    };
+
+    // A new target restarts the transition from wherever the speed is now.
+    if target != speed.target {
+        speed.from = speed.current;
+        speed.target = target;
+        speed.elapsed = 0.0;
+    }
+
+    let transition = settings.speed.transition;
+    if speed.elapsed >= transition {
+        speed.current = speed.target;
+        return;
+    }
+
+    speed.elapsed += time.delta_seconds();
+    let progress = settings.speed.easing.ease(speed.elapsed / transition);
+    speed.current = eased_speed(speed.from, speed.target, progress);
+}
+
+/// The speed some way through a transition. Easing can overshoot, but never past a standstill, since
+/// springing past a stop would send the stars briefly flying backwards.
+fn eased_speed(from: f32, target: f32, progress: f32) -> f32 {
+    (from + (target - from) * progress).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transition(easing: Easing, from: f32, target: f32) -> impl Iterator<Item = f32> {
+        (0..=100).map(move |step| eased_speed(from, target, easing.ease(step as f32 / 100.0)))
+    }
+
+    #[test]
+    fn stopping_never_goes_backwards() {
+        for easing in [Easing::Cubic, Easing::Expo, Easing::Spring] {
+            assert!(transition(easing, 2.0, 0.0).all(|speed| speed >= 0.0));
+            assert_eq!(transition(easing, 2.0, 0.0).last(), Some(0.0));
+        }
+    }
+
+    #[test]
+    fn spring_still_overshoots_while_moving() {
+        assert!(transition(Easing::Spring, 1.0, 2.0).any(|speed| speed > 2.0));
+    }
+}
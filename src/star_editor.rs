@@ -0,0 +1,183 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! An edit mode for placing stars by hand, so people can compose their own scenes over the moving field.
+
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext, EguiPlugin};
+
+use crate::actions::Action;
+use crate::depth::depth_to_z;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::sim::DEPTH_RANGE;
+use crate::star_mesh::StarMesh;
+use crate::state;
+use crate::MainCamera;
+
+/// How far from a pinned star a right click can be and still remove it.
+const REMOVE_RADIUS: f32 = 12.0;
+
+pub struct StarEditorPlugin;
+impl Plugin for StarEditorPlugin {
+    fn build(&self, app: &mut App) {
+        // The inspector brings its own egui in debug builds.
+        if !app.world.contains_resource::<EguiContext>() {
+            app.add_plugin(EguiPlugin);
+        }
+
+        app.register_type::<PinnedStar>()
+            .init_resource::<StarEditor>()
+            .add_system(toggle_star_editor)
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(state::ui_shown)
+                    .with_system(star_editor_ui.after(toggle_star_editor))
+                    .with_system(place_stars.after(star_editor_ui)),
+            );
+    }
+}
+
+/// A star placed by hand. It isn't a [`crate::Star`], so it never moves or gets reset.
+#[derive(Clone, Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct PinnedStar {
+    pub color: Color,
+}
+
+pub struct StarEditor {
+    pub open: bool,
+    /// Size and color the next placed star gets.
+    size: f32,
+    color: Color,
+}
+
+impl Default for StarEditor {
+    fn default() -> Self {
+        Self {
+            open: false,
+            size: 2.0,
+            color: Color::WHITE,
+        }
+    }
+}
+
+fn toggle_star_editor(mut actions: EventReader<Action>, mut editor: ResMut<StarEditor>) {
+    for action in actions.iter() {
+        if *action == Action::ToggleStarEditor {
+            editor.open = !editor.open;
+        }
+    }
+}
+
+fn star_editor_ui(
+    mut commands: Commands,
+    mut egui_context: ResMut<EguiContext>,
+    mut editor: ResMut<StarEditor>,
+    pinned: Query<Entity, With<PinnedStar>>,
+) {
+    if !editor.open {
+        return;
+    }
+
+    let mut open = editor.open;
+
+    egui::Window::new("Star editor")
+        .open(&mut open)
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label("Left click places a star, right click removes one.");
+
+            ui.add(egui::Slider::new(&mut editor.size, 0.5..=10.0).text("Size"));
+
+            ui.horizontal(|ui| {
+                ui.label("Color");
+                // egui's picker works in linear space, but colors are written in sRGB.
+                let mut rgba = editor.color.as_linear_rgba_f32();
+                if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+                    let [r, g, b, a] = rgba;
+                    editor.color = Color::rgba_linear(r, g, b, a).as_rgba();
+                }
+            });
+
+            if ui.button("Remove all placed stars").clicked() {
+                for entity in pinned.iter() {
+                    commands.entity(entity).despawn();
+                }
+            }
+        });
+
+    editor.open = open;
+}
+
+/// Places and removes pinned stars where the user clicks while the editor is open.
+#[allow(clippy::too_many_arguments)]
+fn place_stars(
+    mut commands: Commands,
+    editor: Res<StarEditor>,
+    star_mesh: Res<StarMesh>,
+    mut egui_context: ResMut<EguiContext>,
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    camera: Query<&GlobalTransform, With<MainCamera>>,
+    pinned: Query<(Entity, &Transform), With<PinnedStar>>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+) {
+    // Clicks on the editor window itself shouldn't drop stars behind it.
+    if !editor.open || egui_context.ctx_mut().wants_pointer_input() {
+        return;
+    }
+
+    let place = buttons.just_pressed(MouseButton::Left);
+    let remove = buttons.just_pressed(MouseButton::Right);
+    if !place && !remove {
+        return;
+    }
+
+    let (window, camera) = match (windows.get_primary(), camera.get_single()) {
+        (Some(window), Ok(camera)) => (window, camera),
+        _ => return,
+    };
+
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    // The main camera always shows one world unit per logical pixel, centered on itself.
+    let window_size = Vec2::new(window.width(), window.height());
+    let position = camera.translation().truncate() + cursor - window_size / 2.0;
+
+    if place {
+        // In front of the whole procedural field.
+        let z = depth_to_z(*DEPTH_RANGE.start()) + 1.0;
+        let transform =
+            Transform::from_translation(position.extend(z)).with_scale(Vec3::splat(editor.size));
+
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: Mesh2dHandle(star_mesh.0.clone()),
+                material: pool.get(editor.color, &mut materials),
+                transform,
+                ..default()
+            })
+            .insert(PinnedStar {
+                color: editor.color,
+            });
+    }
+
+    if remove {
+        let nearest = pinned
+            .iter()
+            .map(|(entity, transform)| {
+                let distance = transform.translation.truncate().distance(position);
+                (entity, distance)
+            })
+            .filter(|(_, distance)| *distance <= REMOVE_RADIUS)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        if let Some((entity, _)) = nearest {
+            commands.entity(entity).despawn();
+        }
+    }
+}
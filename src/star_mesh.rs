@@ -0,0 +1,93 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! The one mesh every star is drawn with, rebuilt in place when its settings change.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::StarfieldSettings;
+
+pub struct StarMeshPlugin;
+impl Plugin for StarMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<StarMeshSettings>()
+            .register_type::<StarShape>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, create_star_mesh)
+            .add_system(apply_star_mesh_settings);
+    }
+}
+
+/// What shape stars are drawn as.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum StarShape {
+    #[default]
+    Circle,
+    /// A plain square, the cheapest option and indistinguishable from a circle for tiny stars.
+    Quad,
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StarMeshSettings {
+    pub shape: StarShape,
+    /// How many vertices go around the edge of a circle.
+    /// A handful is plenty for tiny stars, stars scaled up close to the camera need more.
+    pub circle_vertices: usize,
+}
+
+impl Default for StarMeshSettings {
+    fn default() -> Self {
+        Self {
+            shape: StarShape::default(),
+            circle_vertices: 12,
+        }
+    }
+}
+
+impl StarMeshSettings {
+    /// A mesh with a radius of 1, matching the unit circle stars have always used.
+    fn mesh(self) -> Mesh {
+        match self.shape {
+            StarShape::Circle => shape::Circle {
+                radius: 1.0,
+                vertices: self.circle_vertices.max(3),
+            }
+            .into(),
+            StarShape::Quad => shape::Quad::new(Vec2::splat(2.0)).into(),
+        }
+    }
+}
+
+/// Shared by every star, glow, and afterimage.
+pub struct StarMesh(pub Handle<Mesh>);
+
+fn create_star_mesh(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let mesh = meshes.add(settings.star_mesh.mesh());
+    commands.insert_resource(StarMesh(mesh));
+}
+
+/// Rebuilds the shared mesh when its settings change, every star picks up the new one at once.
+fn apply_star_mesh_settings(
+    settings: Res<StarfieldSettings>,
+    star_mesh: Res<StarMesh>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut applied: Local<Option<StarMeshSettings>>,
+) {
+    if *applied == Some(settings.star_mesh) {
+        return;
+    }
+
+    // The first run just records what the mesh was created with.
+    if applied.is_some() {
+        if let Some(mesh) = meshes.get_mut(&star_mesh.0) {
+            *mesh = settings.star_mesh.mesh();
+        }
+    }
+
+    *applied = Some(settings.star_mesh);
+}
@@ -0,0 +1,88 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! The states the app moves through, so systems can be switched on and off by state instead of each
+//! keeping their own flags.
+
+use bevy::ecs::schedule::ShouldRun;
+use bevy::prelude::*;
+
+use crate::actions::Action;
+
+pub struct StatePlugin {
+    /// Go straight to the field once loaded, without stopping at the menu.
+    pub autostart: bool,
+}
+
+impl Plugin for StatePlugin {
+    fn build(&self, app: &mut App) {
+        let finish_loading = if self.autostart {
+            finish_loading_into_field
+        } else {
+            finish_loading_into_menu
+        };
+
+        app.add_state(AppState::Loading)
+            .add_system_set(SystemSet::on_update(AppState::Loading).with_system(finish_loading))
+            .add_system_to_stage(CoreStage::PreUpdate, change_state);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AppState {
+    /// Startup work is still being done.
+    Loading,
+    /// The field drifts behind the main menu.
+    Menu,
+    /// The field is moving and everything can be used.
+    Running,
+    /// The field is stopped but everything can still be used.
+    Paused,
+    /// The field is stopped and all UI is hidden, so a shot can be lined up.
+    PhotoMode,
+}
+
+/// Run criteria for systems that only make sense while the field is moving.
+///
+/// `State::on_update` only works in the stage the state was added to, this works in any stage.
+pub fn running(state: Res<State<AppState>>) -> ShouldRun {
+    (*state.current() == AppState::Running).into()
+}
+
+/// Run criteria for systems that only make sense once the field is up, moving or not.
+pub fn field_shown(state: Res<State<AppState>>) -> ShouldRun {
+    match state.current() {
+        AppState::Running | AppState::Paused | AppState::PhotoMode => ShouldRun::Yes,
+        AppState::Loading | AppState::Menu => ShouldRun::No,
+    }
+}
+
+/// Run criteria for UI, which gets out of the way in photo mode.
+pub fn ui_shown(state: Res<State<AppState>>) -> ShouldRun {
+    match state.current() {
+        AppState::Running | AppState::Paused => ShouldRun::Yes,
+        AppState::Loading | AppState::Menu | AppState::PhotoMode => ShouldRun::No,
+    }
+}
+
+/// Everything is set up during startup, so loading is done by the first update.
+fn finish_loading_into_menu(mut state: ResMut<State<AppState>>) {
+    let _ = state.set(AppState::Menu);
+}
+
+fn finish_loading_into_field(mut state: ResMut<State<AppState>>) {
+    let _ = state.set(AppState::Running);
+}
+
+fn change_state(mut actions: EventReader<Action>, mut state: ResMut<State<AppState>>) {
+    for action in actions.iter() {
+        let next = match (action, state.current()) {
+            (Action::TogglePause, AppState::Running) => AppState::Paused,
+            (Action::TogglePause, AppState::Paused | AppState::PhotoMode) => AppState::Running,
+            (Action::TogglePhotoMode, AppState::Running | AppState::Paused) => AppState::PhotoMode,
+            (Action::TogglePhotoMode, AppState::PhotoMode) => AppState::Running,
+            _ => continue,
+        };
+
+        let _ = state.set(next);
+    }
+}
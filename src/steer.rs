@@ -0,0 +1,154 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Steering where the field flies towards, by moving the point stars stream out from.
+//!
+//! While steering is on the vanishing point follows the mouse, or is nudged around with the fly actions, and
+//! eases back to the middle once it's turned off. The scroll wheel changes the speed whether steering or not.
+
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::window::CursorMoved;
+use bevy_inspector_egui::bevy_egui::EguiContext;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::bindings::{ActionInput, BindingsSystem};
+use crate::dodge::Dodge;
+use crate::settings::StarfieldSettings;
+use crate::sim::Field;
+use crate::spectator::Spectator;
+use crate::speed::{SPEED_LIMITS, SPEED_STEP};
+use crate::state;
+
+/// Pixels of scrolling that count as one notch of a mouse wheel, for touchpads that scroll smoothly.
+const PIXELS_PER_NOTCH: f32 = 100.0;
+
+pub struct SteerPlugin;
+impl Plugin for SteerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SteerSettings>()
+            .init_resource::<Steering>()
+            .add_system(toggle_steering)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                steer
+                    .with_run_criteria(state::running)
+                    .after(BindingsSystem),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                scroll_speed.with_run_criteria(state::running),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SteerSettings {
+    pub enabled: bool,
+    /// How far the vanishing point can get from the middle, as a fraction of the way to the window's edge.
+    pub reach: f32,
+    /// How far the fly actions move the vanishing point each second, 1.0 being from the middle to the edge of its reach.
+    pub key_speed: f32,
+    /// How quickly the vanishing point catches up with where it's steered, higher is snappier.
+    pub follow: f32,
+}
+
+impl Default for SteerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reach: 0.8,
+            key_speed: 1.0,
+            follow: 3.0,
+        }
+    }
+}
+
+/// Where the field is being steered towards, -1.0 to 1.0 on each axis from the middle of the window.
+#[derive(Default)]
+struct Steering {
+    target: Vec2,
+    current: Vec2,
+}
+
+fn toggle_steering(mut actions: EventReader<Action>, mut settings: ResMut<StarfieldSettings>) {
+    for action in actions.iter() {
+        if *action == Action::ToggleSteering {
+            settings.steer.enabled = !settings.steer.enabled;
+        }
+    }
+}
+
+/// Moves the vanishing point towards the mouse or along the fly actions, and hands it to the simulation.
+#[allow(clippy::too_many_arguments)]
+fn steer(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    input: Res<ActionInput>,
+    spectator: Res<Spectator>,
+    dodge: Res<Dodge>,
+    windows: Res<Windows>,
+    mut cursor: EventReader<CursorMoved>,
+    mut steering: ResMut<Steering>,
+    mut field: ResMut<Field>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let half_size = Vec2::new(window.width(), window.height()) / 2.0;
+    let steer_settings = settings.steer;
+
+    if steer_settings.enabled {
+        if let Some(moved) = cursor.iter().last() {
+            steering.target = (moved.position / half_size - 1.0).clamp(Vec2::NEG_ONE, Vec2::ONE);
+        }
+
+        // The spectator and the dodge game use the same actions to get around.
+        if !spectator.active() && !dodge.active() {
+            let axis = |positive: Action, negative: Action| {
+                input.pressed(positive) as i32 as f32 - input.pressed(negative) as i32 as f32
+            };
+            let direction = Vec2::new(
+                axis(Action::FlyRight, Action::FlyLeft),
+                axis(Action::Warp, Action::FlyBack),
+            );
+            let nudge =
+                direction.normalize_or_zero() * steer_settings.key_speed * time.delta_seconds();
+            steering.target = (steering.target + nudge).clamp(Vec2::NEG_ONE, Vec2::ONE);
+        }
+    } else {
+        cursor.clear();
+        steering.target = Vec2::ZERO;
+    }
+
+    let follow = (steer_settings.follow * time.delta_seconds()).min(1.0);
+    steering.current = steering.current.lerp(steering.target, follow);
+
+    field.set_vanishing_point(steering.current * half_size * steer_settings.reach);
+}
+
+/// Speeds the field up or slows it down a step for each notch the scroll wheel turns.
+fn scroll_speed(
+    mut scrolls: EventReader<MouseWheel>,
+    mut egui_context: ResMut<EguiContext>,
+    mut settings: ResMut<StarfieldSettings>,
+) {
+    let notches: f32 = scrolls
+        .iter()
+        .map(|scroll| match scroll.unit {
+            MouseScrollUnit::Line => scroll.y,
+            MouseScrollUnit::Pixel => scroll.y / PIXELS_PER_NOTCH,
+        })
+        .sum();
+
+    // Scrolling over a window scrolls the window.
+    if notches == 0.0 || egui_context.ctx_mut().wants_pointer_input() {
+        return;
+    }
+
+    let (min, max) = SPEED_LIMITS;
+    settings.speed.multiplier =
+        (settings.speed.multiplier * SPEED_STEP.powf(notches)).clamp(min, max);
+}
@@ -0,0 +1,416 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Keeps separate machines showing one continuous field, for video walls made of several PCs.
+//!
+//! One instance leads, broadcasting its settings and where every star is over UDP, a batch of stars to a
+//! packet. Followers take both on and keep simulating between updates so motion stays smooth. Every screen then shifts its camera by its
+//! own offset, so each one shows its own part of the same field. Rather than trying to keep separate
+//! simulations in lockstep, followers are simply corrected with the leader's stars many times a second.
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::UdpSocket;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::depth::depth_to_z;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::settings::StarfieldSettings;
+use crate::shake::CameraHome;
+use crate::share;
+use crate::theme::Theme;
+use crate::Star;
+
+/// Every packet starts with this, so stray traffic on the port is ignored.
+const MAGIC: &[u8; 4] = b"SFSY";
+
+/// Follows the magic, and is bumped whenever packets change so mismatched builds don't misread each other.
+const PROTOCOL_VERSION: u8 = 1;
+
+const SETTINGS_PACKET: u8 = 0;
+const STARS_PACKET: u8 = 1;
+
+/// Floats sent for each star: x, y, depth, tint, mass, base speed and scale.
+const STAR_FLOATS: usize = 7;
+
+/// Bytes each star takes up in a stars packet.
+const STAR_BYTES: usize = STAR_FLOATS * 4;
+
+/// Stars sent in each stars packet, which keeps every packet well inside the largest a datagram can be.
+const STARS_PER_PACKET: usize = 1024;
+
+/// Bytes at the start of a stars packet's body: how many stars the leader has, then the first one in it.
+const STARS_HEADER: usize = 8;
+
+/// Seconds between star updates from the leader.
+const STARS_INTERVAL: f32 = 0.05;
+
+/// Seconds between settings updates from the leader when nothing has changed, so late followers catch up.
+const SETTINGS_INTERVAL: f32 = 1.0;
+
+/// Largest packet a follower accepts, the most a UDP datagram can carry.
+const MAX_PACKET: usize = 65_507;
+
+pub struct SyncPlugin;
+impl Plugin for SyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SyncSettings>()
+            .register_type::<SyncRole>()
+            .add_startup_system(open_socket)
+            .add_system(place_screen)
+            .add_system(broadcast)
+            .add_system_to_stage(CoreStage::PreUpdate, follow);
+    }
+}
+
+/// What part this instance plays in a synchronized wall.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum SyncRole {
+    /// Runs on its own.
+    #[default]
+    Off,
+    /// Sends its field to every follower.
+    Leader,
+    /// Shows whatever the leader sends.
+    Follower,
+}
+
+#[derive(Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncSettings {
+    /// Changes to the role, address and port take effect on the next start.
+    pub role: SyncRole,
+    /// Where the leader sends updates, usually the broadcast address of the local network.
+    pub address: String,
+    pub port: u16,
+    /// How far this screen's middle is from the middle of the whole wall, in pixels.
+    pub offset: Vec2,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self {
+            role: SyncRole::Off,
+            address: "255.255.255.255".to_string(),
+            port: 7879,
+            offset: Vec2::ZERO,
+        }
+    }
+}
+
+struct SyncSocket {
+    socket: UdpSocket,
+    since_stars: f32,
+    since_settings: f32,
+    /// The share code a follower last applied, so repeats of it are skipped.
+    last_code: String,
+    /// Whether a follower has already warned about packets from a leader running another version.
+    warned_version: bool,
+}
+
+/// A packet from the leader, as read by a follower.
+#[derive(Debug, PartialEq)]
+enum Packet<'a> {
+    /// The leader's settings, as a share code.
+    Settings(&'a [u8]),
+    /// A batch of stars: how many the leader has, the index of the first one here, and their values.
+    Stars {
+        total: usize,
+        first: usize,
+        stars: &'a [u8],
+    },
+    /// From a leader whose packets are laid out differently.
+    OtherVersion(u8),
+}
+
+/// Reads a packet, or gives `None` for anything that isn't one.
+fn read_packet(bytes: &[u8]) -> Option<Packet<'_>> {
+    let read_u32 =
+        |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+
+    match bytes.strip_prefix(MAGIC.as_slice())? {
+        [PROTOCOL_VERSION, SETTINGS_PACKET, body @ ..] => Some(Packet::Settings(body)),
+        [PROTOCOL_VERSION, STARS_PACKET, body @ ..] if body.len() >= STARS_HEADER => {
+            let (header, stars) = body.split_at(STARS_HEADER);
+            Some(Packet::Stars {
+                total: read_u32(&header[..4]),
+                first: read_u32(&header[4..]),
+                stars,
+            })
+        }
+        [PROTOCOL_VERSION, ..] => None,
+        [version, ..] => Some(Packet::OtherVersion(*version)),
+        [] => None,
+    }
+}
+
+fn open_socket(mut commands: Commands, settings: Res<StarfieldSettings>) {
+    let sync = &settings.sync;
+    let socket = match sync.role {
+        SyncRole::Off => return,
+        SyncRole::Leader => UdpSocket::bind(("0.0.0.0", 0))
+            .and_then(|socket| socket.set_broadcast(true).map(|_| socket)),
+        SyncRole::Follower => UdpSocket::bind(("0.0.0.0", sync.port))
+            .and_then(|socket| socket.set_nonblocking(true).map(|_| socket)),
+    };
+
+    match socket {
+        Ok(socket) => {
+            info!("Synchronizing as {:?} on port {}", sync.role, sync.port);
+            commands.insert_resource(SyncSocket {
+                socket,
+                since_stars: 0.0,
+                since_settings: 0.0,
+                last_code: String::new(),
+                warned_version: false,
+            });
+        }
+        Err(error) => warn!("Couldn't open port {} to synchronize, {error}", sync.port),
+    }
+}
+
+fn place_screen(settings: Res<StarfieldSettings>, mut home: ResMut<CameraHome>) {
+    if settings.is_changed() && home.0 != settings.sync.offset {
+        home.0 = settings.sync.offset;
+    }
+}
+
+/// Sends the leader's settings and stars out to every follower.
+fn broadcast(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+    socket: Option<ResMut<SyncSocket>>,
+    stars: Query<(&Star, &Transform)>,
+) {
+    let mut socket = match socket {
+        Some(socket) if settings.sync.role == SyncRole::Leader => socket,
+        _ => return,
+    };
+
+    let target = (settings.sync.address.as_str(), settings.sync.port);
+    let send = |socket: &UdpSocket, packet: &[u8]| {
+        if let Err(error) = socket.send_to(packet, target) {
+            warn!("Couldn't send a sync update, {error}");
+        }
+    };
+
+    socket.since_settings += time.delta_seconds();
+    if settings.is_changed() || theme.is_changed() || socket.since_settings >= SETTINGS_INTERVAL {
+        let code = share::encode(&settings, &theme);
+        send(&socket.socket, &packet(SETTINGS_PACKET, code.as_bytes()));
+        socket.since_settings = 0.0;
+    }
+
+    socket.since_stars += time.delta_seconds();
+    if socket.since_stars >= STARS_INTERVAL {
+        let floats: Vec<u8> = stars
+            .iter()
+            .flat_map(|(star, transform)| {
+                // Where the simulation has the star, rather than where it's drawn between physics steps.
+                let position = star.position(transform);
+                let values: [f32; STAR_FLOATS] = [
+                    position.x,
+                    position.y,
+                    star.depth,
+                    star.tint,
+                    star.mass,
+                    star.base_speed,
+                    transform.scale.x,
+                ];
+                values.into_iter().flat_map(f32::to_le_bytes)
+            })
+            .collect();
+
+        let total = (floats.len() / STAR_BYTES) as u32;
+        for (index, chunk) in floats.chunks(STARS_PER_PACKET * STAR_BYTES).enumerate() {
+            let first = (index * STARS_PER_PACKET) as u32;
+            let mut body = Vec::with_capacity(STARS_HEADER + chunk.len());
+            body.extend_from_slice(&total.to_le_bytes());
+            body.extend_from_slice(&first.to_le_bytes());
+            body.extend_from_slice(chunk);
+            send(&socket.socket, &packet(STARS_PACKET, &body));
+        }
+        socket.since_stars = 0.0;
+    }
+}
+
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = MAGIC.to_vec();
+    packet.push(PROTOCOL_VERSION);
+    packet.push(kind);
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// Takes on the latest settings and stars from the leader.
+#[allow(clippy::too_many_arguments)]
+fn follow(
+    mut settings: ResMut<StarfieldSettings>,
+    mut theme: ResMut<Theme>,
+    socket: Option<ResMut<SyncSocket>>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    mut stars: Query<(&mut Star, &mut Transform, &mut Handle<StarMaterial>)>,
+) {
+    let mut socket = match socket {
+        Some(socket) if settings.sync.role == SyncRole::Follower => socket,
+        _ => return,
+    };
+
+    // Only the newest of each kind of packet matters, anything older is already out of date.
+    let mut buffer = vec![0; MAX_PACKET];
+    let mut latest_code = None;
+    let mut leader_total = None;
+    let mut latest_stars = HashMap::new();
+    loop {
+        match socket.socket.recv(&mut buffer) {
+            Ok(size) => match read_packet(&buffer[..size]) {
+                Some(Packet::Settings(body)) => {
+                    latest_code = Some(String::from_utf8_lossy(body).into_owned());
+                }
+                Some(Packet::Stars {
+                    total,
+                    first,
+                    stars,
+                }) => {
+                    leader_total = Some(total);
+                    latest_stars.insert(first, stars.to_vec());
+                }
+                Some(Packet::OtherVersion(version)) if !socket.warned_version => {
+                    warn!("Ignoring a sync leader using version {version} of the protocol, this is version {PROTOCOL_VERSION}");
+                    socket.warned_version = true;
+                }
+                _ => {}
+            },
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            Err(error) => {
+                warn!("Couldn't receive a sync update, {error}");
+                break;
+            }
+        }
+    }
+
+    if let Some(code) = latest_code.filter(|code| *code != socket.last_code) {
+        match share::decode(&code) {
+            Ok((mut shared, shared_theme)) => {
+                // Anything about this machine rather than the field stays as it is.
+                share::keep_local(&mut shared, &settings);
+
+                if shared != *settings {
+                    *settings = shared;
+                }
+                if shared_theme != *theme {
+                    *theme = shared_theme;
+                }
+            }
+            Err(error) => warn!("Ignoring settings from the leader, {error}"),
+        }
+        socket.last_code = code;
+    }
+
+    let leader_total = match leader_total {
+        Some(total) => total,
+        None => return,
+    };
+
+    // Which of our stars gets which of the leader's doesn't matter, they all end up drawn in the same places.
+    for (index, (mut star, mut transform, mut material)) in stars.iter_mut().enumerate() {
+        if index >= leader_total {
+            // The leader has fewer stars, so hide the rest until they next respawn.
+            transform.scale = Vec3::ZERO;
+            continue;
+        }
+
+        // Stars whose packet didn't arrive this time carry on as they were.
+        let first = index / STARS_PER_PACKET * STARS_PER_PACKET;
+        let offset = (index - first) * STAR_BYTES;
+        let values = match latest_stars
+            .get(&first)
+            .and_then(|body| body.get(offset..offset + STAR_BYTES))
+        {
+            Some(bytes) => read_star(bytes),
+            None => continue,
+        };
+
+        // In the order the leader wrote them.
+        let (depth, tint, scale) = (values[2], values[3], values[6]);
+        star.place(
+            &mut transform,
+            Vec3::new(values[0], values[1], depth_to_z(depth)),
+        );
+        transform.scale = Vec3::new(scale, scale, 1.0);
+        star.depth = depth;
+        star.mass = values[4];
+        star.base_speed = values[5];
+
+        if star.tint != tint {
+            star.tint = tint;
+            *material = pool.get(theme.star_color(tint), &mut materials);
+        }
+    }
+}
+
+/// One star's values from a stars packet.
+fn read_star(bytes: &[u8]) -> [f32; STAR_FLOATS] {
+    let mut values = [0.0; STAR_FLOATS];
+    for (value, bytes) in values.iter_mut().zip(bytes.chunks_exact(4)) {
+        *value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_settings_packets() {
+        let sent = packet(SETTINGS_PACKET, b"code");
+        assert_eq!(read_packet(&sent), Some(Packet::Settings(b"code")));
+    }
+
+    #[test]
+    fn reads_stars_packets() {
+        let mut body = Vec::new();
+        body.extend_from_slice(&3000_u32.to_le_bytes());
+        body.extend_from_slice(&1024_u32.to_le_bytes());
+        let values: [f32; STAR_FLOATS] = [1.0, 2.0, 0.5, 0.25, 1.0, 40.0, 1.5];
+        body.extend(values.into_iter().flat_map(f32::to_le_bytes));
+
+        let sent = packet(STARS_PACKET, &body);
+        match read_packet(&sent) {
+            Some(Packet::Stars {
+                total,
+                first,
+                stars,
+            }) => {
+                assert_eq!((total, first), (3000, 1024));
+                assert_eq!(read_star(stars), values);
+            }
+            other => panic!("expected stars, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_other_traffic() {
+        assert_eq!(read_packet(b""), None);
+        assert_eq!(read_packet(b"HTTP/1.1 200 OK"), None);
+        assert_eq!(read_packet(MAGIC), None);
+        // Too short to hold the stars header.
+        assert_eq!(read_packet(&packet(STARS_PACKET, &[0; 4])), None);
+        assert_eq!(read_packet(&packet(7, b"")), None);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut sent = packet(SETTINGS_PACKET, b"code");
+        sent[MAGIC.len()] = PROTOCOL_VERSION + 1;
+        assert_eq!(
+            read_packet(&sent),
+            Some(Packet::OtherVersion(PROTOCOL_VERSION + 1))
+        );
+    }
+}
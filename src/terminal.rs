@@ -0,0 +1,201 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Draws the starfield as characters in a terminal, for SSH sessions and TTYs without a GPU.
+//!
+//! The field takes over the whole terminal on its alternate screen until Ctrl-C, Escape or Q is pressed,
+//! and fills the terminal again whenever it's resized.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use glam::Vec2;
+use rand::Rng;
+
+use crate::sim::{Field, StarfieldRng};
+
+/// Roughly 30 frames per second is plenty for text.
+const FRAME_TIME: Duration = Duration::from_millis(33);
+
+/// Characters from dimmest to brightest.
+const BRIGHTNESS_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// How many terminal cells there are per star. Text is far coarser than pixels, so we need a lot fewer stars.
+const CELLS_PER_STAR: usize = 12;
+
+/// Used when the terminal doesn't tell us how big it is.
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+
+struct TerminalStar {
+    position: Vec2,
+    base_speed: f32,
+    depth: f32,
+}
+
+impl TerminalStar {
+    fn new(field: &Field, rng: &mut impl Rng) -> Self {
+        Self {
+            position: field.spawn_position(rng),
+            base_speed: field.random_base_speed(rng),
+            depth: field.random_depth(rng),
+        }
+    }
+
+    fn update(&mut self, field: &Field, delta_seconds: f32, rng: &mut impl Rng) {
+        let velocity = field.velocity(self.position, self.base_speed, delta_seconds);
+        self.position += velocity * delta_seconds;
+
+        if field.outside_extent(self.position) {
+            self.position = field.respawn_position(rng);
+            self.base_speed = field.random_base_speed(rng);
+            self.depth = field.random_depth(rng);
+        }
+    }
+
+    /// Near stars are bright, and stars get brighter as they speed up towards the edges.
+    fn brightness(&self, field: &Field) -> f32 {
+        let distance = (self.position.length() / field.extent()).min(1.0);
+        (1.0 - self.depth) * (0.5 + 0.5 * distance)
+    }
+}
+
+/// Puts the terminal back how it was found when dropped, even if the field panics.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let raw = RawTerminal;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(raw)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        // Nothing more can be done if the terminal can't be restored.
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Runs the terminal front-end until it's quit or stdout goes away.
+pub fn run(mut field: Field, seed: u64) {
+    let mut rng = StarfieldRng::new(seed);
+    field.reseed(seed);
+
+    let _raw = match RawTerminal::enter() {
+        Ok(raw) => raw,
+        Err(error) => {
+            eprintln!("Couldn't take over the terminal, {error}");
+            return;
+        }
+    };
+
+    let mut stdout = io::stdout().lock();
+    let mut size = terminal_size();
+    let mut stars = Vec::new();
+    let mut frame = Vec::new();
+    let mut last_frame = Instant::now();
+
+    loop {
+        let (columns, rows) = (size.0 as usize, size.1 as usize);
+        // Bigger terminals get more stars, and smaller ones lose some.
+        let star_count = (columns * rows / CELLS_PER_STAR).max(1);
+        stars.truncate(star_count);
+        while stars.len() < star_count {
+            stars.push(TerminalStar::new(&field, &mut rng.field));
+        }
+
+        let frame_start = Instant::now();
+        let delta_seconds = (frame_start - last_frame).as_secs_f32();
+        last_frame = frame_start;
+
+        stars
+            .iter_mut()
+            .for_each(|star| star.update(&field, delta_seconds, &mut rng.field));
+        draw(&stars, &field, columns, rows, &mut frame);
+
+        let written = queue!(stdout, MoveTo(0, 0))
+            .and_then(|_| stdout.write_all(&frame))
+            .and_then(|_| stdout.flush());
+        if written.is_err() {
+            return;
+        }
+
+        // Waiting for input doubles as waiting for the next frame.
+        let mut timeout = FRAME_TIME.saturating_sub(frame_start.elapsed());
+        while let Ok(true) = event::poll(timeout) {
+            match event::read() {
+                Ok(Event::Key(key)) if quits(key) => return,
+                Ok(Event::Resize(columns, rows)) => {
+                    size = (columns.max(1), rows.max(1));
+                    if queue!(stdout, Clear(ClearType::All)).is_err() {
+                        return;
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => return,
+            }
+            timeout = FRAME_TIME.saturating_sub(frame_start.elapsed());
+        }
+    }
+}
+
+/// Raw mode stops Ctrl-C from interrupting the process, so it has to be picked up as a key press.
+fn quits(key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+        KeyCode::Char('q') | KeyCode::Esc => true,
+        _ => false,
+    }
+}
+
+/// Renders the stars into a buffer of text, one line per row.
+fn draw(stars: &[TerminalStar], field: &Field, columns: usize, rows: usize, frame: &mut Vec<u8>) {
+    let mut cells: Vec<Option<f32>> = vec![None; columns * rows];
+
+    let extents = field.extents();
+    for star in stars {
+        // Map the extent onto the terminal, keeping the brightest star when several land in one cell.
+        let normalized = (star.position / extents + 1.0) / 2.0;
+        let column = (normalized.x * columns as f32) as usize;
+        let row = ((1.0 - normalized.y) * rows as f32) as usize;
+        if column >= columns || row >= rows {
+            continue;
+        }
+
+        let cell = &mut cells[row * columns + column];
+        let brightness = star.brightness(field);
+        *cell = Some(cell.map_or(brightness, |current| current.max(brightness)));
+    }
+
+    frame.clear();
+    for (index, brightness) in cells.iter().enumerate() {
+        if index > 0 && index % columns == 0 {
+            frame.extend_from_slice(b"\r\n");
+        }
+
+        // Anything that has a star in it gets at least the dimmest visible character, even the farthest star.
+        let character = match brightness {
+            Some(brightness) => {
+                let last = BRIGHTNESS_RAMP.len() - 1;
+                let step = (brightness.clamp(0.0, 1.0) * (last - 1) as f32).round() as usize;
+                BRIGHTNESS_RAMP[1 + step]
+            }
+            None => b' ',
+        };
+        frame.push(character);
+    }
+}
+
+/// How many columns and rows the terminal has.
+fn terminal_size() -> (u16, u16) {
+    match terminal::size() {
+        Ok((columns, rows)) if columns > 0 && rows > 0 => (columns, rows),
+        _ => DEFAULT_SIZE,
+    }
+}
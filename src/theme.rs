@@ -0,0 +1,316 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! The colors the field is drawn with, a handful of built in presets, and any custom ones the user made.
+//!
+//! Custom themes live in `themes.ron` in the config directory, as a list of `(name, theme)` entries. The
+//! theme editor saves to it, and it can be edited by hand too.
+
+use std::fs;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::persist::config_path;
+use crate::Star;
+
+const FILE_NAME: &str = "themes.ron";
+
+pub struct ThemePlugin;
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Theme>()
+            .init_resource::<Theme>()
+            .insert_resource(CustomThemes::load())
+            .add_system(cycle_theme)
+            .add_system_to_stage(CoreStage::PreUpdate, apply_theme)
+            .add_system(match_menus_to_theme);
+    }
+}
+
+/// The set of colors the starfield is drawn with.
+#[derive(Clone, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub clear_color: Color,
+    /// Stars are tinted somewhere between `star_color` and `star_tint`, each star picks its own spot in that range.
+    pub star_color: Color,
+    pub star_tint: Color,
+    /// Opacity of the halo drawn around each star, 0.0 disables it.
+    pub glow: f32,
+    /// Seconds it takes for a star's afterimages to fade out, 0.0 disables them.
+    pub afterimage_decay: f32,
+    /// Color of warp trails, or `None` to draw each trail in the color of its star.
+    pub trail_color: Option<Color>,
+    /// Multiplied into the nebula's colors, white leaves them as they are.
+    pub nebula_tint: Color,
+}
+
+impl Theme {
+    /// White stars on a black sky.
+    pub const CLASSIC: Theme = Theme {
+        clear_color: Color::BLACK,
+        star_color: Color::WHITE,
+        star_tint: Color::WHITE,
+        glow: 0.0,
+        afterimage_decay: 0.0,
+        trail_color: None,
+        nebula_tint: Color::WHITE,
+    };
+
+    /// Dark stars on paper white, for bright rooms and sitting behind documents.
+    pub const LIGHT: Theme = Theme {
+        clear_color: Color::rgb(0.96, 0.95, 0.92),
+        star_color: Color::rgb(0.12, 0.12, 0.16),
+        star_tint: Color::rgb(0.12, 0.12, 0.16),
+        glow: 0.0,
+        afterimage_decay: 0.0,
+        trail_color: None,
+        nebula_tint: Color::WHITE,
+    };
+
+    /// Monochrome green phosphor, like an old terminal display.
+    pub const PHOSPHOR_GREEN: Theme = Theme {
+        clear_color: Color::rgb(0.01, 0.03, 0.01),
+        star_color: Color::rgb(0.2, 1.0, 0.3),
+        star_tint: Color::rgb(0.2, 1.0, 0.3),
+        glow: 0.15,
+        afterimage_decay: 0.8,
+        trail_color: None,
+        nebula_tint: Color::rgb(0.2, 1.0, 0.3),
+    };
+
+    /// Monochrome amber phosphor.
+    pub const PHOSPHOR_AMBER: Theme = Theme {
+        clear_color: Color::rgb(0.03, 0.02, 0.0),
+        star_color: Color::rgb(1.0, 0.7, 0.1),
+        star_tint: Color::rgb(1.0, 0.7, 0.1),
+        glow: 0.15,
+        afterimage_decay: 0.8,
+        trail_color: None,
+        nebula_tint: Color::rgb(1.0, 0.7, 0.1),
+    };
+
+    /// Magenta fading to cyan over a deep purple sky, with hot pink trails.
+    pub const SYNTHWAVE: Theme = Theme {
+        clear_color: Color::rgb(0.06, 0.01, 0.1),
+        star_color: Color::rgb(1.0, 0.3, 0.85),
+        star_tint: Color::rgb(0.2, 0.95, 1.0),
+        glow: 0.2,
+        afterimage_decay: 0.0,
+        trail_color: Some(Color::rgb(1.0, 0.2, 0.6)),
+        nebula_tint: Color::rgb(1.0, 0.4, 1.0),
+    };
+
+    /// The built in themes along with the names they're listed under.
+    pub const PRESETS: [(&'static str, Theme); 5] = [
+        ("Classic", Theme::CLASSIC),
+        ("Light", Theme::LIGHT),
+        ("Green phosphor", Theme::PHOSPHOR_GREEN),
+        ("Amber phosphor", Theme::PHOSPHOR_AMBER),
+        ("Synthwave", Theme::SYNTHWAVE),
+    ];
+
+    /// A dark palette with a random hue and a contrasting tint.
+    pub fn random(rng: &mut impl Rng) -> Theme {
+        let hue = rng.gen_range(0.0..360.0);
+        let tint_hue = (hue + rng.gen_range(60.0..300.0)) % 360.0;
+
+        Theme {
+            clear_color: Color::hsl(hue, 0.6, rng.gen_range(0.02..0.06)),
+            star_color: Color::hsl(hue, 0.3, 0.9),
+            star_tint: Color::hsl(tint_hue, rng.gen_range(0.5..0.9), 0.7),
+            glow: rng.gen_range(0.05..0.25),
+            ..Theme::CLASSIC
+        }
+    }
+
+    /// The color of a star at some point between the two ends of the tint range.
+    pub fn star_color(&self, tint: f32) -> Color {
+        let from = Vec4::from(self.star_color.as_rgba_f32());
+        let to = Vec4::from(self.star_tint.as_rgba_f32());
+        let [r, g, b, a] = from.lerp(to, tint.clamp(0.0, 1.0)).to_array();
+        Color::rgba(r, g, b, a)
+    }
+
+    /// The color of the trail behind a star at some point in the tint range.
+    pub fn trail_color(&self, tint: f32) -> Color {
+        self.trail_color.unwrap_or_else(|| self.star_color(tint))
+    }
+
+    /// Whether the background is bright enough that dark text reads better on top of it.
+    pub fn is_light(&self) -> bool {
+        let [r, g, b, _] = self.clear_color.as_rgba_f32();
+        0.2126 * r + 0.7152 * g + 0.0722 * b > 0.5
+    }
+
+    /// One of the nebula's colors with the theme's tint applied.
+    pub fn tint_nebula(&self, color: Color) -> Color {
+        color * Vec4::from(self.nebula_tint.as_rgba_f32())
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::CLASSIC
+    }
+}
+
+/// A theme the user saved from the editor or wrote into the themes file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CustomTheme {
+    pub name: String,
+    pub theme: Theme,
+}
+
+/// The user's own themes, kept in their own file next to the settings.
+#[derive(Default)]
+pub struct CustomThemes(pub Vec<CustomTheme>);
+
+impl CustomThemes {
+    fn load() -> Self {
+        let path = match config_path(FILE_NAME) {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+
+        match ron::from_str(&contents) {
+            Ok(themes) => Self(themes),
+            Err(error) => {
+                eprintln!("Ignoring saved themes in {}, {error}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let path = match config_path(FILE_NAME) {
+            Some(path) => path,
+            None => {
+                warn!("Couldn't find a config directory to save themes to");
+                return;
+            }
+        };
+
+        let saved = ron::ser::to_string_pretty(&self.0, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                if let Some(directory) = path.parent() {
+                    fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+                }
+                fs::write(&path, contents).map_err(|error| error.to_string())
+            });
+
+        match saved {
+            Ok(()) => info!("Saved themes to {}", path.display()),
+            Err(error) => warn!("Couldn't save themes to {}, {error}", path.display()),
+        }
+    }
+
+    /// Adds a theme, replacing any existing one with the same name.
+    pub fn insert(&mut self, name: String, theme: Theme) {
+        match self.0.iter_mut().find(|custom| custom.name == name) {
+            Some(custom) => custom.theme = theme,
+            None => self.0.push(CustomTheme { name, theme }),
+        }
+    }
+}
+
+/// Switches to the theme after the current one, going through the presets and then the custom themes.
+/// Starts again from the first preset when the current theme isn't any of them.
+fn cycle_theme(
+    mut actions: EventReader<Action>,
+    custom_themes: Res<CustomThemes>,
+    mut theme: ResMut<Theme>,
+) {
+    for action in actions.iter() {
+        if *action != Action::CycleTheme {
+            continue;
+        }
+
+        let themes: Vec<&Theme> = Theme::PRESETS
+            .iter()
+            .map(|(_, preset)| preset)
+            .chain(custom_themes.0.iter().map(|custom| &custom.theme))
+            .collect();
+        let next = themes
+            .iter()
+            .position(|candidate| **candidate == *theme)
+            .map_or(0, |current| (current + 1) % themes.len());
+        *theme = themes[next].clone();
+    }
+}
+
+/// Pushes the theme's colors onto the clear color and every existing star whenever it changes.
+fn apply_theme(
+    theme: Res<Theme>,
+    mut clear_color: ResMut<ClearColor>,
+    pool: Option<ResMut<MaterialPool>>,
+    materials: Option<ResMut<Assets<StarMaterial>>>,
+    mut query: Query<(&Star, &mut Handle<StarMaterial>)>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    clear_color.0 = theme.clear_color;
+
+    // The 3D field colors its stars itself.
+    let (mut pool, mut materials) = match pool.zip(materials) {
+        Some(pool_and_materials) => pool_and_materials,
+        None => return,
+    };
+    for (star, mut material) in query.iter_mut() {
+        *material = pool.get(theme.star_color(star.tint), &mut materials);
+    }
+}
+
+/// Draws menus and panels light on light themes and dark on dark ones, so they stay readable.
+fn match_menus_to_theme(
+    theme: Res<Theme>,
+    egui_context: Option<ResMut<EguiContext>>,
+    mut shown_light: Local<Option<bool>>,
+) {
+    let mut egui_context = match egui_context {
+        Some(egui_context) => egui_context,
+        None => return,
+    };
+    let light = theme.is_light();
+    if *shown_light == Some(light) {
+        return;
+    }
+
+    let visuals = if light {
+        egui::Visuals::light()
+    } else {
+        egui::Visuals::dark()
+    };
+    egui_context.ctx_mut().set_visuals(visuals);
+    *shown_light = Some(light);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_light_preset_is_light() {
+        assert!(Theme::LIGHT.is_light());
+        for theme in [
+            Theme::CLASSIC,
+            Theme::PHOSPHOR_GREEN,
+            Theme::PHOSPHOR_AMBER,
+            Theme::SYNTHWAVE,
+        ] {
+            assert!(!theme.is_light());
+        }
+    }
+}
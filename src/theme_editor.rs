@@ -0,0 +1,146 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! An in-app window for editing the theme's colors live and saving the result as a preset.
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext, EguiPlugin};
+
+use crate::actions::Action;
+use crate::state;
+use crate::theme::{CustomThemes, Theme};
+
+pub struct ThemeEditorPlugin;
+impl Plugin for ThemeEditorPlugin {
+    fn build(&self, app: &mut App) {
+        // The inspector brings its own egui in debug builds.
+        if !app.world.contains_resource::<EguiContext>() {
+            app.add_plugin(EguiPlugin);
+        }
+
+        app.init_resource::<ThemeEditor>()
+            .add_system(toggle_theme_editor)
+            .add_system(
+                theme_editor_ui
+                    .after(toggle_theme_editor)
+                    .with_run_criteria(state::ui_shown),
+            );
+    }
+}
+
+#[derive(Default)]
+struct ThemeEditor {
+    open: bool,
+    /// What the next saved preset will be called.
+    name: String,
+}
+
+fn toggle_theme_editor(mut actions: EventReader<Action>, mut editor: ResMut<ThemeEditor>) {
+    for action in actions.iter() {
+        if *action == Action::ToggleThemeEditor {
+            editor.open = !editor.open;
+        }
+    }
+}
+
+fn theme_editor_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut editor: ResMut<ThemeEditor>,
+    mut custom_themes: ResMut<CustomThemes>,
+    mut theme: ResMut<Theme>,
+) {
+    if !editor.open {
+        return;
+    }
+
+    // Edit a copy so the theme only counts as changed when something actually changed.
+    let mut edited = theme.clone();
+    let mut open = editor.open;
+
+    egui::Window::new("Theme")
+        .open(&mut open)
+        .resizable(false)
+        .show(egui_context.ctx_mut(), |ui| {
+            egui::ComboBox::from_label("Preset")
+                .selected_text("Load a preset")
+                .show_ui(ui, |ui| {
+                    for (name, preset) in Theme::PRESETS {
+                        if ui.selectable_label(edited == preset, name).clicked() {
+                            edited = preset;
+                        }
+                    }
+                    for custom in &custom_themes.0 {
+                        if ui
+                            .selectable_label(edited == custom.theme, &custom.name)
+                            .clicked()
+                        {
+                            edited = custom.theme.clone();
+                        }
+                    }
+                });
+
+            ui.separator();
+
+            egui::Grid::new("theme_colors").show(ui, |ui| {
+                color_row(ui, "Sky", &mut edited.clear_color);
+                color_row(ui, "Stars", &mut edited.star_color);
+                color_row(ui, "Star tint", &mut edited.star_tint);
+                trail_row(ui, &mut edited);
+                color_row(ui, "Nebula tint", &mut edited.nebula_tint);
+            });
+
+            ui.add(egui::Slider::new(&mut edited.glow, 0.0..=1.0).text("Glow"));
+            ui.add(egui::Slider::new(&mut edited.afterimage_decay, 0.0..=3.0).text("Afterimages"));
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut editor.name);
+
+                let name = editor.name.trim().to_string();
+                if ui
+                    .add_enabled(!name.is_empty(), egui::Button::new("Save as preset"))
+                    .clicked()
+                {
+                    custom_themes.insert(name, edited.clone());
+                    custom_themes.save();
+                    editor.name.clear();
+                }
+            });
+        });
+
+    editor.open = open;
+    if edited != *theme {
+        *theme = edited;
+    }
+}
+
+/// A labelled color picker inside a grid.
+fn color_row(ui: &mut egui::Ui, label: &str, color: &mut Color) {
+    ui.label(label);
+    color_button(ui, color);
+    ui.end_row();
+}
+
+/// Trails either follow their star's color or get one of their own.
+fn trail_row(ui: &mut egui::Ui, theme: &mut Theme) {
+    ui.label("Trails");
+    ui.horizontal(|ui| {
+        let mut own_color = theme.trail_color.is_some();
+        if ui.checkbox(&mut own_color, "Own color").changed() {
+            theme.trail_color = own_color.then_some(theme.star_color);
+        }
+        if let Some(color) = &mut theme.trail_color {
+            color_button(ui, color);
+        }
+    });
+    ui.end_row();
+}
+
+fn color_button(ui: &mut egui::Ui, color: &mut Color) {
+    // egui's picker works in linear space, but themes are written in sRGB.
+    let mut rgba = color.as_linear_rgba_f32();
+    if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+        let [r, g, b, a] = rgba;
+        *color = Color::rgba_linear(r, g, b, a).as_rgba();
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Warp streaks drawn behind moving stars.
+//!
+//! Each trail remembers where its star has been over the last moment of simulated time and is stretched from
+//! the oldest of those positions to the star, so faster stars get longer and brighter streaks on their own.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::transform::TransformSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::settings::StarfieldSettings;
+use crate::speed::SimulationSpeed;
+use crate::theme::Theme;
+use crate::Star;
+
+/// Streaks shorter than this aren't worth drawing, in pixels.
+const MIN_TRAIL_LENGTH: f32 = 2.0;
+
+pub struct TrailPlugin;
+impl Plugin for TrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TrailSettings>()
+            .add_startup_system_to_stage(StartupStage::PreStartup, create_trail_mesh)
+            .add_system(toggle_trails)
+            .add_system_to_stage(CoreStage::PreUpdate, clear_trails)
+            .add_system(add_trails)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                stretch_trails
+                    .after(crate::interpolate_stars)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrailSettings {
+    pub enabled: bool,
+    /// Seconds of movement each trail covers, at the field's current speed.
+    pub duration: f32,
+    /// The longest a trail can get, in pixels. Trails this long are drawn at full opacity.
+    pub max_length: f32,
+    /// Opacity of the longest trails, shorter ones fade out towards nothing.
+    pub opacity: f32,
+}
+
+impl Default for TrailSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration: 0.15,
+            max_length: 400.0,
+            opacity: 0.6,
+        }
+    }
+}
+
+/// A star's streak, with the positions it's stretched across.
+#[derive(Component)]
+pub struct StarTrail {
+    owner: Entity,
+    /// Where the star was and at what point in simulated time, oldest first.
+    history: VecDeque<(f32, Vec2)>,
+    /// The star's age last frame, so a respawned star doesn't streak across the screen to its new spot.
+    last_age: f32,
+}
+
+/// Marks a star that already has a trail.
+#[derive(Component)]
+struct Trailed;
+
+/// The tapered mesh every trail is drawn with, one unit long and wide with the wide end at +x.
+pub struct TrailMesh(pub Handle<Mesh>);
+
+fn create_trail_mesh(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![[0.5, 0.5, 0.0], [0.5, -0.5, 0.0], [-0.5, 0.0, 0.0]],
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 3]);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        vec![[1.0, 0.0], [1.0, 1.0], [0.0, 0.5]],
+    );
+    mesh.set_indices(Some(Indices::U32(vec![0, 2, 1])));
+
+    commands.insert_resource(TrailMesh(meshes.add(mesh)));
+}
+
+fn toggle_trails(mut actions: EventReader<Action>, mut settings: ResMut<StarfieldSettings>) {
+    for action in actions.iter() {
+        if *action == Action::ToggleTrails {
+            settings.trail.enabled = !settings.trail.enabled;
+        }
+    }
+}
+
+/// Removes trails whose star no longer exists, or every trail once they're turned off.
+fn clear_trails(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    stars: Query<Entity, With<Trailed>>,
+    trails: Query<(Entity, &StarTrail)>,
+) {
+    let enabled = settings.trail.enabled;
+
+    for (entity, trail) in trails.iter() {
+        if !enabled || stars.get(trail.owner).is_err() {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    if !enabled {
+        for star in stars.iter() {
+            commands.entity(star).remove::<Trailed>();
+        }
+    }
+}
+
+/// Gives every star without a trail one, hidden until the star has moved far enough to streak.
+fn add_trails(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    mesh: Res<TrailMesh>,
+    query: Query<(Entity, &Star), Without<Trailed>>,
+) {
+    if !settings.trail.enabled {
+        return;
+    }
+
+    for (entity, star) in query.iter() {
+        commands
+            .spawn_bundle(MaterialMesh2dBundle::<StarMaterial> {
+                mesh: mesh.0.clone().into(),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(StarTrail {
+                owner: entity,
+                history: VecDeque::new(),
+                last_age: star.age,
+            });
+
+        commands.entity(entity).insert(Trailed);
+    }
+}
+
+/// Records where each star is now and stretches its trail back to where it was a moment ago.
+#[allow(clippy::too_many_arguments)]
+fn stretch_trails(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    settings: Res<StarfieldSettings>,
+    theme: Res<Theme>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    stars: Query<(&Star, &Transform, &Visibility), Without<StarTrail>>,
+    mut trails: Query<(
+        &mut StarTrail,
+        &mut Transform,
+        &mut Visibility,
+        &mut Handle<StarMaterial>,
+    )>,
+    mut clock: Local<f32>,
+) {
+    // Simulated time rather than real time, so trails hold still while paused and stretch with the speed.
+    *clock += time.delta_seconds() * speed.current * speed.pulse * speed.audio;
+    let now = *clock;
+    let trail_settings = settings.trail;
+
+    for (mut trail, mut transform, mut visibility, mut material) in trails.iter_mut() {
+        let (star, star_transform, star_visibility) = match stars.get(trail.owner) {
+            Ok(star) => star,
+            Err(_) => continue,
+        };
+
+        if star.age < trail.last_age {
+            trail.history.clear();
+        }
+        trail.last_age = star.age;
+
+        // Nothing new to record while the field is paused.
+        let head = star_transform.translation.truncate();
+        let last_recorded = trail.history.back().map(|(recorded, _)| *recorded);
+        if last_recorded != Some(now) {
+            trail.history.push_back((now, head));
+        }
+        while let Some((recorded, _)) = trail.history.front() {
+            if now - recorded <= trail_settings.duration {
+                break;
+            }
+            trail.history.pop_front();
+        }
+
+        let tail = trail.history.front().map_or(head, |(_, tail)| *tail);
+        let offset = head - tail;
+        let length = offset.length().min(trail_settings.max_length);
+
+        let shown = star_visibility.is_visible && length >= MIN_TRAIL_LENGTH;
+        if visibility.is_visible != shown {
+            visibility.is_visible = shown;
+        }
+        if !shown {
+            continue;
+        }
+
+        // The wide end sits on the star and matches its width, tapering off to a point at the tail.
+        let direction = offset / offset.length();
+        let center = head - direction * length / 2.0;
+        *transform = Transform {
+            translation: center.extend(star_transform.translation.z - 0.02),
+            rotation: Quat::from_rotation_z(direction.y.atan2(direction.x)),
+            scale: Vec3::new(length, star_transform.scale.x * 2.0, 1.0),
+        };
+
+        let alpha = trail_settings.opacity * length / trail_settings.max_length
+            * settings.depth_cues.brightness(star.depth);
+        let color = theme.trail_color(star.tint);
+        let faded = pool.get(*color.clone().set_a(alpha), &mut materials);
+        if *material != faded {
+            *material = faded;
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A wormhole mode that wraps the field around the inside of a rotating cylinder rushing past the camera.
+//!
+//! Stars in the normal field fly outwards from the middle, so how far a star is from the middle is used as how
+//! far along the tunnel it has come. The simulation, speed and colors are all shared with the normal field,
+//! only what's drawn changes.
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::depth::depth_to_z;
+use crate::materials::StarMaterial;
+use crate::parallax::ParallaxSystem;
+use crate::settings::StarfieldSettings;
+use crate::sim::Field;
+use crate::spectator::SpectatorSystem;
+use crate::speed::SimulationSpeed;
+
+/// How far away the far end of the tunnel is, in the same units as its radius.
+const TUNNEL_LENGTH: f32 = 4000.0;
+
+/// Distance from the camera to the screen, stars this far down the tunnel are drawn at their usual size.
+const FOCAL_LENGTH: f32 = 400.0;
+
+/// Stars closer than this have already rushed past the camera.
+const NEAR_PLANE: f32 = 20.0;
+
+/// Seconds it takes to blend between the normal field and the tunnel.
+const BLEND_DURATION: f32 = 0.8;
+
+pub struct TunnelPlugin;
+impl Plugin for TunnelPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TunnelSettings>()
+            .init_resource::<Tunnel>()
+            .add_system(spin_tunnel)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_tunnel_view
+                    .after(TransformSystem::TransformPropagate)
+                    .before(SpectatorSystem)
+                    .before(ParallaxSystem),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TunnelSettings {
+    pub enabled: bool,
+    /// Radius of the cylinder the stars sit on.
+    pub radius: f32,
+    /// How fast the tunnel rotates, in radians per second at normal speed.
+    pub rotation: f32,
+    /// How much farther round stars deeper in the tunnel are turned, in radians per unit of depth.
+    pub twist: f32,
+}
+
+impl Default for TunnelSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 300.0,
+            rotation: 0.4,
+            twist: 0.0005,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Tunnel {
+    /// How far the tunnel has turned, in radians.
+    angle: f32,
+    /// How much of the tunnel is shown, 0.0 being the normal field.
+    blend: f32,
+}
+
+impl Tunnel {
+    pub fn in_view(&self) -> bool {
+        self.blend > 0.0
+    }
+}
+
+fn spin_tunnel(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    speed: Res<SimulationSpeed>,
+    mut tunnel: ResMut<Tunnel>,
+) {
+    let delta = time.delta_seconds();
+    let tunnel_settings = settings.tunnel;
+
+    // Rotation follows the field's speed, so pausing stops the spin too.
+    tunnel.angle =
+        (tunnel.angle + tunnel_settings.rotation * speed.current * delta) % std::f32::consts::TAU;
+
+    let step = delta / BLEND_DURATION;
+    tunnel.blend = if tunnel_settings.enabled {
+        (tunnel.blend + step).min(1.0)
+    } else {
+        (tunnel.blend - step).max(0.0)
+    };
+}
+
+/// Moves everything drawn with a star material onto the tunnel wall.
+fn apply_tunnel_view(
+    settings: Res<StarfieldSettings>,
+    tunnel: Res<Tunnel>,
+    field: Res<Field>,
+    mut query: Query<&mut GlobalTransform, With<Handle<StarMaterial>>>,
+) {
+    if !tunnel.in_view() {
+        return;
+    }
+
+    let tunnel_settings = settings.tunnel;
+
+    let extent = field.extent();
+    for mut global_transform in query.iter_mut() {
+        let mut transform = global_transform.compute_transform();
+        let flat = transform.translation.truncate();
+
+        // Stars start at the far end in the middle of the field and reach the camera at its edge.
+        let travelled = (flat.length() / extent).min(1.0);
+        let distance = NEAR_PLANE + (TUNNEL_LENGTH - NEAR_PLANE) * (1.0 - travelled);
+
+        let angle = flat.y.atan2(flat.x) + tunnel.angle + distance * tunnel_settings.twist;
+        let perspective = FOCAL_LENGTH / distance;
+        let projected = Vec2::new(angle.cos(), angle.sin()) * tunnel_settings.radius * perspective;
+
+        let blend = tunnel.blend;
+        let depth = distance / TUNNEL_LENGTH;
+        transform.translation = flat.lerp(projected, blend).extend(depth_to_z(depth));
+        transform.scale *= 1.0 + (perspective - 1.0) * blend;
+        *global_transform = transform.into();
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! Stars flickering as if seen through an atmosphere.
+//!
+//! Each star twinkles at its own pace, and the far away stars near the middle twinkle the most while the
+//! ones streaking past at the edges hardly do at all.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{Field, StarfieldRng};
+use crate::Star;
+
+pub struct TwinklePlugin;
+impl Plugin for TwinklePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<TwinkleSettings>()
+            .register_type::<Twinkle>()
+            .init_resource::<TwinkleBoost>()
+            .add_system(add_twinkles);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TwinkleSettings {
+    pub enabled: bool,
+    /// How much of its brightness a star loses at the bottom of a twinkle, 0.0 to 1.0.
+    pub amplitude: f32,
+    /// How many times a second stars twinkle on average.
+    pub frequency: f32,
+}
+
+impl Default for TwinkleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            amplitude: 0.6,
+            frequency: 1.5,
+        }
+    }
+}
+
+/// Extra twinkling on top of the settings, from 0.0 to 1.0, for effects like reacting to music.
+/// Stars twinkle by this much even while twinkling is turned off.
+#[derive(Default)]
+pub struct TwinkleBoost(pub f32);
+
+impl TwinkleSettings {
+    /// How bright a star is right now, 1.0 being its full brightness.
+    pub fn brightness(
+        &self,
+        twinkle: &Twinkle,
+        field: &Field,
+        position: Vec2,
+        seconds: f32,
+        boost: f32,
+    ) -> f32 {
+        let amplitude = if self.enabled { self.amplitude } else { 0.0 } + boost;
+        if amplitude <= 0.0 {
+            return 1.0;
+        }
+
+        // Two sines at unrelated rates, so the flicker never settles into an obvious rhythm.
+        let angle = TAU * self.frequency * twinkle.frequency * seconds + twinkle.phase;
+        let wave = (angle.sin() + (angle * 2.3 + twinkle.phase).sin()) / 4.0 + 0.5;
+
+        let calm = field
+            .extent_progress(position - field.vanishing_point())
+            .min(1.0);
+        1.0 - amplitude.clamp(0.0, 1.0) * twinkle.amplitude * (1.0 - calm) * wave
+    }
+}
+
+/// How a star twinkles, relative to the settings.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Twinkle {
+    amplitude: f32,
+    frequency: f32,
+    /// Where in its cycle the star started, in radians.
+    phase: f32,
+}
+
+impl Twinkle {
+    fn random(rng: &mut impl Rng) -> Self {
+        Self {
+            amplitude: rng.gen_range(0.5..=1.0),
+            frequency: rng.gen_range(0.5..=1.5),
+            phase: rng.gen_range(0.0..TAU),
+        }
+    }
+}
+
+fn add_twinkles(
+    mut commands: Commands,
+    mut rng: ResMut<StarfieldRng>,
+    query: Query<Entity, (With<Star>, Without<Twinkle>)>,
+) {
+    for entity in query.iter() {
+        commands
+            .entity(entity)
+            .insert(Twinkle::random(&mut rng.effects));
+    }
+}
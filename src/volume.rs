@@ -0,0 +1,370 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A true 3D field, flown through with a perspective camera instead of drawn flat.
+//!
+//! Stars are scattered through a box ahead of the camera and stream towards it, so perspective makes near
+//! stars bigger and faster for free. Once a star is behind the camera it goes back out past the far plane.
+//! The flat field's settings, theme, speed, bindings and star mesh are shared, and stars are placed by the same
+//! [`Field`]: its extent is the box's cross-section, and a star's depth is how far along the box it is.
+
+use bevy::prelude::*;
+use bevy::window::{WindowCreated, WindowResized};
+use rand::Rng;
+
+use crate::actions::ActionsPlugin;
+use crate::bindings::BindingsPlugin;
+use crate::settings::StarfieldSettings;
+use crate::sim::{Field, StarfieldRng};
+use crate::speed::{SimulationSpeed, SpeedPlugin};
+use crate::star_mesh::{StarMesh, StarMeshPlugin};
+use crate::state::StatePlugin;
+use crate::theme::{Theme, ThemePlugin};
+use crate::MainCamera;
+
+/// How far ahead of the camera stars are scattered, in world units.
+const FAR_PLANE: f32 = 2000.0;
+
+/// World units across the box for each unit of the field's extent, wide enough to fill the window at the far plane.
+const SPREAD: f32 = 1.6;
+
+/// How close to the camera's axis stars can be, so none fly straight through the lens and fill the screen.
+const CLEARANCE: f32 = 20.0;
+
+/// Spots tried for a star clear of the camera's axis before the last one is taken anyway.
+const PLACEMENT_ATTEMPTS: usize = 8;
+
+/// World units a second a star flies for each unit of its base speed, about 400 for the default speed range.
+const FLY_SPEED: f32 = 9.0;
+
+/// Radius of an average star, in world units.
+const STAR_SIZE: f32 = 1.5;
+
+/// How many materials the theme's tint range is split into. Stars share them so they draw in batches.
+const TINT_STEPS: usize = 8;
+
+/// How many brightnesses each tint comes in, for dimming stars with distance.
+const SHADE_STEPS: usize = 8;
+
+pub struct VolumePlugin;
+impl Plugin for VolumePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StarfieldSettings>()
+            .init_resource::<StarfieldRng>()
+            .init_resource::<Field>()
+            .add_plugin(StatePlugin { autostart: true })
+            .add_plugin(ActionsPlugin)
+            .add_plugin(BindingsPlugin)
+            .add_plugin(ThemePlugin)
+            .add_plugin(StarMeshPlugin)
+            .add_plugin(SpeedPlugin)
+            .add_startup_system_to_stage(StartupStage::PreStartup, configure_simulation)
+            .add_startup_system_to_stage(StartupStage::PreStartup, create_tint_materials)
+            .add_startup_system(setup)
+            .add_system(configure_field)
+            .add_system(match_star_count.after(configure_field))
+            .add_system(apply_theme)
+            .add_system(fly_stars.after(configure_field));
+    }
+}
+
+/// A star in the 3D field.
+///
+/// Stars are flat discs facing +Z. The camera always looks straight down -Z, so they stay turned towards it
+/// like billboards without having to be rotated.
+#[derive(Component)]
+pub struct VolumeStar {
+    /// How fast the star flies, picked from the field's speed range.
+    base_speed: f32,
+    /// Which step along the theme's tint range the star is colored with.
+    tint: usize,
+    /// Which brightness the star is drawn at, dimmer the further away it is.
+    shade: usize,
+}
+
+/// One unlit material for each step along the theme's tint range, in each brightness.
+struct TintMaterials(Vec<Handle<StandardMaterial>>);
+
+impl TintMaterials {
+    fn get(&self, tint: usize, shade: usize) -> Handle<StandardMaterial> {
+        self.0[tint * SHADE_STEPS + shade].clone()
+    }
+}
+
+/// How far along the theme's tint range a step is.
+fn step_tint(step: usize) -> f32 {
+    step as f32 / (TINT_STEPS - 1) as f32
+}
+
+/// How bright a shade is, 1.0 being the nearest.
+fn step_brightness(step: usize) -> f32 {
+    1.0 - step as f32 / (SHADE_STEPS - 1) as f32
+}
+
+/// The color of a tint in one of its brightnesses.
+fn shaded_color(theme: &Theme, tint: usize, shade: usize) -> Color {
+    let mut color = theme.star_color(step_tint(tint));
+    let alpha = color.a() * step_brightness(shade);
+    color.set_a(alpha);
+    color
+}
+
+/// Which shade a star should be drawn in this far along the box, following the depth cue for brightness.
+fn shade_at(settings: &StarfieldSettings, z: f32) -> usize {
+    let depth = (-z / FAR_PLANE).clamp(0.0, 1.0);
+    let brightness = settings.depth_cues.brightness(depth);
+    ((1.0 - brightness) * (SHADE_STEPS - 1) as f32).round() as usize
+}
+
+/// Hands the field's settings to the simulation before anything is spawned.
+fn configure_simulation(
+    settings: Res<StarfieldSettings>,
+    mut field: ResMut<Field>,
+    mut rng: ResMut<StarfieldRng>,
+) {
+    info!("Generating the field from seed {}", settings.seed);
+    rng.reseed(settings.seed);
+    field.reseed(settings.seed);
+    field.set_depth_distribution(settings.depth_distribution);
+    field.set_shape(settings.field);
+    field.set_spawn_distribution(settings.spawn_distribution);
+}
+
+/// Keeps the field in step with the settings, and its cross-section in step with the window's shape.
+fn configure_field(
+    settings: Res<StarfieldSettings>,
+    windows: Res<Windows>,
+    mut field: ResMut<Field>,
+    mut created: EventReader<WindowCreated>,
+    mut resized: EventReader<WindowResized>,
+) {
+    let window_changed = created.iter().count() + resized.iter().count() > 0;
+    if !settings.is_changed() && !window_changed {
+        return;
+    }
+
+    field.set_shape(settings.field);
+    field.set_depth_distribution(settings.depth_distribution);
+    field.set_spawn_distribution(settings.spawn_distribution);
+    if let Some(window) = windows.get_primary() {
+        if window.width() > 0.0 && window.height() > 0.0 {
+            field.set_aspect_ratio(window.width() / window.height());
+        }
+    }
+}
+
+fn create_tint_materials(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let tints = (0..TINT_STEPS)
+        .flat_map(|tint| (0..SHADE_STEPS).map(move |shade| (tint, shade)))
+        .map(|(tint, shade)| {
+            materials.add(StandardMaterial {
+                base_color: shaded_color(&theme, tint, shade),
+                unlit: true,
+                alpha_mode: AlphaMode::Blend,
+                ..default()
+            })
+        })
+        .collect();
+    commands.insert_resource(TintMaterials(tints));
+}
+
+fn setup(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    field: Res<Field>,
+    star_mesh: Res<StarMesh>,
+    tints: Res<TintMaterials>,
+    mut rng: ResMut<StarfieldRng>,
+) {
+    commands
+        .spawn_bundle(Camera3dBundle {
+            projection: PerspectiveProjection {
+                far: FAR_PLANE * 1.1,
+                ..default()
+            }
+            .into(),
+            ..default()
+        })
+        .insert(MainCamera);
+
+    for _ in 0..settings.star_count {
+        spawn_star(
+            &mut commands,
+            &settings,
+            &field,
+            &star_mesh,
+            &tints,
+            &mut rng.field,
+        );
+    }
+}
+
+fn spawn_star(
+    commands: &mut Commands,
+    settings: &StarfieldSettings,
+    field: &Field,
+    star_mesh: &StarMesh,
+    tints: &TintMaterials,
+    rng: &mut impl Rng,
+) {
+    let size = STAR_SIZE * rng.gen_range(0.5..=1.5);
+    let z = -field.random_depth(rng) * FAR_PLANE;
+    let star = VolumeStar {
+        base_speed: field.random_base_speed(rng),
+        tint: rng.gen_range(0..TINT_STEPS),
+        shade: shade_at(settings, z),
+    };
+
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: star_mesh.0.clone(),
+            material: tints.get(star.tint, star.shade),
+            transform: Transform::from_translation(cross_section(field, rng).extend(z))
+                .with_scale(Vec3::new(size, size, 1.0)),
+            ..default()
+        })
+        .insert(star);
+}
+
+/// Somewhere across the box stars are scattered through, spread out like the flat field and clear of the
+/// camera's axis. Perspective already bunches distant stars towards the middle, so new and recycled stars
+/// are placed the same way.
+fn cross_section(field: &Field, rng: &mut impl Rng) -> Vec2 {
+    let mut position = field.spawn_position(rng) * SPREAD;
+    for _ in 1..PLACEMENT_ATTEMPTS {
+        if position.length() > CLEARANCE {
+            break;
+        }
+        position = field.spawn_position(rng) * SPREAD;
+    }
+    position
+}
+
+/// Adds or removes stars when the star count setting changes.
+#[allow(clippy::too_many_arguments)]
+fn match_star_count(
+    mut commands: Commands,
+    settings: Res<StarfieldSettings>,
+    field: Res<Field>,
+    star_mesh: Res<StarMesh>,
+    tints: Res<TintMaterials>,
+    mut rng: ResMut<StarfieldRng>,
+    stars: Query<Entity, With<VolumeStar>>,
+    mut applied: Local<Option<u32>>,
+) {
+    let wanted = settings.star_count;
+    let previous = applied.replace(wanted);
+    if previous.is_none() || previous == Some(wanted) {
+        return;
+    }
+
+    let count = stars.iter().count();
+    let wanted = wanted as usize;
+    for _ in count..wanted {
+        spawn_star(
+            &mut commands,
+            &settings,
+            &field,
+            &star_mesh,
+            &tints,
+            &mut rng.field,
+        );
+    }
+    for entity in stars.iter().skip(wanted) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Recolors the shared materials whenever the theme changes, the background is left to the theme itself.
+fn apply_theme(
+    theme: Res<Theme>,
+    tints: Res<TintMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    for tint in 0..TINT_STEPS {
+        for shade in 0..SHADE_STEPS {
+            if let Some(material) = materials.get_mut(&tints.get(tint, shade)) {
+                material.base_color = shaded_color(&theme, tint, shade);
+            }
+        }
+    }
+}
+
+/// Streams stars towards the camera, sending them back out past the far plane once they're behind it.
+fn fly_stars(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    speed: Res<SimulationSpeed>,
+    field: Res<Field>,
+    tints: Res<TintMaterials>,
+    mut rng: ResMut<StarfieldRng>,
+    mut query: Query<(
+        &mut VolumeStar,
+        &mut Transform,
+        &mut Handle<StandardMaterial>,
+    )>,
+) {
+    // How far a star with a base speed of 1.0 flies this frame.
+    let step = FLY_SPEED * time.delta_seconds() * speed.current * speed.pulse * speed.audio;
+
+    for (mut star, mut transform, mut material) in query.iter_mut() {
+        transform.translation.z += star.base_speed * step;
+
+        if transform.translation.z > 0.0 {
+            let z = transform.translation.z - FAR_PLANE;
+            transform.translation = cross_section(&field, &mut rng.field).extend(z);
+            star.base_speed = field.random_base_speed(&mut rng.field);
+        }
+
+        let shade = shade_at(&settings, transform.translation.z);
+        if shade != star.shade {
+            star.shade = shade;
+            *material = tints.get(star.tint, shade);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::depth::DepthCues;
+
+    #[test]
+    fn far_stars_are_dimmer() {
+        let mut settings = StarfieldSettings {
+            depth_cues: DepthCues {
+                brightness: 1.0,
+                ..default()
+            },
+            ..default()
+        };
+        assert_eq!(shade_at(&settings, 0.0), 0);
+        assert_eq!(shade_at(&settings, -FAR_PLANE), SHADE_STEPS - 1);
+        assert!(shade_at(&settings, -FAR_PLANE / 4.0) < shade_at(&settings, -FAR_PLANE / 2.0));
+
+        settings.depth_cues.brightness = 0.0;
+        assert_eq!(shade_at(&settings, -FAR_PLANE), 0);
+    }
+
+    #[test]
+    fn stars_are_placed_by_the_field() {
+        let mut field = Field::default();
+        field.set_aspect_ratio(2.0);
+        let extents = field.extents() * SPREAD;
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let position = cross_section(&field, &mut rng);
+            assert!(!position.abs().cmpgt(extents).any(), "{position}");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::capture::{CaptureRequest, CaptureSaved};
+use crate::clock;
+use crate::settings::StarfieldSettings;
+
+/// How long the field runs before a startup export, so stars have spread out and picked up speed.
+const STARTUP_EXPORT_DELAY: f32 = 3.0;
+
+pub struct WallpaperPlugin {
+    /// Exports a wallpaper shortly after startup and quits once it's saved.
+    pub export_and_exit: bool,
+}
+
+impl Plugin for WallpaperPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WallpaperSettings>()
+            .add_system(export_on_action);
+
+        if self.export_and_exit {
+            app.add_system(export_after_delay)
+                .add_system(exit_when_saved);
+        }
+    }
+}
+
+/// Size and look of exported wallpapers.
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WallpaperSettings {
+    pub width: u32,
+    pub height: u32,
+    pub bloom: f32,
+}
+
+impl Default for WallpaperSettings {
+    fn default() -> Self {
+        Self {
+            width: 3840,
+            height: 2160,
+            bloom: 0.8,
+        }
+    }
+}
+
+/// Exports the field as it is right now, as a wallpaper or at the window's size.
+fn export_on_action(
+    mut actions: EventReader<Action>,
+    settings: Res<StarfieldSettings>,
+    windows: Res<Windows>,
+    mut requests: EventWriter<CaptureRequest>,
+) {
+    for action in actions.iter() {
+        match action {
+            Action::ExportWallpaper => requests.send(wallpaper_request(&settings)),
+            Action::Screenshot => {
+                if let Some(window) = windows.get_primary() {
+                    requests.send(screenshot_request(window));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn export_after_delay(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    mut requests: EventWriter<CaptureRequest>,
+    mut exported: Local<bool>,
+) {
+    if !*exported && time.seconds_since_startup() as f32 >= STARTUP_EXPORT_DELAY {
+        requests.send(wallpaper_request(&settings));
+        *exported = true;
+    }
+}
+
+/// Prints where the wallpaper ended up, so scripts can pick it up, and quits.
+fn exit_when_saved(mut saved: EventReader<CaptureSaved>, mut exit: EventWriter<AppExit>) {
+    if let Some(saved) = saved.iter().next() {
+        println!("{}", saved.path.display());
+        exit.send(AppExit);
+    }
+}
+
+fn wallpaper_request(settings: &StarfieldSettings) -> CaptureRequest {
+    let wallpaper = settings.wallpaper;
+
+    CaptureRequest {
+        size: UVec2::new(wallpaper.width, wallpaper.height),
+        path: PathBuf::from(format!(
+            "starfield-wallpaper-{}x{}-{}.png",
+            wallpaper.width,
+            wallpaper.height,
+            timestamp()
+        )),
+        bloom: wallpaper.bloom,
+    }
+}
+
+/// A capture of exactly what's in the window, minus any UI.
+fn screenshot_request(window: &Window) -> CaptureRequest {
+    CaptureRequest {
+        size: UVec2::new(window.physical_width(), window.physical_height()),
+        path: PathBuf::from(format!("starfield-screenshot-{}.png", timestamp())),
+        bloom: 0.0,
+    }
+}
+
+fn timestamp() -> u64 {
+    clock::unix_seconds()
+}
@@ -0,0 +1,195 @@
+// Copyright Quentin Wright 2022, All Rights Reserved.
+
+//! A warp drive that's switched on and off rather than held.
+//!
+//! Engaging it ramps the field up to warp speed along the speed easing curve, stretching stars into lines
+//! and blue-shifting their colors. It stays at warp until disengaged, then ramps back down the same way.
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::Action;
+use crate::materials::{MaterialPool, StarMaterial};
+use crate::parallax::ParallaxSystem;
+use crate::settings::StarfieldSettings;
+use crate::spectator::SpectatorSystem;
+use crate::speed::{Easing, SimulationSpeed};
+use crate::state;
+use crate::Star;
+
+/// The color stars are shifted towards at full warp.
+const BLUE_SHIFT: Color = Color::rgb(0.55, 0.7, 1.0);
+
+pub struct WarpPlugin;
+impl Plugin for WarpPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WarpSettings>()
+            .register_type::<WarpState>()
+            .register_type::<WarpDrive>()
+            .init_resource::<WarpDrive>()
+            .add_system(toggle_warp_drive)
+            .add_system(
+                run_warp_drive
+                    .with_run_criteria(state::running)
+                    .after(toggle_warp_drive),
+            )
+            .add_system(blue_shift.after(run_warp_drive))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                stretch_stars
+                    .after(TransformSystem::TransformPropagate)
+                    .before(SpectatorSystem)
+                    .before(ParallaxSystem),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Reflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WarpSettings {
+    /// How much faster the field moves at warp, as a multiplier.
+    pub speed: f32,
+    /// Seconds it takes to get up to warp speed.
+    pub accelerate: f32,
+    /// Seconds it takes to drop back out of warp.
+    pub decelerate: f32,
+    /// The curve the ramp up and down follows.
+    pub easing: Easing,
+    /// Seconds of movement stars are stretched across at warp.
+    pub stretch: f32,
+    /// How far star colors are shifted towards blue at warp, 0.0 turns it off.
+    pub blue_shift: f32,
+}
+
+impl Default for WarpSettings {
+    fn default() -> Self {
+        Self {
+            speed: 8.0,
+            accelerate: 2.0,
+            decelerate: 1.5,
+            easing: Easing::Cubic,
+            stretch: 0.05,
+            blue_shift: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[reflect_value(PartialEq, Serialize, Deserialize)]
+pub enum WarpState {
+    #[default]
+    Idle,
+    Accelerating,
+    Warp,
+    Decelerating,
+}
+
+#[derive(Clone, Default, Reflect)]
+pub struct WarpDrive {
+    state: WarpState,
+    /// How far between idle and full warp the drive is, before easing.
+    progress: f32,
+    /// How strongly warp is showing right now, after easing.
+    intensity: f32,
+}
+
+impl WarpDrive {
+    pub fn state(&self) -> WarpState {
+        self.state
+    }
+
+    /// How strongly warp is showing, 0.0 when idle and 1.0 at full warp.
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+}
+
+fn toggle_warp_drive(mut actions: EventReader<Action>, mut drive: ResMut<WarpDrive>) {
+    for action in actions.iter() {
+        if *action != Action::ToggleWarpDrive {
+            continue;
+        }
+
+        // Toggling halfway through a ramp turns it around from wherever it got to.
+        drive.state = match drive.state {
+            WarpState::Idle | WarpState::Decelerating => WarpState::Accelerating,
+            WarpState::Accelerating | WarpState::Warp => WarpState::Decelerating,
+        };
+    }
+}
+
+/// Moves the drive along its ramp and sets the speed to match.
+fn run_warp_drive(
+    time: Res<Time>,
+    settings: Res<StarfieldSettings>,
+    mut drive: ResMut<WarpDrive>,
+    mut speed: ResMut<SimulationSpeed>,
+) {
+    let warp = settings.warp;
+    let delta = time.delta_seconds();
+
+    match drive.state {
+        WarpState::Idle | WarpState::Warp => {}
+        WarpState::Accelerating => {
+            drive.progress = (drive.progress + delta / warp.accelerate.max(f32::EPSILON)).min(1.0);
+            if drive.progress >= 1.0 {
+                drive.state = WarpState::Warp;
+            }
+        }
+        WarpState::Decelerating => {
+            drive.progress = (drive.progress - delta / warp.decelerate.max(f32::EPSILON)).max(0.0);
+            if drive.progress <= 0.0 {
+                drive.state = WarpState::Idle;
+            }
+        }
+    }
+
+    drive.intensity = warp.easing.ease(drive.progress);
+    speed.warp = 1.0 + (warp.speed - 1.0) * drive.intensity;
+}
+
+fn blue_shift(
+    settings: Res<StarfieldSettings>,
+    drive: Res<WarpDrive>,
+    mut pool: ResMut<MaterialPool>,
+    mut materials: ResMut<Assets<StarMaterial>>,
+    mut applied: Local<f32>,
+) {
+    let amount = settings.warp.blue_shift * drive.intensity;
+    if amount != *applied {
+        *applied = amount;
+        pool.set_shift(BLUE_SHIFT, amount, &mut materials);
+    }
+}
+
+/// Stretches stars back along the way they're moving into streaks, after transforms are propagated.
+fn stretch_stars(
+    settings: Res<StarfieldSettings>,
+    drive: Res<WarpDrive>,
+    speed: Res<SimulationSpeed>,
+    mut query: Query<(&Star, &mut GlobalTransform)>,
+) {
+    if drive.intensity <= 0.0 {
+        return;
+    }
+
+    let seconds = settings.warp.stretch * drive.intensity * speed.current;
+
+    for (star, mut global_transform) in query.iter_mut() {
+        let mut transform = global_transform.compute_transform();
+        let movement = star.velocity.truncate() * seconds;
+        let length = movement.length();
+        // Stars shrunk away to nothing stay that way.
+        if length <= 0.0 || transform.scale.x <= 0.0 {
+            continue;
+        }
+
+        // The star's mesh has a radius of 1, so its far edge stays put and the rest trails behind.
+        let direction = movement / length;
+        transform.rotation = Quat::from_rotation_z(direction.y.atan2(direction.x));
+        transform.translation -= (movement / 2.0).extend(0.0);
+        transform.scale.x += length / 2.0;
+        *global_transform = transform.into();
+    }
+}